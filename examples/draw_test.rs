@@ -245,7 +245,7 @@ fn handle_window_events(win: &mut Window) {
         .presenter
         .lock()
         .unwrap()
-        .present(cmd_buffer, out_tex.color())
+        .present(cmd_buffer, &out_tex)
         .map(|f| f.then_signal_fence_and_flush().map(|f| f.wait(None)));
 
     if let Err(err) = res {
@@ -269,12 +269,11 @@ fn handle_window_events(win: &mut Window) {
     let cmd_buffer = data.renderer.new_cmd_buffer().unwrap();
     let (cmd_buffer, out_tex) = data.renderer.render(cmd_buffer).unwrap();
 
-    let res = data
-        .presenter
-        .lock()
-        .unwrap()
-        .present(cmd_buffer, out_tex.color())
+    let mut presenter = data.presenter.lock().unwrap();
+    let res = presenter
+        .present(cmd_buffer, &out_tex)
         .map(|f| f.then_signal_fence_and_flush().map(|f| f.wait(None)));
+    presenter.frame_completed();
 
     if let Err(err) = res {
         println!("presenter error: {}", err);
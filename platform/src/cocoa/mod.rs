@@ -68,14 +68,20 @@ pub(crate) fn init_app(
     app_delegate.set_dark_appearance();
     app_delegate.set_default_main_menu(name);
 
-    let instance = Instance::with_loader(
-        FunctionPointers::new({
-            use std::os::raw::c_char;
-            use vk_sys as vk;
-            use vulkano::instance::loader::Loader;
+    let loader = FunctionPointers::new({
+        use std::os::raw::c_char;
+        use vk_sys as vk;
+        use vulkano::instance::loader::Loader;
 
-            Box::new(statically_linked_vulkan_loader!())
-        }),
+        Box::new(statically_linked_vulkan_loader!())
+    });
+
+    // extended color spaces, if available
+    let ext_swapchain_colorspace = InstanceExtensions::supported_by_core_with_loader(&loader)
+        .map_or(false, |ext| ext.ext_swapchain_colorspace);
+
+    let instance = Instance::with_loader(
+        loader,
         Some(&ApplicationInfo {
             application_name: Some(name.into()),
             application_version: Some(Version {
@@ -89,6 +95,7 @@ pub(crate) fn init_app(
         &InstanceExtensions {
             khr_surface: true,
             mvk_macos_surface: true,
+            ext_swapchain_colorspace,
             ..InstanceExtensions::none()
         },
         None,
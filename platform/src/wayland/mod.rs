@@ -138,6 +138,9 @@ pub(crate) fn init_app(
         &InstanceExtensions {
             khr_surface: true,
             khr_wayland_surface: true,
+            // extended color spaces, if available
+            ext_swapchain_colorspace: InstanceExtensions::supported_by_core()
+                .map_or(false, |ext| ext.ext_swapchain_colorspace),
             ..InstanceExtensions::none()
         },
         None,
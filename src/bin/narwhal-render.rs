@@ -0,0 +1,157 @@
+//! Renders a scene file to an image.
+//!
+//! ```text
+//! narwhal-render SCENE -o OUTPUT [--size WIDTHxHEIGHT] [--resolution N] [--time SECONDS]
+//!     [--frame N] [--quiet]
+//! ```
+//!
+//! The output format is determined by the file extension (PNG or EXR). Per-node statistics
+//! (evaluation time, texture allocations, and tessellated vertices) are printed to stderr unless
+//! `--quiet` is given.
+
+use cgmath::Vector2;
+use failure::{format_err, Error};
+use narwhal::node::{defs, Graph};
+use narwhal::render::{offscreen_device, offscreen_instance, Renderer};
+use std::env;
+use std::fs;
+use std::process;
+use std::time::Duration;
+
+const USAGE: &str = "usage: narwhal-render SCENE -o OUTPUT [--size WIDTHxHEIGHT] \
+                     [--resolution N] [--time SECONDS] [--frame N] [--quiet]";
+
+struct Options {
+    scene: String,
+    output: String,
+    size: Option<Vector2<f64>>,
+    resolution: f32,
+    time: f64,
+    frame: u64,
+    quiet: bool,
+}
+
+fn parse_args() -> Result<Options, Error> {
+    let mut scene = None;
+    let mut output = None;
+    let mut size = None;
+    let mut resolution = 1.;
+    let mut time = 0.;
+    let mut frame = 0;
+    let mut quiet = false;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| format_err!("missing value for {}", arg))
+        };
+        match &*arg {
+            "-o" | "--output" => output = Some(value()?),
+            "--size" => {
+                let value = value()?;
+                let mut parts = value.split('x').map(str::parse::<f64>);
+                size = match (parts.next(), parts.next(), parts.next()) {
+                    (Some(Ok(width)), Some(Ok(height)), None) => Some(Vector2::new(width, height)),
+                    _ => return Err(format_err!("invalid size {}", value)),
+                };
+            }
+            "--resolution" => resolution = value()?.parse()?,
+            "--time" => time = value()?.parse()?,
+            "--frame" => frame = value()?.parse()?,
+            "-q" | "--quiet" => quiet = true,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+            _ if scene.is_none() && !arg.starts_with('-') => scene = Some(arg.clone()),
+            _ => return Err(format_err!("unexpected argument {}\n{}", arg, USAGE)),
+        }
+    }
+
+    Ok(Options {
+        scene: scene.ok_or_else(|| format_err!("no scene given\n{}", USAGE))?,
+        output: output.ok_or_else(|| format_err!("no output given\n{}", USAGE))?,
+        size,
+        resolution,
+        time,
+        frame,
+        quiet,
+    })
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs() as f64 * 1000. + f64::from(duration.subsec_nanos()) / 1_000_000.
+}
+
+fn run() -> Result<(), Error> {
+    let options = parse_args()?;
+
+    let instance = offscreen_instance()?;
+    let (device, queue) = offscreen_device(&instance)?;
+    let mut renderer = Renderer::new(Graph::new(), device, queue)?;
+    for node_type in defs::builtin() {
+        renderer.add_node_type(node_type)?;
+    }
+
+    let source = fs::read_to_string(&options.scene)?;
+    let mut graph = Graph::from_scene(&source, renderer.schemas())?;
+
+    if let Some(size) = options.size {
+        let camera = graph.output();
+        graph
+            .node_mut(&camera)
+            .ok_or_else(|| format_err!("scene has no output camera"))?
+            .set(defs::CameraProps::Size.into(), size);
+    }
+
+    *renderer.graph_mut() = graph;
+    renderer.set_resolution(options.resolution);
+    renderer.set_time(options.time);
+    renderer.set_frame(options.frame);
+    renderer.set_profiling(!options.quiet);
+
+    let bitmap = renderer.render_to_bitmap()?;
+
+    if let Some(report) = renderer.report() {
+        eprintln!(
+            "{:>10}  {:>8}  {:>8}  node",
+            "time (ms)", "textures", "vertices"
+        );
+        for stats in report.nodes() {
+            let node_type = renderer
+                .graph()
+                .node(&stats.node)
+                .map_or("(group contents)", |node| node.node_type.as_str());
+            let time = if stats.cached {
+                "cached".to_string()
+            } else {
+                format!("{:.3}", millis(stats.cpu_time))
+            };
+            eprintln!(
+                "{:>10}  {:>8}  {:>8}  #{} {}",
+                time,
+                stats.textures_allocated,
+                stats.vertices_tessellated,
+                stats.node.id(),
+                node_type
+            );
+        }
+        eprintln!(
+            "{:>10.3}  {:>8}  {:>8}  total",
+            millis(report.total_cpu_time()),
+            report.textures_allocated(),
+            report.vertices_tessellated()
+        );
+    }
+
+    bitmap.save(&options.output)?;
+    Ok(())
+}
+
+fn main() {
+    if let Err(err) = run() {
+        eprintln!("narwhal-render: {}", err);
+        process::exit(1);
+    }
+}
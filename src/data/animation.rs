@@ -0,0 +1,254 @@
+//! Keyframe animation.
+
+use crate::data::{morph_paths, morph_weights, Color, Value};
+use cgmath::{InnerSpace, Matrix3, Matrix4, Quaternion, SquareMatrix, Vector3, VectorSpace};
+
+/// Number of bisection steps used to solve bezier easing curves.
+const BEZIER_ITERATIONS: usize = 32;
+
+/// How values are interpolated between a keyframe and the next one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Interpolation {
+    /// Linear interpolation.
+    Linear,
+
+    /// Interpolation eased by a cubic bezier curve from (0, 0) to (1, 1) with the two given
+    /// control points (x1, y1, x2, y2), like CSS `cubic-bezier`. X coordinates should be in the
+    /// range [0, 1].
+    Bezier(f64, f64, f64, f64),
+
+    /// Linear interpolation quantized to the given number of steps, like CSS `steps(n, end)`.
+    Step(u32),
+
+    /// The value is held until the next keyframe.
+    Hold,
+}
+
+impl Default for Interpolation {
+    fn default() -> Interpolation {
+        Interpolation::Linear
+    }
+}
+
+impl Interpolation {
+    /// Maps a linear interpolation factor in [0, 1] to an eased factor.
+    pub fn ease(&self, t: f64) -> f64 {
+        let t = t.max(0.).min(1.);
+        match *self {
+            Interpolation::Linear => t,
+            Interpolation::Bezier(x1, y1, x2, y2) => {
+                // solve x(s) = t for s by bisection, since x is monotonic for x1, x2 in [0, 1]
+                let (mut lower, mut upper) = (0., 1.);
+                for _ in 0..BEZIER_ITERATIONS {
+                    let mid = (lower + upper) / 2.;
+                    if cubic_bezier(x1, x2, mid) < t {
+                        lower = mid;
+                    } else {
+                        upper = mid;
+                    }
+                }
+                cubic_bezier(y1, y2, (lower + upper) / 2.)
+            }
+            Interpolation::Step(steps) => {
+                let steps = f64::from(steps.max(1));
+                (t * steps).floor() / steps
+            }
+            Interpolation::Hold => 0.,
+        }
+    }
+}
+
+/// Evaluates a 1D cubic bezier curve from 0 to 1 with the given control points.
+fn cubic_bezier(p1: f64, p2: f64, t: f64) -> f64 {
+    let u = 1. - t;
+    3. * u * u * t * p1 + 3. * u * t * t * p2 + t * t * t
+}
+
+/// A keyframe.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Keyframe {
+    /// Time in seconds.
+    pub time: f64,
+
+    /// The value at this keyframe.
+    pub value: Value,
+
+    /// Interpolation towards the next keyframe.
+    pub interpolation: Interpolation,
+}
+
+/// A keyframe track that animates a single property.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Track {
+    /// Keyframes, sorted by time.
+    keyframes: Vec<Keyframe>,
+}
+
+impl Track {
+    /// Creates a new empty track.
+    pub fn new() -> Track {
+        Track::default()
+    }
+
+    /// Returns true if there are no keyframes.
+    pub fn is_empty(&self) -> bool {
+        self.keyframes.is_empty()
+    }
+
+    /// Returns all keyframes, sorted by time.
+    pub fn keyframes(&self) -> &[Keyframe] {
+        &self.keyframes
+    }
+
+    /// Inserts a keyframe, replacing any keyframe at the same time.
+    pub fn insert<T: Into<Value>>(&mut self, time: f64, value: T, interpolation: Interpolation) {
+        let keyframe = Keyframe {
+            time,
+            value: value.into(),
+            interpolation,
+        };
+        match self.keyframes.iter().position(|k| k.time >= time) {
+            Some(i) if self.keyframes[i].time == time => self.keyframes[i] = keyframe,
+            Some(i) => self.keyframes.insert(i, keyframe),
+            None => self.keyframes.push(keyframe),
+        }
+    }
+
+    /// Removes the keyframe at the given time and returns it, if it existed.
+    pub fn remove(&mut self, time: f64) -> Option<Keyframe> {
+        let i = self.keyframes.iter().position(|k| k.time == time)?;
+        Some(self.keyframes.remove(i))
+    }
+
+    /// Returns the value at the given time.
+    ///
+    /// Before the first and after the last keyframe, their values are held. Adjacent keyframes
+    /// with values that can not be interpolated (see [interpolate]) behave like
+    /// [Interpolation::Hold].
+    pub fn sample(&self, time: f64) -> Option<Value> {
+        let next = match self.keyframes.iter().position(|k| k.time > time) {
+            Some(0) => return Some(self.keyframes[0].value.clone()),
+            Some(i) => i,
+            None => return self.keyframes.last().map(|k| k.value.clone()),
+        };
+        let a = &self.keyframes[next - 1];
+        let b = &self.keyframes[next];
+
+        let t = a.interpolation.ease((time - a.time) / (b.time - a.time));
+        Some(interpolate(&a.value, &b.value, t).unwrap_or_else(|| a.value.clone()))
+    }
+}
+
+/// Interpolates between two values of the same type.
+///
+/// Supports floats, vectors, colors, matrices, paths, and stroke weights. Matrices are decomposed
+/// into translation, rotation, and scale, which are interpolated separately; shear is not
+/// preserved. Paths and stroke weights are morphed (see [morph_paths]). Returns None for other
+/// types or if the types differ.
+pub fn interpolate(a: &Value, b: &Value, t: f64) -> Option<Value> {
+    Some(match (a, b) {
+        (Value::Float(a), Value::Float(b)) => Value::Float(a + (b - a) * t),
+        (Value::Vec2(a), Value::Vec2(b)) => Value::Vec2(a.lerp(*b, t)),
+        (Value::Vec3(a), Value::Vec3(b)) => Value::Vec3(a.lerp(*b, t)),
+        (Value::Vec4(a), Value::Vec4(b)) => Value::Vec4(a.lerp(*b, t)),
+        (Value::Color(a), Value::Color(b)) => {
+            let t = t as f32;
+            Value::Color(Color {
+                r: a.r + (b.r - a.r) * t,
+                g: a.g + (b.g - a.g) * t,
+                b: a.b + (b.b - a.b) * t,
+                a: a.a + (b.a - a.a) * t,
+            })
+        }
+        (Value::Mat4(a), Value::Mat4(b)) => Value::Mat4(interpolate_matrix(a, b, t)),
+        (Value::Path2D(a), Value::Path2D(b)) => Value::Path2D(morph_paths(a, b, t)),
+        (Value::StrokeWeight(a), Value::StrokeWeight(b)) => {
+            Value::StrokeWeight(morph_weights(a, b, t))
+        }
+        _ => return None,
+    })
+}
+
+/// A matrix decomposed into translation, rotation, and scale.
+struct Decomposed {
+    translation: Vector3<f64>,
+    rotation: Quaternion<f64>,
+    scale: Vector3<f64>,
+}
+
+impl Decomposed {
+    fn new(m: &Matrix4<f64>) -> Decomposed {
+        let translation = m.w.truncate();
+        let (x, y, z) = (m.x.truncate(), m.y.truncate(), m.z.truncate());
+        let mut scale = Vector3::new(x.magnitude(), y.magnitude(), z.magnitude());
+        if Matrix3::from_cols(x, y, z).determinant() < 0. {
+            scale.x = -scale.x;
+        }
+
+        let axis = |v: Vector3<f64>, s: f64| if s == 0. { v } else { v / s };
+        let rotation = Matrix3::from_cols(axis(x, scale.x), axis(y, scale.y), axis(z, scale.z));
+
+        Decomposed {
+            translation,
+            rotation: Quaternion::from(rotation).normalize(),
+            scale,
+        }
+    }
+
+    fn to_matrix(&self) -> Matrix4<f64> {
+        Matrix4::from_translation(self.translation)
+            * Matrix4::from(self.rotation)
+            * Matrix4::from_nonuniform_scale(self.scale.x, self.scale.y, self.scale.z)
+    }
+}
+
+/// Interpolates between two matrices by decomposing them.
+fn interpolate_matrix(a: &Matrix4<f64>, b: &Matrix4<f64>, t: f64) -> Matrix4<f64> {
+    let a = Decomposed::new(a);
+    let b = Decomposed::new(b);
+
+    // take the shorter path
+    let b_rotation = if a.rotation.dot(b.rotation) < 0. {
+        -b.rotation
+    } else {
+        b.rotation
+    };
+
+    Decomposed {
+        translation: a.translation.lerp(b.translation, t),
+        rotation: a.rotation.slerp(b_rotation, t),
+        scale: a.scale.lerp(b.scale, t),
+    }
+    .to_matrix()
+}
+
+#[test]
+fn sampling() {
+    let mut track = Track::new();
+    track.insert(1., 0., Interpolation::Linear);
+    track.insert(3., 4., Interpolation::Hold);
+    track.insert(2., 2., Interpolation::Step(2));
+
+    assert_eq!(track.sample(0.), Some(Value::Float(0.)));
+    assert_eq!(track.sample(1.5), Some(Value::Float(1.)));
+    assert_eq!(track.sample(2.4), Some(Value::Float(2.)));
+    assert_eq!(track.sample(2.6), Some(Value::Float(3.)));
+    assert_eq!(track.sample(5.), Some(Value::Float(4.)));
+
+    let ease = Interpolation::Bezier(0.42, 0., 0.58, 1.);
+    assert!((ease.ease(0.5) - 0.5).abs() < 1e-6);
+    assert!(ease.ease(0.25) < 0.25);
+
+    let a = Matrix4::from_translation(Vector3::new(0., 0., 0.));
+    let b = Matrix4::from_translation(Vector3::new(2., 4., 0.)) * Matrix4::from_scale(3.);
+    let mid = interpolate(&Value::Mat4(a), &Value::Mat4(b), 0.5);
+    let expected = Matrix4::from_translation(Vector3::new(1., 2., 0.)) * Matrix4::from_scale(2.);
+    match mid {
+        Some(Value::Mat4(mid)) => {
+            for i in 0..4 {
+                assert!((mid[i] - expected[i]).magnitude() < 1e-9);
+            }
+        }
+        _ => panic!("expected a matrix"),
+    }
+}
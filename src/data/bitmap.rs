@@ -0,0 +1,472 @@
+//! Bitmap image decoding and encoding.
+
+use crate::data::{ACES_CG, LINEAR_SRGB, SRGB};
+use lcms_prime::pixel_format::RGBA;
+use lcms_prime::{Intent, Profile, Transform};
+use std::io::Cursor;
+use std::path::Path;
+use std::{fmt, fs, io};
+
+/// Bitmap decoding errors.
+#[derive(Debug, Fail)]
+pub enum BitmapError {
+    /// The file could not be read.
+    #[fail(display = "could not read image: {}", _0)]
+    Io(io::Error),
+
+    /// The data is not in a known image format.
+    #[fail(display = "unknown image format")]
+    UnknownFormat,
+
+    /// The image could not be decoded.
+    #[fail(display = "failed to decode {} image: {}", _0, _1)]
+    Decode(BitmapFormat, String),
+
+    /// The image has a pixel format that can’t be converted.
+    #[fail(display = "unsupported pixel format: {}", _0)]
+    UnsupportedPixelFormat(String),
+
+    /// The embedded color profile is invalid or can’t be converted to ACEScg.
+    #[fail(display = "color profile error: {}", _0)]
+    Profile(String),
+
+    /// The image could not be encoded.
+    #[fail(display = "failed to encode {} image: {}", _0, _1)]
+    Encode(BitmapFormat, String),
+}
+
+impl From<io::Error> for BitmapError {
+    fn from(err: io::Error) -> BitmapError {
+        BitmapError::Io(err)
+    }
+}
+
+/// Supported bitmap file formats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BitmapFormat {
+    Png,
+    Jpeg,
+    Exr,
+}
+
+impl BitmapFormat {
+    /// Guesses the file format from the first few bytes.
+    pub fn from_magic(data: &[u8]) -> Option<BitmapFormat> {
+        if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+            Some(BitmapFormat::Png)
+        } else if data.starts_with(&[0xff, 0xd8, 0xff]) {
+            Some(BitmapFormat::Jpeg)
+        } else if data.starts_with(&[0x76, 0x2f, 0x31, 0x01]) {
+            Some(BitmapFormat::Exr)
+        } else {
+            None
+        }
+    }
+}
+
+impl BitmapFormat {
+    /// Guesses the file format from a file extension.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<BitmapFormat> {
+        let extension = path.as_ref().extension()?.to_str()?.to_lowercase();
+        match &*extension {
+            "png" => Some(BitmapFormat::Png),
+            "jpg" | "jpeg" => Some(BitmapFormat::Jpeg),
+            "exr" => Some(BitmapFormat::Exr),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for BitmapFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BitmapFormat::Png => write!(f, "PNG"),
+            BitmapFormat::Jpeg => write!(f, "JPEG"),
+            BitmapFormat::Exr => write!(f, "EXR"),
+        }
+    }
+}
+
+/// A decoded bitmap in linear ACEScg with premultiplied alpha.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bitmap {
+    /// Width in pixels.
+    pub width: u32,
+
+    /// Height in pixels.
+    pub height: u32,
+
+    /// RGBA pixels, row by row, starting at the top left.
+    pub pixels: Vec<f32>,
+}
+
+/// Straight-alpha RGBA pixels that have not been color-managed yet.
+struct RawPixels {
+    width: u32,
+    height: u32,
+    pixels: Vec<f32>,
+    icc_profile: Option<Vec<u8>>,
+    premultiplied: bool,
+}
+
+impl Bitmap {
+    /// Reads and decodes an image file.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Bitmap, BitmapError> {
+        Bitmap::decode(&fs::read(path)?)
+    }
+
+    /// Decodes an image from memory.
+    ///
+    /// PNG and JPEG images are assumed to be sRGB if they have no embedded color profile, and EXR
+    /// images are assumed to have linear Rec. 709 primaries.
+    pub fn decode(data: &[u8]) -> Result<Bitmap, BitmapError> {
+        let format = match BitmapFormat::from_magic(data) {
+            Some(format) => format,
+            None => return Err(BitmapError::UnknownFormat),
+        };
+
+        let raw = match format {
+            BitmapFormat::Png => decode_png(data),
+            BitmapFormat::Jpeg => decode_jpeg(data),
+            BitmapFormat::Exr => decode_exr(data),
+        }
+        .map_err(|err| match err {
+            DecodeError::Decode(err) => BitmapError::Decode(format, err),
+            DecodeError::Unsupported(err) => BitmapError::UnsupportedPixelFormat(err),
+        })?;
+
+        let profile = match &raw.icc_profile {
+            Some(icc_profile) => Profile::deser(&mut Cursor::new(icc_profile))
+                .map_err(|err| BitmapError::Profile(format!("{:?}", err)))?,
+            None => match format {
+                BitmapFormat::Png | BitmapFormat::Jpeg => SRGB.clone(),
+                BitmapFormat::Exr => LINEAR_SRGB.clone(),
+            },
+        };
+
+        let transform: Transform<RGBA<f32>, RGBA<f32>> =
+            Transform::new(&profile, &ACES_CG, Intent::Perceptual)
+                .map_err(BitmapError::Profile)?;
+
+        let mut pixels = Vec::new();
+        pixels.resize(raw.pixels.len(), 0.);
+
+        if raw.premultiplied {
+            // color transforms need straight alpha
+            let mut straight = raw.pixels;
+            for pixel in straight.chunks_mut(4) {
+                if pixel[3] > 0. {
+                    pixel[0] /= pixel[3];
+                    pixel[1] /= pixel[3];
+                    pixel[2] /= pixel[3];
+                }
+            }
+            transform.convert(&straight, &mut pixels);
+        } else {
+            transform.convert(&raw.pixels, &mut pixels);
+        }
+
+        for pixel in pixels.chunks_mut(4) {
+            pixel[0] *= pixel[3];
+            pixel[1] *= pixel[3];
+            pixel[2] *= pixel[3];
+        }
+
+        Ok(Bitmap {
+            width: raw.width,
+            height: raw.height,
+            pixels,
+        })
+    }
+}
+
+impl Bitmap {
+    /// Converts the pixels to the given color profile with straight alpha.
+    fn convert_to(&self, profile: &Profile) -> Result<Vec<f32>, BitmapError> {
+        let transform: Transform<RGBA<f32>, RGBA<f32>> =
+            Transform::new(&ACES_CG, profile, Intent::Perceptual).map_err(BitmapError::Profile)?;
+
+        let mut straight = self.pixels.clone();
+        for pixel in straight.chunks_mut(4) {
+            if pixel[3] > 0. {
+                pixel[0] /= pixel[3];
+                pixel[1] /= pixel[3];
+                pixel[2] /= pixel[3];
+            }
+        }
+
+        let mut pixels = Vec::new();
+        pixels.resize(straight.len(), 0.);
+        transform.convert(&straight, &mut pixels);
+        Ok(pixels)
+    }
+
+    /// Returns the largest absolute difference between corresponding channel values, or None if
+    /// the bitmaps differ in size. Useful for comparing renders against a reference.
+    pub fn max_difference(&self, other: &Bitmap) -> Option<f32> {
+        if (self.width, self.height) != (other.width, other.height) {
+            return None;
+        }
+        Some(
+            self.pixels
+                .iter()
+                .zip(&other.pixels)
+                .map(|(a, b)| (a - b).abs())
+                .fold(0., f32::max),
+        )
+    }
+
+    /// Converts the pixels to 8-bit sRGB with straight alpha, row by row.
+    pub fn to_srgb8(&self) -> Result<Vec<u8>, BitmapError> {
+        Ok(self
+            .convert_to(&SRGB)?
+            .into_iter()
+            .map(|x| (x.max(0.).min(1.) * 255.).round() as u8)
+            .collect())
+    }
+
+    /// Encodes and writes an image file. The format is determined by the file extension.
+    ///
+    /// PNG images are written as 8-bit sRGB, and EXR images are written with linear Rec. 709
+    /// primaries and premultiplied alpha, matching the assumptions made by [Bitmap::decode].
+    /// Writing JPEG images is not supported.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), BitmapError> {
+        let path = path.as_ref();
+        match BitmapFormat::from_path(path) {
+            Some(BitmapFormat::Png) => {
+                let data = self.to_srgb8()?;
+                let file = io::BufWriter::new(fs::File::create(path)?);
+                let mut encoder = png::Encoder::new(file, self.width, self.height);
+                encoder.set_color(png::ColorType::Rgba);
+                encoder.set_depth(png::BitDepth::Eight);
+                encoder
+                    .write_header()
+                    .and_then(|mut writer| writer.write_image_data(&data))
+                    .map_err(|err| BitmapError::Encode(BitmapFormat::Png, err.to_string()))
+            }
+            Some(BitmapFormat::Exr) => {
+                let mut pixels = self.convert_to(&LINEAR_SRGB)?;
+                for pixel in pixels.chunks_mut(4) {
+                    pixel[0] *= pixel[3];
+                    pixel[1] *= pixel[3];
+                    pixel[2] *= pixel[3];
+                }
+
+                let width = self.width as usize;
+                exr::prelude::write_rgba_file(path, width, self.height as usize, |x, y| {
+                    let i = (y * width + x) * 4;
+                    (pixels[i], pixels[i + 1], pixels[i + 2], pixels[i + 3])
+                })
+                .map_err(|err| BitmapError::Encode(BitmapFormat::Exr, err.to_string()))
+            }
+            Some(format) => Err(BitmapError::Encode(format, "unsupported".into())),
+            None => Err(BitmapError::UnknownFormat),
+        }
+    }
+}
+
+enum DecodeError {
+    Decode(String),
+    Unsupported(String),
+}
+
+fn decode_png(data: &[u8]) -> Result<RawPixels, DecodeError> {
+    let mut decoder = png::Decoder::new(Cursor::new(data));
+    decoder.set_transformations(png::Transformations::EXPAND);
+    let mut reader = decoder
+        .read_info()
+        .map_err(|err| DecodeError::Decode(err.to_string()))?;
+
+    let mut buf = Vec::new();
+    buf.resize(reader.output_buffer_size(), 0);
+    let info = reader
+        .next_frame(&mut buf)
+        .map_err(|err| DecodeError::Decode(err.to_string()))?;
+
+    let channels = match info.color_type {
+        png::ColorType::Grayscale => 1,
+        png::ColorType::GrayscaleAlpha => 2,
+        png::ColorType::Rgb => 3,
+        png::ColorType::Rgba => 4,
+        ty => return Err(DecodeError::Unsupported(format!("{:?}", ty))),
+    };
+
+    let samples: Vec<f32> = match info.bit_depth {
+        png::BitDepth::Eight => buf.iter().map(|x| *x as f32 / 255.).collect(),
+        png::BitDepth::Sixteen => buf
+            .chunks(2)
+            .map(|x| ((x[0] as u16) << 8 | x[1] as u16) as f32 / 65535.)
+            .collect(),
+        depth => return Err(DecodeError::Unsupported(format!("{:?}", depth))),
+    };
+
+    Ok(RawPixels {
+        width: info.width,
+        height: info.height,
+        pixels: expand_to_rgba(&samples, channels),
+        icc_profile: reader.info().icc_profile.as_ref().map(|p| p.to_vec()),
+        premultiplied: false,
+    })
+}
+
+fn decode_jpeg(data: &[u8]) -> Result<RawPixels, DecodeError> {
+    let mut decoder = jpeg_decoder::Decoder::new(Cursor::new(data));
+    let buf = decoder
+        .decode()
+        .map_err(|err| DecodeError::Decode(err.to_string()))?;
+    let info = decoder.info().unwrap();
+
+    let channels = match info.pixel_format {
+        jpeg_decoder::PixelFormat::L8 => 1,
+        jpeg_decoder::PixelFormat::RGB24 => 3,
+        format => return Err(DecodeError::Unsupported(format!("{:?}", format))),
+    };
+
+    let samples: Vec<f32> = buf.iter().map(|x| *x as f32 / 255.).collect();
+
+    Ok(RawPixels {
+        width: info.width as u32,
+        height: info.height as u32,
+        pixels: expand_to_rgba(&samples, channels),
+        icc_profile: decoder.icc_profile(),
+        premultiplied: false,
+    })
+}
+
+fn decode_exr(data: &[u8]) -> Result<RawPixels, DecodeError> {
+    use exr::prelude::*;
+
+    let image = read()
+        .no_deep_data()
+        .largest_resolution_level()
+        .specific_channels()
+        .required("R")
+        .required("G")
+        .required("B")
+        .optional("A", 1.0_f32)
+        .collect_pixels(
+            |resolution, _| {
+                let mut pixels = Vec::new();
+                pixels.resize(resolution.width() * resolution.height() * 4, 0.);
+                (resolution.width(), pixels)
+            },
+            |(width, pixels): &mut (usize, Vec<f32>),
+             position,
+             (r, g, b, a): (f32, f32, f32, f32)| {
+                let index = (position.y() * *width + position.x()) * 4;
+                pixels[index] = r;
+                pixels[index + 1] = g;
+                pixels[index + 2] = b;
+                pixels[index + 3] = a;
+            },
+        )
+        .first_valid_layer()
+        .all_attributes()
+        .from_buffered(Cursor::new(data))
+        .map_err(|err| DecodeError::Decode(err.to_string()))?;
+
+    let size = image.layer_data.size;
+    let (_, pixels) = image.layer_data.channel_data.pixels;
+
+    Ok(RawPixels {
+        width: size.width() as u32,
+        height: size.height() as u32,
+        pixels,
+        icc_profile: None,
+        // EXR images are premultiplied by convention
+        premultiplied: true,
+    })
+}
+
+/// Expands gray, gray+alpha, or RGB samples to RGBA.
+fn expand_to_rgba(samples: &[f32], channels: usize) -> Vec<f32> {
+    let mut pixels = Vec::with_capacity(samples.len() / channels * 4);
+    for pixel in samples.chunks(channels) {
+        match channels {
+            1 => pixels.extend_from_slice(&[pixel[0], pixel[0], pixel[0], 1.]),
+            2 => pixels.extend_from_slice(&[pixel[0], pixel[0], pixel[0], pixel[1]]),
+            3 => pixels.extend_from_slice(&[pixel[0], pixel[1], pixel[2], 1.]),
+            _ => pixels.extend_from_slice(&pixel[0..4]),
+        }
+    }
+    pixels
+}
+
+/// Creates a minimal ICC profile with sRGB primaries and a linear transfer function.
+#[cfg(test)]
+fn linear_srgb_icc() -> Vec<u8> {
+    fn s15f16(data: &mut Vec<u8>, value: f64) {
+        data.extend_from_slice(&((value * 65536.).round() as i32).to_be_bytes());
+    }
+    fn xyz_tag(x: f64, y: f64, z: f64) -> Vec<u8> {
+        let mut data = b"XYZ \0\0\0\0".to_vec();
+        s15f16(&mut data, x);
+        s15f16(&mut data, y);
+        s15f16(&mut data, z);
+        data
+    }
+
+    // an empty curve is the identity
+    let curve = b"curv\0\0\0\0\0\0\0\0".to_vec();
+    let tags: Vec<(&[u8; 4], Vec<u8>)> = vec![
+        (b"wtpt", xyz_tag(0.9642, 1., 0.8249)),
+        (b"rXYZ", xyz_tag(0.4361, 0.2225, 0.0139)),
+        (b"gXYZ", xyz_tag(0.3851, 0.7169, 0.0971)),
+        (b"bXYZ", xyz_tag(0.1431, 0.0606, 0.7141)),
+        (b"rTRC", curve.clone()),
+        (b"gTRC", curve.clone()),
+        (b"bTRC", curve),
+    ];
+
+    let mut table = Vec::new();
+    let mut tag_data = Vec::new();
+    let data_start = 128 + 4 + tags.len() * 12;
+    for (signature, data) in &tags {
+        table.extend_from_slice(&signature[..]);
+        table.extend_from_slice(&((data_start + tag_data.len()) as u32).to_be_bytes());
+        table.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        tag_data.extend_from_slice(data);
+    }
+
+    let mut header = Vec::new();
+    header.extend_from_slice(&((data_start + tag_data.len()) as u32).to_be_bytes());
+    header.extend_from_slice(&[0; 4]);
+    header.extend_from_slice(&[2, 0x10, 0, 0]);
+    header.extend_from_slice(b"mntrRGB XYZ ");
+    header.extend_from_slice(&[0; 12]);
+    header.extend_from_slice(b"acsp");
+    header.extend_from_slice(&[0; 28]);
+    s15f16(&mut header, 0.9642);
+    s15f16(&mut header, 1.);
+    s15f16(&mut header, 0.8249);
+    header.resize(128, 0);
+
+    let mut profile = header;
+    profile.extend_from_slice(&(tags.len() as u32).to_be_bytes());
+    profile.extend_from_slice(&table);
+    profile.extend_from_slice(&tag_data);
+    profile
+}
+
+#[test]
+fn png_icc_profile() {
+    let mut info = png::Info::with_size(1, 1);
+    info.color_type = png::ColorType::Rgba;
+    info.bit_depth = png::BitDepth::Eight;
+    info.icc_profile = Some(linear_srgb_icc().into());
+
+    let mut data = Vec::new();
+    {
+        let encoder = png::Encoder::with_info(&mut data, info).unwrap();
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_image_data(&[128, 128, 128, 255]).unwrap();
+        writer.finish().unwrap();
+    }
+
+    // decoded as sRGB, this gray would be about 0.22
+    let bitmap = Bitmap::decode(&data).unwrap();
+    for channel in &bitmap.pixels[0..3] {
+        assert!((channel - 0.5).abs() < 0.01, "{:?}", bitmap.pixels);
+    }
+    assert_eq!(bitmap.pixels[3], 1.);
+}
@@ -39,6 +39,37 @@ lazy_static! {
 
     /// The sRGB color profile.
     pub static ref SRGB: Profile = Profile::new_srgb();
+
+    /// The sRGB color profile with a linear tone curve.
+    pub static ref LINEAR_SRGB: Profile = Profile::new_rgb(
+        CIExyY {
+            x: 0.3127,
+            y: 0.3290,
+            Y: 1.,
+        },
+        CIExyYTriple {
+            red: CIExyY {
+                x: 0.64,
+                y: 0.33,
+                Y: 1.,
+            },
+            green: CIExyY {
+                x: 0.30,
+                y: 0.60,
+                Y: 1.,
+            },
+            blue: CIExyY {
+                x: 0.15,
+                y: 0.06,
+                Y: 1.,
+            },
+        },
+        [
+            ToneCurve::new_gamma(1.).unwrap(),
+            ToneCurve::new_gamma(1.).unwrap(),
+            ToneCurve::new_gamma(1.).unwrap(),
+        ]
+    ).unwrap();
 }
 
 /// An ACEScg RGBA color.
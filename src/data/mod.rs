@@ -1,18 +1,28 @@
 //! Data types and definitions.
 
+mod animation;
+mod bitmap;
 mod camera;
 pub mod cgmath_ext;
 mod color;
 mod drawable;
+mod morph;
 mod path;
 mod shape;
+mod source;
+mod text;
 mod value;
 mod weight;
 
+pub use self::animation::*;
+pub use self::bitmap::*;
 pub use self::camera::*;
 pub use self::color::*;
 pub use self::drawable::*;
+pub use self::morph::*;
 pub use self::path::*;
 pub use self::shape::*;
+pub use self::source::*;
+pub use self::text::*;
 pub use self::value::*;
 pub use self::weight::*;
@@ -0,0 +1,348 @@
+//! Path and stroke weight interpolation.
+
+use crate::data::{Path2D, Path2DCmd, StrokeWeight, WeightCmd};
+use cgmath::{InnerSpace, Vector2, Vector3, VectorSpace};
+use std::mem;
+
+/// A point type that contours may be made of.
+trait Point: InnerSpace<Scalar = f64> + PartialEq {}
+
+impl<V: InnerSpace<Scalar = f64> + PartialEq> Point for V {}
+
+/// A contiguous run of cubic bézier segments.
+#[derive(Debug, Clone)]
+struct Contour<V> {
+    start: V,
+    segments: Vec<(V, V, V)>,
+    closed: bool,
+}
+
+impl<V: Point> Contour<V> {
+    fn new(start: V) -> Contour<V> {
+        Contour {
+            start,
+            segments: Vec::new(),
+            closed: false,
+        }
+    }
+
+    fn end(&self) -> V {
+        self.segments.last().map_or(self.start, |s| s.2)
+    }
+
+    fn line_to(&mut self, p: V) {
+        let a = self.end();
+        self.segments.push((a.lerp(p, 1. / 3.), a.lerp(p, 2. / 3.), p));
+    }
+
+    fn quad_to(&mut self, c: V, p: V) {
+        // degree elevation
+        let a = self.end();
+        self.segments.push((a.lerp(c, 2. / 3.), p.lerp(c, 2. / 3.), p));
+    }
+
+    fn cubic_to(&mut self, c1: V, c2: V, p: V) {
+        self.segments.push((c1, c2, p));
+    }
+
+    /// Closes the contour with an explicit segment back to the start, if necessary.
+    fn close(&mut self) {
+        if self.end() != self.start {
+            let start = self.start;
+            self.line_to(start);
+        }
+        self.closed = true;
+    }
+
+    /// Returns a contour with the same number of segments that is collapsed to a single point.
+    fn collapsed(&self, point: V) -> Contour<V> {
+        Contour {
+            start: point,
+            segments: self.segments.iter().map(|_| (point, point, point)).collect(),
+            closed: self.closed,
+        }
+    }
+
+    /// Returns the start point of a segment.
+    fn segment_start(&self, i: usize) -> V {
+        if i == 0 {
+            self.start
+        } else {
+            self.segments[i - 1].2
+        }
+    }
+
+    /// Splits the longest segments in half until the contour has the given number of segments.
+    fn subdivide_to(&mut self, count: usize) {
+        while self.segments.len() < count {
+            if self.segments.is_empty() {
+                let start = self.start;
+                self.segments.push((start, start, start));
+                continue;
+            }
+
+            let longest = (0..self.segments.len())
+                .map(|i| (i, (self.segments[i].2 - self.segment_start(i)).magnitude2()))
+                .fold((0, -1.), |a, b| if b.1 > a.1 { b } else { a })
+                .0;
+
+            // de Casteljau at t = 0.5
+            let a = self.segment_start(longest);
+            let (b, c, d) = self.segments[longest];
+            let ab = a.lerp(b, 0.5);
+            let bc = b.lerp(c, 0.5);
+            let cd = c.lerp(d, 0.5);
+            let abc = ab.lerp(bc, 0.5);
+            let bcd = bc.lerp(cd, 0.5);
+            let mid = abc.lerp(bcd, 0.5);
+            self.segments[longest] = (ab, abc, mid);
+            self.segments.insert(longest + 1, (bcd, cd, d));
+        }
+    }
+
+    /// Rotates the segments of a closed contour so that it starts at the given segment.
+    fn rotated(&self, offset: usize) -> Contour<V> {
+        let mut segments = self.segments.clone();
+        segments.rotate_left(offset);
+        Contour {
+            start: self.segment_start(offset),
+            segments,
+            closed: self.closed,
+        }
+    }
+
+    /// Returns the sum of squared distances between corresponding points.
+    fn distance2(&self, other: &Contour<V>) -> f64 {
+        (0..self.segments.len())
+            .map(|i| (self.segment_start(i) - other.segment_start(i)).magnitude2())
+            .sum()
+    }
+
+    fn lerp(&self, other: &Contour<V>, t: f64) -> Contour<V> {
+        Contour {
+            start: self.start.lerp(other.start, t),
+            segments: self
+                .segments
+                .iter()
+                .zip(other.segments.iter())
+                .map(|(a, b)| (a.0.lerp(b.0, t), a.1.lerp(b.1, t), a.2.lerp(b.2, t)))
+                .collect(),
+            closed: if t < 0.5 { self.closed } else { other.closed },
+        }
+    }
+}
+
+/// Brings two contours into point correspondence.
+fn match_contours<V: Point>(a: &Contour<V>, b: &Contour<V>) -> (Contour<V>, Contour<V>) {
+    let mut a = a.clone();
+    let mut b = b.clone();
+    let count = a.segments.len().max(b.segments.len());
+    a.subdivide_to(count);
+    b.subdivide_to(count);
+
+    if a.closed && b.closed {
+        // pick the starting point that minimizes travel
+        let best = (0..count)
+            .map(|offset| (offset, a.distance2(&b.rotated(offset))))
+            .fold((0, std::f64::INFINITY), |x, y| if y.1 < x.1 { y } else { x })
+            .0;
+        b = b.rotated(best);
+    }
+
+    (a, b)
+}
+
+/// Morphs two lists of contours, collapsing contours without a counterpart to a point.
+fn morph_contours<V: Point>(a: &[Contour<V>], b: &[Contour<V>], t: f64) -> Vec<Contour<V>> {
+    (0..a.len().max(b.len()))
+        .map(|i| {
+            let (a, b) = match (a.get(i), b.get(i)) {
+                (Some(a), Some(b)) => match_contours(a, b),
+                (Some(a), None) => (a.clone(), a.collapsed(a.start)),
+                (None, Some(b)) => (b.collapsed(b.start), b.clone()),
+                (None, None) => unreachable!(),
+            };
+            a.lerp(&b, t)
+        })
+        .collect()
+}
+
+/// Converts a path to contours.
+fn path_contours(path: &Path2D) -> Vec<Contour<Vector2<f64>>> {
+    let mut contours = Vec::new();
+    let mut current: Option<Contour<Vector2<f64>>> = None;
+    let mut last_start = Vector2::new(0., 0.);
+
+    for command in path.commands() {
+        if let Path2DCmd::JumpTo(p) = command {
+            contours.extend(current.take());
+            current = Some(Contour::new(*p));
+            last_start = *p;
+            continue;
+        }
+
+        let contour = current.get_or_insert_with(|| Contour::new(last_start));
+        match command {
+            Path2DCmd::JumpTo(_) => unreachable!(),
+            Path2DCmd::LineTo(p) => contour.line_to(*p),
+            Path2DCmd::QuadTo(c, p) => contour.quad_to(*c, *p),
+            Path2DCmd::CubicTo(c1, c2, p) => contour.cubic_to(*c1, *c2, *p),
+            Path2DCmd::CloseShape => {
+                contour.close();
+                contours.extend(current.take());
+            }
+        }
+    }
+
+    contours.extend(current);
+    contours
+}
+
+/// Returns true if both paths have the same command structure.
+fn same_structure(a: &Path2D, b: &Path2D) -> bool {
+    a.commands().len() == b.commands().len()
+        && a.commands()
+            .iter()
+            .zip(b.commands().iter())
+            .all(|(a, b)| mem::discriminant(a) == mem::discriminant(b))
+}
+
+/// Interpolates between two paths.
+///
+/// If both paths have the same command structure, corresponding points are interpolated
+/// directly. Otherwise, both paths are converted to cubic bézier segments, which are subdivided
+/// until corresponding subpaths have the same number of segments. Closed subpaths are rotated so
+/// that corresponding points are close to each other, and subpaths without a counterpart grow
+/// from or shrink to their starting point.
+pub fn morph_paths(a: &Path2D, b: &Path2D, t: f64) -> Path2D {
+    if same_structure(a, b) {
+        let lerp = |a: &Vector2<f64>, b: &Vector2<f64>| a.lerp(*b, t);
+        return a
+            .commands()
+            .iter()
+            .zip(b.commands().iter())
+            .map(|(a, b)| match (a, b) {
+                (Path2DCmd::JumpTo(a), Path2DCmd::JumpTo(b)) => Path2DCmd::JumpTo(lerp(a, b)),
+                (Path2DCmd::LineTo(a), Path2DCmd::LineTo(b)) => Path2DCmd::LineTo(lerp(a, b)),
+                (Path2DCmd::QuadTo(a1, a2), Path2DCmd::QuadTo(b1, b2)) => {
+                    Path2DCmd::QuadTo(lerp(a1, b1), lerp(a2, b2))
+                }
+                (Path2DCmd::CubicTo(a1, a2, a3), Path2DCmd::CubicTo(b1, b2, b3)) => {
+                    Path2DCmd::CubicTo(lerp(a1, b1), lerp(a2, b2), lerp(a3, b3))
+                }
+                _ => Path2DCmd::CloseShape,
+            })
+            .collect::<Vec<_>>()
+            .into();
+    }
+
+    let mut commands = Vec::new();
+    for contour in morph_contours(&path_contours(a), &path_contours(b), t) {
+        commands.push(Path2DCmd::JumpTo(contour.start));
+        for (c1, c2, p) in contour.segments {
+            commands.push(Path2DCmd::CubicTo(c1, c2, p));
+        }
+        if contour.closed {
+            commands.push(Path2DCmd::CloseShape);
+        }
+    }
+    commands.into()
+}
+
+/// Converts a stroke weight profile to a contour. The first command is the starting point.
+fn weight_contour(weight: &StrokeWeight) -> Vec<Contour<Vector3<f64>>> {
+    let mut commands = weight.commands().iter();
+    let start = match commands.next() {
+        Some(WeightCmd::LineTo(p))
+        | Some(WeightCmd::QuadTo(_, p))
+        | Some(WeightCmd::CubicTo(_, _, p)) => *p,
+        None => return Vec::new(),
+    };
+
+    let mut contour = Contour::new(start);
+    for command in commands {
+        match command {
+            WeightCmd::LineTo(p) => contour.line_to(*p),
+            WeightCmd::QuadTo(c, p) => contour.quad_to(*c, *p),
+            WeightCmd::CubicTo(c1, c2, p) => contour.cubic_to(*c1, *c2, *p),
+        }
+    }
+    vec![contour]
+}
+
+/// Interpolates between two stroke weight profiles (see [morph_paths]).
+pub fn morph_weights(a: &StrokeWeight, b: &StrokeWeight, t: f64) -> StrokeWeight {
+    let same_structure = a.commands().len() == b.commands().len()
+        && a.commands()
+            .iter()
+            .zip(b.commands().iter())
+            .all(|(a, b)| mem::discriminant(a) == mem::discriminant(b));
+
+    if same_structure {
+        let mut out = a.clone();
+        let mut points = Vec::new();
+        b.clone().remap_points(&mut |p| points.push(*p));
+        let mut points = points.into_iter();
+        out.remap_points(&mut |p| *p = p.lerp(points.next().unwrap(), t));
+        return out;
+    }
+
+    let mut commands = Vec::new();
+    for contour in morph_contours(&weight_contour(a), &weight_contour(b), t) {
+        commands.push(WeightCmd::LineTo(contour.start));
+        for (c1, c2, p) in contour.segments {
+            commands.push(WeightCmd::CubicTo(c1, c2, p));
+        }
+    }
+    commands.into()
+}
+
+#[test]
+fn morphing() {
+    let square: Path2D = vec![
+        Path2DCmd::JumpTo(Vector2::new(0., 0.)),
+        Path2DCmd::LineTo(Vector2::new(2., 0.)),
+        Path2DCmd::LineTo(Vector2::new(2., 2.)),
+        Path2DCmd::LineTo(Vector2::new(0., 2.)),
+        Path2DCmd::CloseShape,
+    ]
+    .into();
+    let moved: Path2D = vec![
+        Path2DCmd::JumpTo(Vector2::new(2., 0.)),
+        Path2DCmd::LineTo(Vector2::new(4., 0.)),
+        Path2DCmd::LineTo(Vector2::new(4., 2.)),
+        Path2DCmd::LineTo(Vector2::new(2., 2.)),
+        Path2DCmd::CloseShape,
+    ]
+    .into();
+    let mid = morph_paths(&square, &moved, 0.5);
+    assert_eq!(mid.commands()[0], Path2DCmd::JumpTo(Vector2::new(1., 0.)));
+    assert_eq!(mid.commands().len(), 5);
+
+    let triangle: Path2D = vec![
+        Path2DCmd::JumpTo(Vector2::new(2., 0.)),
+        Path2DCmd::LineTo(Vector2::new(2., 2.)),
+        Path2DCmd::LineTo(Vector2::new(0., 0.)),
+        Path2DCmd::CloseShape,
+    ]
+    .into();
+    assert_eq!(morph_paths(&square, &triangle, 0.).flatten_to_verts().len(), 1);
+    let end = morph_paths(&square, &triangle, 1.);
+    // the square has four segments including the implicit closing line
+    assert_eq!(end.commands().len(), 6);
+    match end.commands()[0] {
+        Path2DCmd::JumpTo(p) => assert!(p == Vector2::new(0., 0.) || p == Vector2::new(2., 0.)),
+        _ => panic!("expected a jump"),
+    }
+
+    let constant = StrokeWeight::constant();
+    let tapered: StrokeWeight = vec![
+        WeightCmd::LineTo(Vector3::new(0., 0., 0.)),
+        WeightCmd::LineTo(Vector3::new(0.5, 1., 0.)),
+        WeightCmd::LineTo(Vector3::new(1., 0., 0.)),
+    ]
+    .into();
+    let mid = morph_weights(&constant, &tapered, 0.5);
+    assert_eq!(mid.commands().len(), 3);
+    assert_eq!(mid.commands()[0], WeightCmd::LineTo(Vector3::new(0., 0.5, 0.)));
+}
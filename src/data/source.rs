@@ -0,0 +1,47 @@
+//! Identification of file sources.
+
+use crate::data::Value;
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::time::SystemTime;
+
+/// Identifies the source of loaded file data, such as an image or a font, so that it only needs
+/// to be loaded again if it changed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SourceKey {
+    /// A file path and its modification date, if available.
+    Path(String, Option<SystemTime>),
+
+    /// A hash of raw file data and its length.
+    Raw(u64, usize),
+}
+
+impl SourceKey {
+    /// Creates a key for a file path (as a string) or raw file data. Returns None for other
+    /// values.
+    ///
+    /// Files are identified by their modification date, so this reads the file’s metadata.
+    pub fn from_value(value: &Value) -> Option<SourceKey> {
+        match value {
+            Value::String(path) => Some(SourceKey::Path(
+                path.clone(),
+                fs::metadata(path).and_then(|m| m.modified()).ok(),
+            )),
+            Value::Raw(data) => {
+                let mut hasher = DefaultHasher::new();
+                data.hash(&mut hasher);
+                Some(SourceKey::Raw(hasher.finish(), data.len()))
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns true if this is a file path, which may change without the value changing.
+    pub fn is_path(&self) -> bool {
+        match self {
+            SourceKey::Path(..) => true,
+            SourceKey::Raw(..) => false,
+        }
+    }
+}
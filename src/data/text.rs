@@ -0,0 +1,413 @@
+use crate::data::{Path2D, Path2DCmd};
+use cgmath::Vector2;
+use rusttype::{GlyphId, Point, Scale, Segment};
+use std::path::Path;
+use std::sync::Arc;
+use std::{fmt, fs, io};
+
+/// Font loading errors.
+#[derive(Debug, Fail)]
+pub enum FontError {
+    /// Failed to read the font file.
+    #[fail(display = "failed to read font: {}", _0)]
+    Io(io::Error),
+
+    /// The font data could not be parsed.
+    #[fail(display = "failed to parse font: {}", _0)]
+    Parse(String),
+}
+
+impl From<io::Error> for FontError {
+    fn from(this: io::Error) -> FontError {
+        FontError::Io(this)
+    }
+}
+
+/// A TrueType or OpenType font.
+///
+/// Shaping is limited to per-character glyph lookup with pair kerning, which is sufficient for
+/// Latin and similar scripts. Scripts that need contextual shaping will not render correctly.
+#[derive(Clone)]
+pub struct Font {
+    font: Arc<rusttype::Font<'static>>,
+}
+
+impl fmt::Debug for Font {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Font {{ glyphs: {} }}", self.font.glyph_count())
+    }
+}
+
+/// Horizontal text alignment relative to the origin.
+#[repr(i32)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextAlign {
+    /// Lines start at the origin.
+    Left = 0,
+
+    /// Lines are centered on the origin.
+    Center = 1,
+
+    /// Lines end at the origin.
+    Right = 2,
+}
+
+impl TextAlign {
+    /// Returns the alignment with the given numeric value.
+    pub fn from_index(index: i32) -> Option<TextAlign> {
+        match index {
+            0 => Some(TextAlign::Left),
+            1 => Some(TextAlign::Center),
+            2 => Some(TextAlign::Right),
+            _ => None,
+        }
+    }
+}
+
+impl Default for TextAlign {
+    fn default() -> TextAlign {
+        TextAlign::Left
+    }
+}
+
+/// Text layout parameters.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextLayout {
+    /// The font size (em height).
+    pub size: f64,
+
+    /// Horizontal alignment.
+    pub align: TextAlign,
+
+    /// If set, lines will be wrapped at whitespace so that they fit within this width.
+    pub max_width: Option<f64>,
+
+    /// Line spacing as a multiple of the font’s natural line height.
+    pub line_height: f64,
+}
+
+impl Default for TextLayout {
+    fn default() -> TextLayout {
+        TextLayout {
+            size: 16.,
+            align: TextAlign::Left,
+            max_width: None,
+            line_height: 1.,
+        }
+    }
+}
+
+impl Font {
+    /// Loads a font from a file.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Font, FontError> {
+        Font::from_bytes(fs::read(path)?)
+    }
+
+    /// Loads a font from TTF or OTF data.
+    pub fn from_bytes(data: Vec<u8>) -> Result<Font, FontError> {
+        match rusttype::Font::from_bytes(data) {
+            Ok(font) => Ok(Font {
+                font: Arc::new(font),
+            }),
+            Err(err) => Err(FontError::Parse(err.to_string())),
+        }
+    }
+
+    /// Returns the width of a single line of text.
+    pub fn measure(&self, text: &str, size: f64) -> f64 {
+        let scale = Scale::uniform(size as f32);
+        let mut width = 0.;
+        let mut prev = None;
+        for c in text.chars() {
+            let glyph = self.font.glyph(c).scaled(scale);
+            if let Some(prev) = prev {
+                width += self.font.pair_kerning(scale, prev, glyph.id());
+            }
+            width += glyph.h_metrics().advance_width;
+            prev = Some(glyph.id());
+        }
+        width as f64
+    }
+
+    /// Breaks text into lines, honoring explicit line breaks and wrapping at whitespace if a
+    /// maximum width is set.
+    fn break_lines(&self, text: &str, layout: &TextLayout) -> Vec<String> {
+        let mut lines = Vec::new();
+
+        for paragraph in text.lines() {
+            let max_width = match layout.max_width {
+                Some(max_width) => max_width,
+                None => {
+                    lines.push(paragraph.to_string());
+                    continue;
+                }
+            };
+
+            let mut line = String::new();
+            for word in paragraph.split(' ') {
+                if line.is_empty() {
+                    line.push_str(word);
+                    continue;
+                }
+
+                let candidate = format!("{} {}", line, word);
+                if self.measure(candidate.trim_end(), layout.size) > max_width {
+                    lines.push(line);
+                    line = word.to_string();
+                } else {
+                    line = candidate;
+                }
+            }
+            lines.push(line);
+        }
+
+        lines
+    }
+
+    /// Lays out text and returns glyph outlines, one path per visible glyph.
+    ///
+    /// The origin is at the top of the first line, and the y axis points down.
+    pub fn layout(&self, text: &str, layout: &TextLayout) -> Vec<Path2D> {
+        let scale = Scale::uniform(layout.size as f32);
+        let v_metrics = self.font.v_metrics(scale);
+        let line_advance = (v_metrics.ascent - v_metrics.descent + v_metrics.line_gap) as f64
+            * layout.line_height;
+
+        let mut paths = Vec::new();
+
+        for (i, line) in self.break_lines(text, layout).iter().enumerate() {
+            let line = line.trim_end();
+            let baseline = v_metrics.ascent as f64 + i as f64 * line_advance;
+            let mut x = match layout.align {
+                TextAlign::Left => 0.,
+                TextAlign::Center => -self.measure(line, layout.size) / 2.,
+                TextAlign::Right => -self.measure(line, layout.size),
+            };
+
+            let mut prev: Option<GlyphId> = None;
+            for c in line.chars() {
+                let glyph = self.font.glyph(c).scaled(scale);
+                if let Some(prev) = prev {
+                    x += self.font.pair_kerning(scale, prev, glyph.id()) as f64;
+                }
+
+                if let Some(contours) = glyph.shape() {
+                    let origin = Vector2::new(x, baseline);
+                    let path = glyph_path(&contours, origin);
+                    if !path.commands().is_empty() {
+                        paths.push(path);
+                    }
+                }
+
+                x += glyph.h_metrics().advance_width as f64;
+                prev = Some(glyph.id());
+            }
+        }
+
+        paths
+    }
+}
+
+/// Converts glyph contours (y up) to a path (y down) at the given baseline origin.
+fn glyph_path(contours: &[rusttype::Contour], origin: Vector2<f64>) -> Path2D {
+    let point = |p: Point<f32>| Vector2::new(origin.x + p.x as f64, origin.y - p.y as f64);
+
+    let mut path = Path2D::new();
+    let commands = path.commands_mut();
+    for contour in contours {
+        let start = match contour.segments.first() {
+            Some(Segment::Line(line)) => line.p[0],
+            Some(Segment::Curve(curve)) => curve.p[0],
+            None => continue,
+        };
+        commands.push(Path2DCmd::JumpTo(point(start)));
+        for segment in &contour.segments {
+            commands.push(match segment {
+                Segment::Line(line) => Path2DCmd::LineTo(point(line.p[1])),
+                Segment::Curve(curve) => {
+                    Path2DCmd::QuadTo(point(curve.p[1]), point(curve.p[2]))
+                }
+            });
+        }
+        commands.push(Path2DCmd::CloseShape);
+    }
+    path
+}
+
+/// Builds a minimal TrueType font with an em of 1000 units, an ascent of 800 and a descent of
+/// 200. Space and the lowercase letters advance by half an em, and the letters are squares of
+/// half an em sitting on the baseline.
+#[cfg(test)]
+fn test_font() -> Font {
+    fn u16s(data: &mut Vec<u8>, values: &[u16]) {
+        for value in values {
+            data.extend_from_slice(&value.to_be_bytes());
+        }
+    }
+
+    const GLYPHS: u16 = 28;
+    const ADVANCE: u16 = 500;
+
+    // a format 4 subtable mapping space to glyph 1 and a-z to glyphs 2-27
+    let mut cmap = Vec::new();
+    u16s(&mut cmap, &[0, 1, 3, 1, 0, 12]);
+    u16s(&mut cmap, &[4, 40, 0, 6, 4, 1, 2]);
+    u16s(&mut cmap, &[0x20, 0x7a, 0xffff, 0, 0x20, 0x61, 0xffff]);
+    u16s(
+        &mut cmap,
+        &[1u16.wrapping_sub(0x20), 2u16.wrapping_sub(0x61), 1],
+    );
+    u16s(&mut cmap, &[0, 0, 0]);
+
+    // .notdef and space are empty
+    let mut glyf = Vec::new();
+    let mut offsets = vec![0, 0, 0];
+    for _ in 2..GLYPHS {
+        u16s(&mut glyf, &[1, 0, 0, ADVANCE, ADVANCE, 3, 0]);
+        glyf.extend_from_slice(&[1; 4]);
+        u16s(&mut glyf, &[0, 0, ADVANCE, 0]);
+        u16s(&mut glyf, &[0, ADVANCE, 0, ADVANCE.wrapping_neg()]);
+        offsets.push(glyf.len() as u16 / 2);
+    }
+    let mut loca = Vec::new();
+    u16s(&mut loca, &offsets);
+
+    let mut head = Vec::new();
+    u16s(&mut head, &[1, 0, 1, 0, 0, 0, 0x5f0f, 0x3cf5, 0, 1000]);
+    u16s(&mut head, &[0; 8]);
+    u16s(&mut head, &[0, 0, ADVANCE, ADVANCE, 0, 8, 2, 0, 0]);
+
+    let mut hhea = Vec::new();
+    u16s(
+        &mut hhea,
+        &[1, 0, 800, 200u16.wrapping_neg(), 0, ADVANCE, 0, 0, ADVANCE],
+    );
+    u16s(&mut hhea, &[1, 0, 0, 0, 0, 0, 0, 0, GLYPHS]);
+
+    let mut hmtx = Vec::new();
+    for _ in 0..GLYPHS {
+        u16s(&mut hmtx, &[ADVANCE, 0]);
+    }
+
+    let mut maxp = Vec::new();
+    u16s(&mut maxp, &[0, 0x5000, GLYPHS]);
+
+    let tables = [
+        (b"cmap", cmap),
+        (b"glyf", glyf),
+        (b"head", head),
+        (b"hhea", hhea),
+        (b"hmtx", hmtx),
+        (b"loca", loca),
+        (b"maxp", maxp),
+    ];
+    let mut data = Vec::new();
+    u16s(&mut data, &[1, 0, tables.len() as u16, 64, 2, 48]);
+    let mut contents = Vec::new();
+    for (tag, table) in &tables {
+        let offset = 12 + 16 * tables.len() + contents.len();
+        data.extend_from_slice(*tag);
+        data.extend_from_slice(&[0; 4]); // checksums are not verified
+        data.extend_from_slice(&(offset as u32).to_be_bytes());
+        data.extend_from_slice(&(table.len() as u32).to_be_bytes());
+        contents.extend_from_slice(table);
+        while contents.len() % 4 != 0 {
+            contents.push(0);
+        }
+    }
+    data.extend(contents);
+
+    Font::from_bytes(data).unwrap()
+}
+
+/// Returns the horizontal and vertical extent of a path.
+#[cfg(test)]
+fn path_bounds(path: &Path2D) -> ((f64, f64), (f64, f64)) {
+    let inf = std::f64::INFINITY;
+    let (mut x, mut y) = ((inf, -inf), (inf, -inf));
+    for cmd in path.commands() {
+        let points = match *cmd {
+            Path2DCmd::JumpTo(p) | Path2DCmd::LineTo(p) => vec![p],
+            Path2DCmd::QuadTo(a, b) => vec![a, b],
+            _ => Vec::new(),
+        };
+        for p in points {
+            x = (x.0.min(p.x), x.1.max(p.x));
+            y = (y.0.min(p.y), y.1.max(p.y));
+        }
+    }
+    (x, y)
+}
+
+#[test]
+fn measure() {
+    let font = test_font();
+    assert_eq!(font.measure("", 10.), 0.);
+    assert_eq!(font.measure("ab", 10.), 10.);
+    assert_eq!(font.measure("ab cd", 20.), 50.);
+}
+
+#[test]
+fn line_breaking() {
+    let font = test_font();
+    let layout = TextLayout {
+        size: 10.,
+        max_width: Some(25.),
+        ..TextLayout::default()
+    };
+    assert_eq!(
+        font.break_lines("ab cd ef\ng", &layout),
+        vec!["ab cd", "ef", "g"]
+    );
+
+    // words wider than the maximum width get a line of their own
+    assert_eq!(
+        font.break_lines("a bcdefg h", &layout),
+        vec!["a", "bcdefg", "h"]
+    );
+
+    let layout = TextLayout {
+        max_width: None,
+        ..layout
+    };
+    assert_eq!(
+        font.break_lines("ab cd ef\ng", &layout),
+        vec!["ab cd ef", "g"]
+    );
+}
+
+#[test]
+fn layout() {
+    let font = test_font();
+    let mut layout = TextLayout {
+        size: 10.,
+        max_width: Some(25.),
+        line_height: 2.,
+        ..TextLayout::default()
+    };
+
+    // spaces have no outline
+    let paths = font.layout("ab cd\nef", &layout);
+    assert_eq!(paths.len(), 6);
+
+    let bounds: Vec<_> = paths.iter().map(path_bounds).collect();
+    assert_eq!(bounds[0], ((0., 5.), (3., 8.)));
+    assert_eq!(bounds[1], ((5., 10.), (3., 8.)));
+    assert_eq!(bounds[2], ((15., 20.), (3., 8.)));
+    assert_eq!(bounds[4], ((0., 5.), (23., 28.)));
+
+    for &(align, first_line, second_line) in &[
+        (TextAlign::Left, 0., 0.),
+        (TextAlign::Center, -12.5, -5.),
+        (TextAlign::Right, -25., -10.),
+    ] {
+        layout.align = align;
+        let paths = font.layout("ab cd\nef", &layout);
+        assert_eq!(path_bounds(&paths[0]).0, (first_line, first_line + 5.));
+        assert_eq!(
+            path_bounds(&paths[3]).0,
+            (first_line + 20., first_line + 25.)
+        );
+        assert_eq!(path_bounds(&paths[4]).0, (second_line, second_line + 5.));
+    }
+}
@@ -1,8 +1,12 @@
 //! Node type definitions.
 
+mod schema;
+
+pub use self::schema::*;
+
 use crate::data::{Camera, TryFromValue, Value};
 use crate::node::NodeRef;
-use crate::render::{Context, TexturePool, TextureRef};
+use crate::render::{Context, NodeTextures, TexturePool, TextureRef};
 use failure::Error;
 use fnv::FnvHashMap;
 use std::any::Any;
@@ -58,10 +62,27 @@ conflate_errors_into_eval_error! {
 
 pub type EvalResult<T> = Result<T, EvalError>;
 
+/// Information about the frame being evaluated.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EvalContext {
+    /// Current time in seconds.
+    pub time: f64,
+
+    /// Current frame number.
+    pub frame: u64,
+
+    /// Time since the previous frame in seconds. Zero for the first frame.
+    pub delta: f64,
+
+    /// Rendering resolution.
+    pub resolution: f32,
+}
+
 /// Node input values.
 pub struct Input {
     pub(crate) values: FnvHashMap<usize, Vec<Arc<Value>>>,
     pub(crate) node: NodeRef,
+    pub(crate) context: EvalContext,
 }
 
 impl Input {
@@ -105,6 +126,12 @@ impl Input {
     pub fn node(&self) -> NodeRef {
         self.node
     }
+
+    /// Returns the evaluation context. Nodes that depend on it should not be pure (see
+    /// [DataNode::is_pure]).
+    pub fn context(&self) -> EvalContext {
+        self.context
+    }
 }
 
 /// Texture allocation errors.
@@ -119,6 +146,8 @@ pub enum TexAllocError {
 pub struct NodeContext<'a> {
     pub(crate) context: Context,
     pub(crate) tex_pool: &'a mut TexturePool,
+    pub(crate) textures: &'a mut NodeTextures,
+    pub(crate) tessellated_vertices: &'a mut usize,
 }
 
 impl<'a> NodeContext<'a> {
@@ -130,6 +159,12 @@ impl<'a> NodeContext<'a> {
         self.context.resolution
     }
 
+    /// Records the number of vertices tessellated by this node, for profiling (see
+    /// [crate::render::RenderReport]).
+    pub fn record_tessellation(&mut self, vertices: usize) {
+        *self.tessellated_vertices += vertices;
+    }
+
     /// Allocates a storage texture from the texture pool.
     pub fn new_storage_texture(
         &mut self,
@@ -142,6 +177,21 @@ impl<'a> NodeContext<'a> {
             .map_err(|e| TexAllocError::Internal(e))
     }
 
+    /// Uploads RGBA pixels (linear ACEScg, premultiplied alpha) into a storage texture from the
+    /// texture pool.
+    pub fn upload_texture(
+        &mut self,
+        cmd_buffer: AutoCommandBufferBuilder,
+        px_width: u32,
+        px_height: u32,
+        resolution: f32,
+        pixels: &[f32],
+    ) -> Result<(AutoCommandBufferBuilder, TextureRef), TexAllocError> {
+        self.tex_pool
+            .upload(cmd_buffer, px_width, px_height, resolution, pixels)
+            .map_err(|e| TexAllocError::Internal(e))
+    }
+
     /// Allocates an attachment texture from the texture pool.
     pub fn new_attachment(
         &mut self,
@@ -153,6 +203,41 @@ impl<'a> NodeContext<'a> {
             .attachment(width, height, resolution)
             .map_err(|e| TexAllocError::Internal(e))
     }
+
+    /// Returns a storage texture owned by this node for the given purpose.
+    ///
+    /// The texture persists across renders and is only reallocated when its size or resolution
+    /// changes. Textures that aren’t requested during an evaluation are released.
+    ///
+    /// The returned flag is true if the texture still holds what this node wrote into it when it
+    /// was last evaluated with the same inputs and context, in which case writing it again may be
+    /// skipped. Otherwise, its version is incremented (see [TextureRef::version]) so that nodes
+    /// using it will be re-evaluated.
+    pub fn cached_texture(
+        &mut self,
+        purpose: &'static str,
+        width: f32,
+        height: f32,
+        resolution: f32,
+    ) -> Result<(TextureRef, bool), TexAllocError> {
+        self.textures
+            .get(self.tex_pool, purpose, width, height, resolution, false)
+            .map_err(|e| TexAllocError::Internal(e))
+    }
+
+    /// Returns an attachment texture owned by this node for the given purpose (see
+    /// [NodeContext::cached_texture]).
+    pub fn cached_attachment(
+        &mut self,
+        purpose: &'static str,
+        width: f32,
+        height: f32,
+        resolution: f32,
+    ) -> Result<(TextureRef, bool), TexAllocError> {
+        self.textures
+            .get(self.tex_pool, purpose, width, height, resolution, true)
+            .map_err(|e| TexAllocError::Internal(e))
+    }
 }
 
 /// Node outputs.
@@ -200,6 +285,13 @@ pub trait GraphicsNode: Send + Sync {
     /// region of an input texture is required.
     fn map_context(&self, _context: &mut Context) {}
 
+    /// If true, this node will only be considered dirty if its inputs or context change.
+    ///
+    /// True by default.
+    fn is_pure(&self) -> bool {
+        true
+    }
+
     /// Clears caches, if any.
     fn clear_caches(&mut self) {}
 }
@@ -212,6 +304,11 @@ pub trait SharedDataType: Send + Sync {
     /// Creates a new node.
     fn create(&mut self) -> Box<dyn DataNode>;
 
+    /// Returns the port schema. Empty by default.
+    fn schema(&self) -> NodeSchema {
+        NodeSchema::default()
+    }
+
     /// Clears caches, if any.
     fn clear_caches(&mut self) {}
 }
@@ -224,6 +321,11 @@ pub trait SharedGraphicsType: Send + Sync {
     /// Creates a new node.
     fn create(&mut self) -> Box<dyn GraphicsNode>;
 
+    /// Returns the port schema. Empty by default.
+    fn schema(&self) -> NodeSchema {
+        NodeSchema::default()
+    }
+
     /// Clears caches, if any.
     fn clear_caches(&mut self) {}
 }
@@ -0,0 +1,231 @@
+use crate::data::{Value, ValueType};
+use std::collections::HashMap;
+
+/// Node type schemas by node type name.
+///
+/// Renderers keep a registry of the node types added to them (see
+/// [crate::render::Renderer::schemas]); [crate::node::defs::builtin_schemas] returns one for the
+/// built-in node types that can be used without a renderer.
+#[derive(Debug, Clone, Default)]
+pub struct SchemaRegistry {
+    schemas: HashMap<String, NodeSchema>,
+}
+
+impl SchemaRegistry {
+    /// Creates an empty registry.
+    pub fn new() -> SchemaRegistry {
+        SchemaRegistry::default()
+    }
+
+    /// Adds or replaces the schema of a node type.
+    pub fn insert(&mut self, node_type: &str, schema: NodeSchema) {
+        self.schemas.insert(node_type.into(), schema);
+    }
+
+    /// Returns the schema of a node type.
+    pub fn get(&self, node_type: &str) -> Option<&NodeSchema> {
+        self.schemas.get(node_type)
+    }
+
+    /// Returns true if the registry contains a schema for the node type.
+    pub fn contains(&self, node_type: &str) -> bool {
+        self.schemas.contains_key(node_type)
+    }
+
+    /// Iterates over all node type names and their schemas.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &NodeSchema)> {
+        self.schemas
+            .iter()
+            .map(|(name, schema)| (name.as_str(), schema))
+    }
+}
+
+/// Port direction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PortDirection {
+    In,
+    Out,
+}
+
+/// The number of links an input port accepts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Multiplicity {
+    /// At most one link.
+    One,
+
+    /// Any number of links.
+    Many,
+}
+
+/// Describes a single node property.
+///
+/// Inputs and outputs have separate index spaces, so a node may have an input and an output with
+/// the same index.
+#[derive(Debug, Clone)]
+pub struct PortSchema {
+    /// The property index.
+    pub index: usize,
+
+    /// A stable name.
+    pub name: String,
+
+    /// Whether this is an input or an output.
+    pub direction: PortDirection,
+
+    /// Accepted value types for inputs, or produced value types for outputs. Empty if any type
+    /// is allowed.
+    pub types: Vec<ValueType>,
+
+    /// The number of links this port accepts. Outputs may always have any number of links.
+    pub multiplicity: Multiplicity,
+
+    /// The value used if an input is neither linked nor set.
+    pub default: Option<Value>,
+
+    /// A human-readable description.
+    pub description: String,
+}
+
+impl PortSchema {
+    fn new(index: usize, name: &str, direction: PortDirection, types: &[ValueType]) -> PortSchema {
+        PortSchema {
+            index,
+            name: name.into(),
+            direction,
+            types: types.to_vec(),
+            multiplicity: match direction {
+                PortDirection::In => Multiplicity::One,
+                PortDirection::Out => Multiplicity::Many,
+            },
+            default: None,
+            description: String::new(),
+        }
+    }
+
+    /// Creates an input port that accepts a single link.
+    pub fn input<K: Into<usize>>(index: K, name: &str, types: &[ValueType]) -> PortSchema {
+        PortSchema::new(index.into(), name, PortDirection::In, types)
+    }
+
+    /// Creates an output port.
+    pub fn output<K: Into<usize>>(index: K, name: &str, types: &[ValueType]) -> PortSchema {
+        PortSchema::new(index.into(), name, PortDirection::Out, types)
+    }
+
+    /// Allows any number of links to this port.
+    pub fn many(mut self) -> PortSchema {
+        self.multiplicity = Multiplicity::Many;
+        self
+    }
+
+    /// Sets the default value.
+    pub fn with_default<T: Into<Value>>(mut self, value: T) -> PortSchema {
+        self.default = Some(value.into());
+        self
+    }
+
+    /// Sets the description.
+    pub fn with_description(mut self, description: &str) -> PortSchema {
+        self.description = description.into();
+        self
+    }
+
+    /// Returns true if the port accepts or produces the given type.
+    pub fn accepts(&self, value_type: ValueType) -> bool {
+        self.types.is_empty() || self.types.contains(&value_type)
+    }
+
+    /// Returns true if values produced by this output port may be passed to the given input port.
+    ///
+    /// If `rasterizes` is true, drawables are also accepted by inputs that accept textures (see
+    /// [crate::render::Renderer]).
+    pub fn can_link_to(&self, input: &PortSchema, rasterizes: bool) -> bool {
+        let rasterizes = rasterizes && input.accepts(ValueType::Texture);
+        self.types.is_empty()
+            || self
+                .types
+                .iter()
+                .any(|ty| input.accepts(*ty) || (rasterizes && *ty == ValueType::Drawables))
+    }
+}
+
+/// Describes all properties of a node type.
+///
+/// An empty schema means that the node type does not publish a schema, and that links to and from
+/// its nodes can not be validated.
+#[derive(Debug, Clone, Default)]
+pub struct NodeSchema {
+    pub ports: Vec<PortSchema>,
+
+    /// (input, output) port pairs that are passed through when a node is disabled. Outputs
+    /// without a bypass input produce nothing when disabled.
+    pub bypass: Vec<(usize, usize)>,
+}
+
+impl NodeSchema {
+    /// Creates a new schema from a list of ports.
+    pub fn new(ports: Vec<PortSchema>) -> NodeSchema {
+        NodeSchema {
+            ports,
+            bypass: Vec::new(),
+        }
+    }
+
+    /// Adds a bypass port pair: when a node is disabled, the value of the input will be passed
+    /// through to the output.
+    pub fn with_bypass<I: Into<usize>, O: Into<usize>>(
+        mut self,
+        input: I,
+        output: O,
+    ) -> NodeSchema {
+        self.bypass.push((input.into(), output.into()));
+        self
+    }
+
+    /// Returns the input that is passed through to the given output when a node is disabled.
+    pub fn bypass_input(&self, output: usize) -> Option<usize> {
+        self.bypass
+            .iter()
+            .find(|(_, out)| *out == output)
+            .map(|(input, _)| *input)
+    }
+
+    /// Returns true if there are no ports.
+    pub fn is_empty(&self) -> bool {
+        self.ports.is_empty()
+    }
+
+    /// Returns the input port with the given index.
+    pub fn input(&self, index: usize) -> Option<&PortSchema> {
+        self.inputs().find(|port| port.index == index)
+    }
+
+    /// Returns the output port with the given index.
+    pub fn output(&self, index: usize) -> Option<&PortSchema> {
+        self.outputs().find(|port| port.index == index)
+    }
+
+    /// Returns the input port with the given name.
+    pub fn input_named(&self, name: &str) -> Option<&PortSchema> {
+        self.inputs().find(|port| port.name == name)
+    }
+
+    /// Returns the output port with the given name.
+    pub fn output_named(&self, name: &str) -> Option<&PortSchema> {
+        self.outputs().find(|port| port.name == name)
+    }
+
+    /// Iterates over all input ports.
+    pub fn inputs(&self) -> impl Iterator<Item = &PortSchema> {
+        self.ports
+            .iter()
+            .filter(|port| port.direction == PortDirection::In)
+    }
+
+    /// Iterates over all output ports.
+    pub fn outputs(&self) -> impl Iterator<Item = &PortSchema> {
+        self.ports
+            .iter()
+            .filter(|port| port.direction == PortDirection::Out)
+    }
+}
@@ -1,5 +1,5 @@
 use crate::data::cgmath_ext::{Matrix4Ext, Vector2Ext};
-use crate::data::{Camera, Value};
+use crate::data::{Camera, Value, ValueType};
 use crate::eval::*;
 use cgmath::{Matrix4, Vector2};
 use std::sync::Arc;
@@ -23,10 +23,34 @@ impl SharedDataType for CameraType {
     fn create(&mut self) -> Box<dyn DataNode> {
         Box::new(CameraNode)
     }
+
+    fn schema(&self) -> NodeSchema {
+        NodeSchema::new(vec![
+            PortSchema::input(CameraProps::In, "in", &[ValueType::Texture])
+                .with_description("The rendered scene."),
+            PortSchema::input(CameraProps::Size, "size", &[ValueType::Vec2])
+                .with_description("Viewport size in scene units."),
+            PortSchema::input(CameraProps::Offset, "offset", &[ValueType::Vec2])
+                .with_description("Viewport offset."),
+            PortSchema::input(CameraProps::Transform, "transform", &[ValueType::Mat4])
+                .with_description("Camera transform."),
+            PortSchema::input(CameraProps::Fov, "fov", &[ValueType::Float])
+                .with_description("Field of view in radians."),
+            PortSchema::input(CameraProps::ClipNear, "clip-near", &[ValueType::Float])
+                .with_description("Near clipping plane."),
+            PortSchema::input(CameraProps::ClipFar, "clip-far", &[ValueType::Float])
+                .with_description("Far clipping plane."),
+            PortSchema::output(CameraProps::Camera, "camera", &[ValueType::Any])
+                .with_description("Camera data."),
+        ])
+    }
 }
 
 struct CameraNode;
 
+/// Camera node properties.
+///
+/// The camera output is a [Camera] passed as `Any`.
 #[repr(usize)]
 pub enum CameraProps {
     In = 0,
@@ -36,6 +60,7 @@ pub enum CameraProps {
     Fov = 4,
     ClipNear = 5,
     ClipFar = 6,
+    Camera = 7,
 }
 
 impl Into<usize> for CameraProps {
@@ -62,7 +87,7 @@ impl DataNode for CameraNode {
         camera.clip_near = *input.one::<_, f64>(CameraProps::ClipNear)? as f32;
         camera.clip_far = *input.one::<_, f64>(CameraProps::ClipFar)? as f32;
 
-        output.set(0_usize, Value::Any(Arc::new(camera)));
+        output.set(CameraProps::Camera, Value::Any(Arc::new(camera)));
 
         Ok(())
     }
@@ -1,7 +1,7 @@
-use crate::data::{Color, Value};
+use crate::data::{Color, Value, ValueType};
 use crate::eval::*;
 use crate::node::NodeRef;
-use crate::render::{ShapeRasterizer, TexCompositor, TextureRef, COLOR_FORMAT, DEPTH_FORMAT};
+use crate::render::{ShapeRasterizer, TexCompositor, COLOR_FORMAT, DEPTH_FORMAT};
 use failure::Error;
 use std::sync::{Arc, Mutex};
 use vulkano::command_buffer::{AutoCommandBufferBuilder, DynamicState};
@@ -80,16 +80,27 @@ impl SharedGraphicsType for CompositeType {
     fn create(&mut self) -> Box<dyn GraphicsNode> {
         Box::new(CompositeNode {
             shared: self.shared.clone(),
-            output: None,
             framebuffer: None,
         })
     }
+
+    fn schema(&self) -> NodeSchema {
+        composite_schema()
+    }
+}
+
+pub(crate) fn composite_schema() -> NodeSchema {
+    NodeSchema::new(vec![
+        PortSchema::input(CompositeProps::In, "in", &[ValueType::Texture])
+            .many()
+            .with_description("Layers to composite, from bottom to top."),
+        PortSchema::output(CompositeProps::Out, "out", &[ValueType::Texture]),
+    ])
 }
 
 struct CompositeNode {
     shared: Shared,
-    output: Option<TextureRef>,
-    framebuffer: Option<Arc<dyn FramebufferAbstract + Send + Sync>>,
+    framebuffer: Option<(u64, Arc<dyn FramebufferAbstract + Send + Sync>)>,
 }
 
 #[repr(usize)]
@@ -115,22 +126,28 @@ impl GraphicsNode for CompositeNode {
         let size = (context.camera().width, context.camera().height).into();
         let resolution = context.resolution();
 
-        if self.output.as_ref().map_or(true, |tex| {
-            tex.size() != size || tex.resolution() != resolution
-        }) {
-            let output = context.new_attachment(size.x, size.y, resolution)?;
-            self.framebuffer = Some(Arc::new(
+        let (output_tex, is_valid) =
+            context.cached_attachment("output", size.x, size.y, resolution)?;
+        output.set(CompositeProps::Out, output_tex.clone());
+        if is_valid {
+            return Ok(cmd_buffer);
+        }
+
+        if self
+            .framebuffer
+            .as_ref()
+            .map_or(true, |(texture_id, _)| *texture_id != output_tex.id())
+        {
+            let framebuffer: Arc<dyn FramebufferAbstract + Send + Sync> = Arc::new(
                 Framebuffer::start(Arc::clone(&self.shared.render_pass))
-                    .add(output.color().clone())?
-                    .add(output.depth().unwrap().clone())?
+                    .add(output_tex.color().clone())?
+                    .add(output_tex.depth().unwrap().clone())?
                     .build()?,
-            ));
-            self.output = Some(output);
+            );
+            self.framebuffer = Some((output_tex.id(), framebuffer));
         }
 
-        let framebuffer = self.framebuffer.as_ref().unwrap();
-
-        output.set(CompositeProps::Out, self.output.as_ref().unwrap().clone());
+        let (_, framebuffer) = self.framebuffer.as_ref().unwrap();
 
         cmd_buffer = cmd_buffer.begin_render_pass(
             Arc::clone(framebuffer),
@@ -181,6 +198,8 @@ impl GraphicsNode for CompositeNode {
                     _ => return Err(EvalError::InputType(CompositeProps::In.into())),
                 }
             }
+
+            context.record_tessellation(rasterizer.take_tessellated_vertices());
         }
 
         cmd_buffer = cmd_buffer.end_render_pass()?;
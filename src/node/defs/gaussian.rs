@@ -1,3 +1,4 @@
+use crate::data::ValueType;
 use crate::eval::*;
 use crate::render::fx::GaussianBlur;
 use crate::render::TextureRef;
@@ -30,14 +31,26 @@ impl SharedGraphicsType for GaussianType {
     fn create(&mut self) -> Box<dyn GraphicsNode> {
         Box::new(GaussianNode {
             inner: Arc::clone(&self.inner),
-            textures: None,
         })
     }
+
+    fn schema(&self) -> NodeSchema {
+        gaussian_schema()
+    }
+}
+
+pub(crate) fn gaussian_schema() -> NodeSchema {
+    NodeSchema::new(vec![
+        PortSchema::input(GaussianProps::In, "in", &[ValueType::Texture]),
+        PortSchema::output(GaussianProps::Out, "out", &[ValueType::Texture]),
+        PortSchema::input(GaussianProps::Radius, "radius", &[ValueType::Float])
+            .with_description("Blur radius in scene units."),
+    ])
+    .with_bypass(GaussianProps::In, GaussianProps::Out)
 }
 
 struct GaussianNode {
     inner: Arc<Mutex<GaussianBlur>>,
-    textures: Option<(TextureRef, TextureRef)>,
 }
 
 #[repr(usize)]
@@ -55,6 +68,22 @@ impl Into<usize> for GaussianProps {
 
 const MIN_RADIUS: f32 = 0.1;
 
+/// Returns the number of blur passes for a radius in pixels, or None if the radius is too small
+/// for the blur to be visible.
+pub(crate) fn blur_passes(radius: f32) -> Option<u8> {
+    if radius < MIN_RADIUS {
+        return None;
+    }
+
+    // fixed pass count for now (TODO: quality config?)
+    // TODO: adjust this curve more nicely (this one was just eyeballed)
+    Some(
+        (4. - f32::consts::E.powf(1.5 - radius / 9.))
+            .round()
+            .max(1.) as u8,
+    )
+}
+
 impl GraphicsNode for GaussianNode {
     fn eval(
         &mut self,
@@ -69,46 +98,38 @@ impl GraphicsNode for GaussianNode {
             (input.size(), input.resolution())
         };
 
-        if radius < MIN_RADIUS {
-            output.set(
-                GaussianProps::Out,
-                input.one::<_, TextureRef>(GaussianProps::In)?.clone(),
-            );
-            return Ok(cmd_buffer);
-        }
+        let pass_count = match blur_passes(radius) {
+            Some(pass_count) => pass_count,
+            None => {
+                output.set(
+                    GaussianProps::Out,
+                    input.one::<_, TextureRef>(GaussianProps::In)?.clone(),
+                );
+                return Ok(cmd_buffer);
+            }
+        };
 
-        if self.textures.as_ref().map_or(true, |(tex, _)| {
-            tex.size() != input_size || tex.resolution() != input_resolution
-        }) {
-            let intermediate =
-                context.new_storage_texture(input_size.x, input_size.y, input_resolution)?;
-            let output_tex =
-                context.new_storage_texture(input_size.x, input_size.y, input_resolution)?;
-            self.textures = Some((intermediate, output_tex));
-        }
+        let (intermediate, _) =
+            context.cached_texture("intermediate", input_size.x, input_size.y, input_resolution)?;
+        let (output_tex, is_valid) =
+            context.cached_texture("output", input_size.x, input_size.y, input_resolution)?;
 
-        let (intermediate, output_tex) = self.textures.as_ref().unwrap();
+        if !is_valid {
+            let input_tex: &TextureRef = input.one(GaussianProps::In)?;
 
-        let input_tex: &TextureRef = input.one(GaussianProps::In)?;
+            // FIXME: what about the depth channel?
 
-        // FIXME: what about the depth channel?
+            cmd_buffer = self.inner.lock().unwrap().dispatch(
+                cmd_buffer,
+                input_tex,
+                intermediate.color().as_storage()?,
+                output_tex.color().as_storage()?,
+                radius,
+                pass_count,
+            )?;
+        }
 
-        // fixed pass count for now (TODO: quality config?)
-        // TODO: adjust this curve more nicely (this one was just eyeballed)
-        let pass_count = (4. - f32::consts::E.powf(1.5 - radius / 9.))
-            .round()
-            .max(1.) as u8;
-
-        cmd_buffer = self.inner.lock().unwrap().dispatch(
-            cmd_buffer,
-            input_tex.color(),
-            intermediate.color().as_storage()?,
-            output_tex.color().as_storage()?,
-            radius,
-            pass_count,
-        )?;
-
-        output.set(GaussianProps::Out, output_tex.clone());
+        output.set(GaussianProps::Out, output_tex);
         Ok(cmd_buffer)
     }
 }
@@ -0,0 +1,126 @@
+use crate::data::{Bitmap, SourceKey, Value, ValueType};
+use crate::eval::*;
+use crate::render::TextureRef;
+use failure::Error;
+use std::sync::Arc;
+use vulkano::command_buffer::AutoCommandBufferBuilder;
+use vulkano::device::{Device, Queue};
+
+pub static IMAGE: NodeTypeDef = NodeTypeDef::Graphics(ImageType::new);
+pub const IMAGE_NAME: &str = "narwhal.image";
+
+struct ImageType;
+
+impl ImageType {
+    fn new(_: &Arc<Device>, _: &Arc<Queue>) -> Result<Box<dyn SharedGraphicsType>, Error> {
+        Ok(Box::new(ImageType))
+    }
+}
+
+impl SharedGraphicsType for ImageType {
+    fn name(&self) -> String {
+        IMAGE_NAME.into()
+    }
+
+    fn create(&mut self) -> Box<dyn GraphicsNode> {
+        Box::new(ImageNode { cached: None })
+    }
+
+    fn schema(&self) -> NodeSchema {
+        image_schema()
+    }
+}
+
+pub(crate) fn image_schema() -> NodeSchema {
+    NodeSchema::new(vec![
+        PortSchema::input(
+            ImageProps::Source,
+            "source",
+            &[ValueType::String, ValueType::Raw],
+        )
+        .with_description("A file path or raw PNG, JPEG, or EXR data."),
+        PortSchema::output(ImageProps::Out, "out", &[ValueType::Texture]),
+        PortSchema::input(ImageProps::Resolution, "resolution", &[ValueType::Float])
+            .with_default(1.)
+            .with_description("Pixels per scene unit."),
+    ])
+}
+
+struct ImageNode {
+    cached: Option<(SourceKey, f32, TextureRef)>,
+}
+
+/// Image node properties.
+///
+/// The source may be either a file path (as a string) or raw file data. PNG, JPEG, and EXR images
+/// are supported.
+#[repr(usize)]
+pub enum ImageProps {
+    Source = 0,
+    Out = 1,
+    Resolution = 2,
+}
+
+impl Into<usize> for ImageProps {
+    fn into(self) -> usize {
+        self as usize
+    }
+}
+
+impl GraphicsNode for ImageNode {
+    fn eval(
+        &mut self,
+        input: Input,
+        mut context: NodeContext,
+        output: &mut Output,
+        mut cmd_buffer: AutoCommandBufferBuilder,
+    ) -> EvalResult<AutoCommandBufferBuilder> {
+        let source = input.one::<_, Value>(ImageProps::Source)?;
+        let resolution = match input.one::<_, f64>(ImageProps::Resolution) {
+            Ok(resolution) => *resolution as f32,
+            Err(EvalError::MissingInput(_)) => 1.,
+            Err(err) => return Err(err),
+        };
+
+        let key = match SourceKey::from_value(source) {
+            Some(key) => key,
+            None => return Err(EvalError::InputType(ImageProps::Source.into())),
+        };
+
+        if let Some((cached_key, cached_resolution, texture)) = &self.cached {
+            if *cached_key == key && *cached_resolution == resolution {
+                output.set(ImageProps::Out, texture.clone());
+                return Ok(cmd_buffer);
+            }
+        }
+
+        let bitmap = match source {
+            Value::String(path) => Bitmap::open(path),
+            Value::Raw(data) => Bitmap::decode(data),
+            _ => unreachable!(),
+        }
+        .map_err(|e| EvalError::Input(e.to_string()))?;
+
+        let (c, texture) = context.upload_texture(
+            cmd_buffer,
+            bitmap.width,
+            bitmap.height,
+            resolution,
+            &bitmap.pixels,
+        )?;
+        cmd_buffer = c;
+
+        output.set(ImageProps::Out, texture.clone());
+        self.cached = Some((key, resolution, texture));
+        Ok(cmd_buffer)
+    }
+
+    fn is_pure(&self) -> bool {
+        // files are checked for changes on every render
+        !self.cached.as_ref().map_or(false, |(key, ..)| key.is_path())
+    }
+
+    fn clear_caches(&mut self) {
+        self.cached = None;
+    }
+}
@@ -1,3 +1,4 @@
+use crate::data::ValueType;
 use crate::eval::*;
 use crate::render::fx::{Mask, MaskMode};
 use crate::render::TextureRef;
@@ -29,14 +30,37 @@ impl SharedGraphicsType for MaskType {
     fn create(&mut self) -> Box<dyn GraphicsNode> {
         Box::new(MaskNode {
             inner: Arc::clone(&self.inner),
-            output_tex: None,
         })
     }
+
+    fn schema(&self) -> NodeSchema {
+        mask_schema()
+    }
+}
+
+pub(crate) fn mask_schema() -> NodeSchema {
+    NodeSchema::new(vec![
+        PortSchema::input(MaskProps::In, "in", &[ValueType::Texture]),
+        PortSchema::output(MaskProps::Out, "out", &[ValueType::Texture]),
+        PortSchema::input(MaskProps::Mask, "mask", &[ValueType::Texture])
+            .with_description("The mask. If not linked, the input is passed through."),
+        PortSchema::input(MaskProps::Mode, "mode", &[ValueType::Any, ValueType::Float])
+            .with_description("A MaskMode or its numeric value."),
+    ])
+    .with_bypass(MaskProps::In, MaskProps::Out)
+}
+
+/// Reads the mask mode input.
+pub(crate) fn mask_mode(input: &Input) -> EvalResult<MaskMode> {
+    match input.one::<_, f64>(MaskProps::Mode) {
+        Ok(index) => MaskMode::from_index(*index as i32)
+            .ok_or_else(|| EvalError::InputType(MaskProps::Mode.into())),
+        Err(_) => Ok(*input.one_any::<_, MaskMode>(MaskProps::Mode)?),
+    }
 }
 
 struct MaskNode {
     inner: Arc<Mutex<Mask>>,
-    output_tex: Option<TextureRef>,
 }
 
 #[repr(usize)]
@@ -75,30 +99,26 @@ impl GraphicsNode for MaskNode {
             (input.size(), input.resolution())
         };
 
-        if self.output_tex.as_ref().map_or(true, |tex| {
-            tex.size() != input_size || tex.resolution() != input_resolution
-        }) {
-            let output_tex =
-                context.new_storage_texture(input_size.x, input_size.y, input_resolution)?;
-            self.output_tex = Some(output_tex);
-        }
+        let (output_tex, is_valid) =
+            context.cached_texture("output", input_size.x, input_size.y, input_resolution)?;
 
-        let output_tex = self.output_tex.as_ref().unwrap();
-        let input_tex: &TextureRef = input.one(MaskProps::In)?;
-        let mask: &TextureRef = input.one(MaskProps::Mask)?;
-        let mode = *input.one_any::<_, MaskMode>(MaskProps::Mode)?;
+        if !is_valid {
+            let input_tex: &TextureRef = input.one(MaskProps::In)?;
+            let mask: &TextureRef = input.one(MaskProps::Mask)?;
+            let mode = mask_mode(&input)?;
 
-        // FIXME: what about the depth channel?
+            // FIXME: what about the depth channel?
 
-        cmd_buffer = self.inner.lock().unwrap().dispatch(
-            cmd_buffer,
-            input_tex.color(),
-            mask.color(),
-            output_tex.color().as_storage()?,
-            mode,
-        )?;
+            cmd_buffer = self.inner.lock().unwrap().dispatch(
+                cmd_buffer,
+                input_tex,
+                mask,
+                output_tex.color().as_storage()?,
+                mode,
+            )?;
+        }
 
-        output.set(MaskProps::Out, output_tex.clone());
+        output.set(MaskProps::Out, output_tex);
         Ok(cmd_buffer)
     }
 }
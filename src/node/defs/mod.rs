@@ -3,9 +3,56 @@
 mod camera;
 mod composite;
 mod gaussian;
+mod image;
 mod mask;
+mod path_morph;
+mod text;
+mod time;
+mod tone_map;
 
 pub use self::camera::*;
 pub use self::composite::*;
 pub use self::gaussian::*;
+pub use self::image::*;
 pub use self::mask::*;
+pub use self::path_morph::*;
+pub use self::text::*;
+pub use self::time::*;
+pub use self::tone_map::*;
+
+use crate::eval::{NodeTypeDef, SchemaRegistry};
+
+/// Returns all built-in node types.
+pub fn builtin() -> Vec<NodeTypeDef> {
+    vec![
+        CAMERA,
+        COMPOSITE,
+        GAUSSIAN_BLUR,
+        IMAGE,
+        MASK,
+        PATH_MORPH,
+        TEXT,
+        TIME,
+        TONE_MAP,
+    ]
+}
+
+/// Returns the schemas of all built-in node types.
+///
+/// This does not require a device, so it can be used to resolve property names (e.g. when loading
+/// scenes) without a renderer.
+pub fn builtin_schemas() -> SchemaRegistry {
+    let mut schemas = SchemaRegistry::new();
+    for def in builtin() {
+        if let NodeTypeDef::Data(create) = def {
+            let data_type = create();
+            schemas.insert(&data_type.name(), data_type.schema());
+        }
+    }
+    schemas.insert(COMPOSITE_NAME, composite_schema());
+    schemas.insert(GAUSSIAN_BLUR_NAME, gaussian_schema());
+    schemas.insert(IMAGE_NAME, image_schema());
+    schemas.insert(MASK_NAME, mask_schema());
+    schemas.insert(TONE_MAP_NAME, tone_map_schema());
+    schemas
+}
@@ -0,0 +1,78 @@
+use crate::data::{morph_paths, morph_weights, Value, ValueType};
+use crate::eval::*;
+
+pub static PATH_MORPH: NodeTypeDef = NodeTypeDef::Data(PathMorphType::new);
+pub const PATH_MORPH_NAME: &str = "narwhal.path-morph";
+
+struct PathMorphType;
+
+impl PathMorphType {
+    fn new() -> Box<dyn SharedDataType> {
+        Box::new(PathMorphType)
+    }
+}
+
+impl SharedDataType for PathMorphType {
+    fn name(&self) -> String {
+        PATH_MORPH_NAME.into()
+    }
+
+    fn create(&mut self) -> Box<dyn DataNode> {
+        Box::new(PathMorphNode)
+    }
+
+    fn schema(&self) -> NodeSchema {
+        let types = &[ValueType::Path2D, ValueType::StrokeWeight];
+        NodeSchema::new(vec![
+            PortSchema::input(PathMorphProps::From, "from", types),
+            PortSchema::input(PathMorphProps::To, "to", types)
+                .with_description("Must be of the same type as from."),
+            PortSchema::input(PathMorphProps::T, "t", &[ValueType::Float])
+                .with_default(0.)
+                .with_description("Interpolation factor from 0 (from) to 1 (to)."),
+            PortSchema::output(PathMorphProps::Out, "out", types),
+        ])
+        .with_bypass(PathMorphProps::From, PathMorphProps::Out)
+    }
+}
+
+struct PathMorphNode;
+
+/// Path morph node properties.
+///
+/// Interpolates between two paths or two stroke weights (see [crate::data::morph_paths]).
+#[repr(usize)]
+pub enum PathMorphProps {
+    From = 0,
+    To = 1,
+    T = 2,
+    Out = 3,
+}
+
+impl Into<usize> for PathMorphProps {
+    fn into(self) -> usize {
+        self as usize
+    }
+}
+
+impl DataNode for PathMorphNode {
+    fn eval(&mut self, input: Input, output: &mut Output) -> EvalResult<()> {
+        let from = input.one::<_, Value>(PathMorphProps::From)?;
+        let to = input.one::<_, Value>(PathMorphProps::To)?;
+        let t = *input.one::<_, f64>(PathMorphProps::T)?;
+
+        let value = match (from, to) {
+            (Value::Path2D(a), Value::Path2D(b)) => Value::Path2D(morph_paths(a, b, t)),
+            (Value::StrokeWeight(a), Value::StrokeWeight(b)) => {
+                Value::StrokeWeight(morph_weights(a, b, t))
+            }
+            (Value::Path2D(_), _) | (Value::StrokeWeight(_), _) => {
+                return Err(EvalError::InputType(PathMorphProps::To.into()));
+            }
+            _ => return Err(EvalError::InputType(PathMorphProps::From.into())),
+        };
+
+        output.set(PathMorphProps::Out, value);
+        Ok(())
+    }
+}
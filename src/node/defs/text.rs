@@ -0,0 +1,182 @@
+use crate::data::cgmath_ext::Matrix4Ext;
+use crate::data::{
+    Color, Drawable, Font, Shape, SourceKey, TextAlign, TextLayout, Value, ValueType,
+};
+use crate::eval::*;
+use cgmath::{Matrix4, SquareMatrix};
+
+pub static TEXT: NodeTypeDef = NodeTypeDef::Data(TextType::new);
+pub const TEXT_NAME: &str = "narwhal.text";
+
+struct TextType;
+
+impl TextType {
+    fn new() -> Box<dyn SharedDataType> {
+        Box::new(TextType)
+    }
+}
+
+impl SharedDataType for TextType {
+    fn name(&self) -> String {
+        TEXT_NAME.into()
+    }
+
+    fn create(&mut self) -> Box<dyn DataNode> {
+        Box::new(TextNode { font: None })
+    }
+
+    fn schema(&self) -> NodeSchema {
+        NodeSchema::new(vec![
+            PortSchema::input(TextProps::Text, "text", &[ValueType::String]),
+            PortSchema::output(TextProps::Out, "out", &[ValueType::Drawables]),
+            PortSchema::input(
+                TextProps::Font,
+                "font",
+                &[ValueType::String, ValueType::Raw],
+            )
+            .with_description("A file path or raw TTF or OTF data."),
+            PortSchema::input(TextProps::Size, "size", &[ValueType::Float])
+                .with_description("Font size."),
+            PortSchema::input(TextProps::Fill, "fill", &[ValueType::Color]),
+            PortSchema::input(
+                TextProps::Align,
+                "align",
+                &[ValueType::Any, ValueType::Float],
+            )
+            .with_description("A TextAlign or its numeric value. Left-aligned by default."),
+            PortSchema::input(TextProps::Width, "width", &[ValueType::Float])
+                .with_description("If set, lines will be wrapped to fit this width."),
+            PortSchema::input(TextProps::LineHeight, "line-height", &[ValueType::Float])
+                .with_default(1.)
+                .with_description("Line height as a multiple of the natural line height."),
+            PortSchema::input(TextProps::Transform, "transform", &[ValueType::Mat4]),
+        ])
+    }
+}
+
+struct TextNode {
+    font: Option<(SourceKey, Font)>,
+}
+
+/// Text node properties.
+///
+/// The font may be either a file path (as a string) or raw TTF/OTF data. The alignment is a
+/// [`TextAlign`] or its numeric value and defaults to left-aligned. If a width is given, lines are
+/// wrapped at whitespace to fit. The line height is a multiple of the font’s natural line height.
+///
+/// Outputs one drawable per glyph, with the origin at the top of the first line.
+#[repr(usize)]
+pub enum TextProps {
+    Text = 0,
+    Out = 1,
+    Font = 2,
+    Size = 3,
+    Fill = 4,
+    Align = 5,
+    Width = 6,
+    LineHeight = 7,
+    Transform = 8,
+}
+
+impl Into<usize> for TextProps {
+    fn into(self) -> usize {
+        self as usize
+    }
+}
+
+/// Reads the alignment input.
+fn text_align(input: &Input) -> EvalResult<TextAlign> {
+    match input.one::<_, f64>(TextProps::Align) {
+        Ok(index) => TextAlign::from_index(*index as i32)
+            .ok_or_else(|| EvalError::InputType(TextProps::Align.into())),
+        Err(EvalError::MissingInput(_)) => Ok(TextAlign::default()),
+        Err(_) => Ok(*input.one_any::<_, TextAlign>(TextProps::Align)?),
+    }
+}
+
+impl TextNode {
+    fn font(&mut self, source: &Value) -> EvalResult<&Font> {
+        let key = match SourceKey::from_value(source) {
+            Some(key) => key,
+            None => return Err(EvalError::InputType(TextProps::Font.into())),
+        };
+
+        let is_cached = match &self.font {
+            Some((cached_key, _)) => *cached_key == key,
+            None => false,
+        };
+
+        if !is_cached {
+            let font = match source {
+                Value::String(path) => Font::open(path),
+                Value::Raw(data) => Font::from_bytes(data.clone()),
+                _ => unreachable!(),
+            }
+            .map_err(|e| EvalError::Input(e.to_string()))?;
+            self.font = Some((key, font));
+        }
+
+        Ok(&self.font.as_ref().unwrap().1)
+    }
+}
+
+impl DataNode for TextNode {
+    fn eval(&mut self, input: Input, output: &mut Output) -> EvalResult<()> {
+        let text = input.one::<_, String>(TextProps::Text)?;
+        let size = *input.one::<_, f64>(TextProps::Size)?;
+        let fill = *input.one::<_, Color>(TextProps::Fill)?;
+        let align = text_align(&input)?;
+        let max_width = match input.one::<_, f64>(TextProps::Width) {
+            Ok(width) => Some(*width),
+            Err(EvalError::MissingInput(_)) => None,
+            Err(err) => return Err(err),
+        };
+        let line_height = match input.one::<_, f64>(TextProps::LineHeight) {
+            Ok(line_height) => *line_height,
+            Err(EvalError::MissingInput(_)) => 1.,
+            Err(err) => return Err(err),
+        };
+        let transform = match input.one::<_, Matrix4<f64>>(TextProps::Transform) {
+            Ok(transform) => transform.into_f32(),
+            Err(EvalError::MissingInput(_)) => Matrix4::identity(),
+            Err(err) => return Err(err),
+        };
+
+        let layout = TextLayout {
+            size,
+            align,
+            max_width,
+            line_height,
+        };
+
+        let node = input.node();
+        let font = self.font(input.one::<_, Value>(TextProps::Font)?)?;
+        let drawables: Vec<_> = font
+            .layout(text, &layout)
+            .into_iter()
+            .enumerate()
+            .map(|(i, path)| Drawable {
+                id: (node, i as u64),
+                shape: Shape {
+                    path,
+                    stroke: None,
+                    fill: Some(fill),
+                    transform: Some(transform),
+                },
+            })
+            .collect();
+
+        output.set(TextProps::Out, drawables);
+
+        Ok(())
+    }
+
+    fn is_pure(&self) -> bool {
+        // font files are checked for changes on every render
+        !self.font.as_ref().map_or(false, |(key, _)| key.is_path())
+    }
+
+    fn clear_caches(&mut self) {
+        self.font = None;
+    }
+}
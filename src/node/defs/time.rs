@@ -0,0 +1,68 @@
+use crate::data::ValueType;
+use crate::eval::*;
+
+pub static TIME: NodeTypeDef = NodeTypeDef::Data(TimeType::new);
+pub const TIME_NAME: &str = "narwhal.time";
+
+struct TimeType;
+
+impl TimeType {
+    fn new() -> Box<dyn SharedDataType> {
+        Box::new(TimeType)
+    }
+}
+
+impl SharedDataType for TimeType {
+    fn name(&self) -> String {
+        TIME_NAME.into()
+    }
+
+    fn create(&mut self) -> Box<dyn DataNode> {
+        Box::new(TimeNode)
+    }
+
+    fn schema(&self) -> NodeSchema {
+        NodeSchema::new(vec![
+            PortSchema::output(TimeProps::Time, "time", &[ValueType::Float])
+                .with_description("Current time in seconds."),
+            PortSchema::output(TimeProps::Frame, "frame", &[ValueType::Float])
+                .with_description("Current frame number."),
+            PortSchema::output(TimeProps::Delta, "delta", &[ValueType::Float])
+                .with_description("Time since the previous frame in seconds."),
+            PortSchema::output(TimeProps::Resolution, "resolution", &[ValueType::Float])
+                .with_description("Rendering resolution."),
+        ])
+    }
+}
+
+struct TimeNode;
+
+/// Time node properties. All of these are outputs.
+#[repr(usize)]
+pub enum TimeProps {
+    Time = 0,
+    Frame = 1,
+    Delta = 2,
+    Resolution = 3,
+}
+
+impl Into<usize> for TimeProps {
+    fn into(self) -> usize {
+        self as usize
+    }
+}
+
+impl DataNode for TimeNode {
+    fn eval(&mut self, input: Input, output: &mut Output) -> EvalResult<()> {
+        let context = input.context();
+        output.set(TimeProps::Time, context.time);
+        output.set(TimeProps::Frame, context.frame as f64);
+        output.set(TimeProps::Delta, context.delta);
+        output.set(TimeProps::Resolution, f64::from(context.resolution));
+        Ok(())
+    }
+
+    fn is_pure(&self) -> bool {
+        false
+    }
+}
@@ -0,0 +1,134 @@
+use crate::data::ValueType;
+use crate::eval::*;
+use crate::render::fx::{ToneMap, ToneMapOperator};
+use crate::render::TextureRef;
+use failure::Error;
+use std::sync::{Arc, Mutex};
+use vulkano::command_buffer::AutoCommandBufferBuilder;
+use vulkano::device::{Device, Queue};
+
+pub static TONE_MAP: NodeTypeDef = NodeTypeDef::Graphics(ToneMapType::new);
+pub const TONE_MAP_NAME: &str = "narwhal.tone-map";
+
+struct ToneMapType {
+    inner: Arc<Mutex<ToneMap>>,
+}
+
+impl ToneMapType {
+    fn new(device: &Arc<Device>, _: &Arc<Queue>) -> Result<Box<dyn SharedGraphicsType>, Error> {
+        Ok(Box::new(ToneMapType {
+            inner: Arc::new(Mutex::new(ToneMap::new(Arc::clone(device))?)),
+        }))
+    }
+}
+
+impl SharedGraphicsType for ToneMapType {
+    fn name(&self) -> String {
+        TONE_MAP_NAME.into()
+    }
+
+    fn create(&mut self) -> Box<dyn GraphicsNode> {
+        Box::new(ToneMapNode {
+            inner: Arc::clone(&self.inner),
+        })
+    }
+
+    fn schema(&self) -> NodeSchema {
+        tone_map_schema()
+    }
+}
+
+pub(crate) fn tone_map_schema() -> NodeSchema {
+    NodeSchema::new(vec![
+        PortSchema::input(ToneMapProps::In, "in", &[ValueType::Texture]),
+        PortSchema::output(ToneMapProps::Out, "out", &[ValueType::Texture]),
+        PortSchema::input(
+            ToneMapProps::Operator,
+            "operator",
+            &[ValueType::Any, ValueType::Float],
+        )
+        .with_default(ToneMapOperator::default() as i32 as f64)
+        .with_description("A ToneMapOperator or its numeric value. Defaults to no tone mapping."),
+        PortSchema::input(ToneMapProps::Exposure, "exposure", &[ValueType::Float])
+            .with_default(0.)
+            .with_description("Exposure adjustment in stops."),
+    ])
+    .with_bypass(ToneMapProps::In, ToneMapProps::Out)
+}
+
+/// Reads the tone mapping operator input.
+pub(crate) fn tone_map_operator(input: &Input) -> EvalResult<ToneMapOperator> {
+    match input.one::<_, f64>(ToneMapProps::Operator) {
+        Ok(index) => ToneMapOperator::from_index(*index as i32)
+            .ok_or_else(|| EvalError::InputType(ToneMapProps::Operator.into())),
+        Err(EvalError::MissingInput(_)) => Ok(ToneMapOperator::default()),
+        Err(_) => Ok(*input.one_any::<_, ToneMapOperator>(ToneMapProps::Operator)?),
+    }
+}
+
+struct ToneMapNode {
+    inner: Arc<Mutex<ToneMap>>,
+}
+
+#[repr(usize)]
+pub enum ToneMapProps {
+    In = 0,
+    Out = 1,
+    Operator = 2,
+    Exposure = 3,
+}
+
+impl Into<usize> for ToneMapProps {
+    fn into(self) -> usize {
+        self as usize
+    }
+}
+
+impl GraphicsNode for ToneMapNode {
+    fn eval(
+        &mut self,
+        input: Input,
+        mut context: NodeContext,
+        output: &mut Output,
+        mut cmd_buffer: AutoCommandBufferBuilder,
+    ) -> EvalResult<AutoCommandBufferBuilder> {
+        let (input_size, input_resolution) = {
+            let input = input.one::<_, TextureRef>(ToneMapProps::In)?;
+            (input.size(), input.resolution())
+        };
+
+        let operator = tone_map_operator(&input)?;
+        let exposure = match input.one::<_, f64>(ToneMapProps::Exposure) {
+            Ok(exposure) => *exposure as f32,
+            Err(EvalError::MissingInput(_)) => 0.,
+            Err(err) => return Err(err),
+        };
+
+        if operator == ToneMapOperator::None && exposure == 0. {
+            output.set(
+                ToneMapProps::Out,
+                input.one::<_, TextureRef>(ToneMapProps::In)?.clone(),
+            );
+            return Ok(cmd_buffer);
+        }
+
+        let (output_tex, is_valid) =
+            context.cached_texture("output", input_size.x, input_size.y, input_resolution)?;
+
+        if !is_valid {
+            let input_tex: &TextureRef = input.one(ToneMapProps::In)?;
+
+            cmd_buffer = self.inner.lock().unwrap().dispatch(
+                cmd_buffer,
+                input_tex,
+                output_tex.color().as_storage()?,
+                output_tex.px_size(),
+                operator,
+                exposure,
+            )?;
+        }
+
+        output.set(ToneMapProps::Out, output_tex);
+        Ok(cmd_buffer)
+    }
+}
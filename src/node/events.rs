@@ -0,0 +1,138 @@
+use crate::node::{Graph, NodeRef};
+use crate::util::BSMap;
+use fnv::FnvHashSet;
+use std::mem;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// A structured graph change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GraphEvent {
+    /// A node was added.
+    NodeAdded(NodeRef),
+
+    /// A node was removed. Its links will have been reported as removed beforehand.
+    NodeRemoved(NodeRef),
+
+    /// Two node properties were linked (out node, out prop, in node, in prop).
+    Linked(NodeRef, usize, NodeRef, usize),
+
+    /// Two node properties were unlinked (out node, out prop, in node, in prop).
+    Unlinked(NodeRef, usize, NodeRef, usize),
+
+    /// A node property was set, removed, or mutated.
+    PropertyChanged(NodeRef, usize),
+
+    /// A node was enabled or disabled.
+    EnabledChanged(NodeRef, bool),
+
+    /// Another node was made the graph’s output node (see [Graph::set_output]).
+    ///
+    /// This is about the graph structure only; it is not emitted when nodes are evaluated.
+    OutputNodeChanged(NodeRef),
+}
+
+/// A graph event subscription handle.
+///
+/// Handles are unique across all graphs. A cloned graph keeps the subscriptions of the original,
+/// however, so the handle alone does not identify the graph it was obtained from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SubscriberId(u64);
+
+impl SubscriberId {
+    fn new() -> SubscriberId {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+        SubscriberId(NEXT_ID.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+/// Event queues of a graph.
+#[derive(Debug, Clone)]
+pub(crate) struct Events {
+    subscribers: BSMap<SubscriberId, Vec<GraphEvent>>,
+
+    /// Nodes that were accessed mutably in order, along with their enabled state at the time.
+    touched: Vec<(NodeRef, bool)>,
+
+    /// The nodes in `touched`.
+    touched_set: FnvHashSet<NodeRef>,
+}
+
+impl Events {
+    pub(crate) fn new() -> Events {
+        Events {
+            subscribers: BSMap::new(),
+            touched: Vec::new(),
+            touched_set: FnvHashSet::default(),
+        }
+    }
+
+    fn push(&mut self, event: GraphEvent) {
+        for (_, queue) in self.subscribers.iter_mut() {
+            queue.push(event);
+        }
+    }
+
+    /// Records that a node is about to be mutated.
+    pub(crate) fn touch(&mut self, node: NodeRef, enabled: bool) {
+        if self.touched_set.insert(node) {
+            self.touched.push((node, enabled));
+        }
+    }
+}
+
+impl Graph {
+    /// Emits property changes of nodes that were accessed mutably since the last event.
+    fn flush_touched(&mut self) {
+        let touched = mem::replace(&mut self.events.touched, Vec::new());
+        self.events.touched_set.clear();
+        for (node_ref, was_enabled) in &touched {
+            let node = match self.nodes.get_mut(node_ref) {
+                Some(node) => node,
+                None => continue,
+            };
+            for prop in node.take_changed() {
+                self.events
+                    .push(GraphEvent::PropertyChanged(*node_ref, prop));
+            }
+            if node.enabled != *was_enabled {
+                self.events
+                    .push(GraphEvent::EnabledChanged(*node_ref, node.enabled));
+            }
+        }
+    }
+
+    /// Emits an event to all subscribers.
+    pub(super) fn emit(&mut self, event: GraphEvent) {
+        // pending property changes happened before this event
+        self.flush_touched();
+        self.events.push(event);
+    }
+
+    /// Subscribes to graph change events. Events will be queued until they are drained with
+    /// [Graph::drain_events].
+    pub fn subscribe(&mut self) -> SubscriberId {
+        self.flush_touched();
+        let id = SubscriberId::new();
+        self.events.subscribers.insert(id, Vec::new());
+        id
+    }
+
+    /// Returns true if the subscription exists.
+    pub fn is_subscribed(&self, id: SubscriberId) -> bool {
+        self.events.subscribers.contains_key(&id)
+    }
+
+    /// Removes a subscription and discards its queued events.
+    pub fn unsubscribe(&mut self, id: SubscriberId) {
+        self.events.subscribers.remove(&id);
+    }
+
+    /// Returns all events queued for a subscriber since the last call, in order.
+    pub fn drain_events(&mut self, id: SubscriberId) -> Vec<GraphEvent> {
+        self.flush_touched();
+        match self.events.subscribers.get_mut(&id) {
+            Some(queue) => mem::replace(queue, Vec::new()),
+            None => Vec::new(),
+        }
+    }
+}
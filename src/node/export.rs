@@ -0,0 +1,256 @@
+use crate::data::Value;
+use crate::eval::{NodeSchema, SchemaRegistry};
+use crate::node::{Graph, NodeRef};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fmt::Write;
+
+/// Maximum length of string values in property summaries.
+const MAX_SUMMARY_STRING_LEN: usize = 32;
+
+/// Renderer state that may be overlaid on a debug export.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DebugOverlay {
+    /// Nodes that are marked dirty.
+    pub dirty: HashSet<NodeRef>,
+
+    /// Nodes that have cached outputs.
+    pub cached: HashSet<NodeRef>,
+}
+
+impl DebugOverlay {
+    fn state(&self, node: NodeRef) -> JsonNodeState {
+        JsonNodeState {
+            dirty: self.dirty.contains(&node),
+            cached: self.cached.contains(&node),
+        }
+    }
+}
+
+/// Returns a short human-readable summary of a value.
+pub fn summarize_value(value: &Value) -> String {
+    match value {
+        Value::Float(x) => format!("{}", x),
+        Value::String(s) => {
+            if s.chars().count() > MAX_SUMMARY_STRING_LEN {
+                let s: String = s.chars().take(MAX_SUMMARY_STRING_LEN).collect();
+                format!("{:?}…", s)
+            } else {
+                format!("{:?}", s)
+            }
+        }
+        Value::Vec2(v) => format!("({}, {})", v.x, v.y),
+        Value::Vec3(v) => format!("({}, {}, {})", v.x, v.y, v.z),
+        Value::Vec4(v) => format!("({}, {}, {}, {})", v.x, v.y, v.z, v.w),
+        Value::Mat4(_) => "4×4 matrix".into(),
+        Value::Color(c) => format!("rgba({}, {}, {}, {})", c.r, c.g, c.b, c.a),
+        Value::Path2D(path) => format!("path with {} commands", path.commands().len()),
+        Value::StrokeWeight(_) => "stroke weight".into(),
+        Value::Drawables(drawables) => format!("{} drawables", drawables.len()),
+        Value::Texture(_) => "texture".into(),
+        Value::Raw(data) => format!("{} bytes", data.len()),
+        Value::Any(_) => "any".into(),
+    }
+}
+
+/// Escapes a string for use in a double-quoted DOT string. Other control characters than line
+/// breaks and tabs are replaced with spaces.
+fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push('\t'),
+            c if c.is_control() => out.push(' '),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// A graph as written by [Graph::to_json].
+#[derive(Serialize)]
+struct JsonGraph<'a> {
+    output: NodeRef,
+    nodes: Vec<JsonNode<'a>>,
+    links: Vec<JsonLink>,
+}
+
+#[derive(Serialize)]
+struct JsonNode<'a> {
+    id: NodeRef,
+    #[serde(rename = "type")]
+    node_type: &'a str,
+    enabled: bool,
+    properties: Vec<JsonProperty>,
+    #[serde(flatten)]
+    state: Option<JsonNodeState>,
+}
+
+#[derive(Serialize)]
+struct JsonProperty {
+    index: usize,
+    name: Option<String>,
+    #[serde(rename = "type")]
+    value_type: String,
+    summary: String,
+}
+
+/// Node state from a [DebugOverlay].
+#[derive(Serialize)]
+struct JsonNodeState {
+    dirty: bool,
+    cached: bool,
+}
+
+#[derive(Serialize)]
+struct JsonLink {
+    from: NodeRef,
+    from_port: usize,
+    from_name: Option<String>,
+    to: NodeRef,
+    to_port: usize,
+    to_name: Option<String>,
+}
+
+impl Graph {
+    /// Returns the schema for a node, if any.
+    fn schema_for<'a>(&self, schemas: &'a SchemaRegistry, node: NodeRef) -> Option<&'a NodeSchema> {
+        self.node(&node)
+            .and_then(|node| schemas.get(&node.node_type))
+    }
+
+    /// Returns the name of a node’s input or output port.
+    pub(super) fn port_name(
+        &self,
+        schemas: &SchemaRegistry,
+        node: NodeRef,
+        prop: usize,
+        output: bool,
+    ) -> Option<String> {
+        let schema = self.schema_for(schemas, node)?;
+        let port = if output {
+            schema.output(prop)
+        } else {
+            schema.input(prop)
+        };
+        port.map(|port| port.name.clone())
+    }
+
+    /// Returns a label for a port: its name if known, or its index.
+    fn port_label(
+        &self,
+        schemas: &SchemaRegistry,
+        node: NodeRef,
+        prop: usize,
+        output: bool,
+    ) -> String {
+        match self.port_name(schemas, node, prop, output) {
+            Some(name) => name,
+            None => format!("#{}", prop),
+        }
+    }
+
+    /// Writes the graph in the Graphviz DOT format.
+    ///
+    /// Nodes are labeled with their type, ID, and property summaries, and links with their port
+    /// names, as far as their node types have a schema. The output node is drawn with a bold
+    /// outline. If an overlay is given, dirty nodes are filled red and nodes with cached outputs are
+    /// filled green.
+    pub fn to_dot(&self, schemas: &SchemaRegistry, overlay: Option<&DebugOverlay>) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "digraph {{");
+        let _ = writeln!(out, "    rankdir=LR;");
+        let _ = writeln!(out, "    node [shape=box, fontname=monospace];");
+
+        for (node_ref, node) in self.iter_nodes() {
+            let mut label = format!("{}\\n#{}\\n", escape(&node.node_type), node_ref.0);
+            if !node.enabled {
+                label.push_str("(disabled)\\l");
+            }
+            for (prop, value) in node.props.iter() {
+                let _ = write!(
+                    label,
+                    "{} = {}\\l",
+                    escape(&self.port_label(schemas, *node_ref, *prop, false)),
+                    escape(&summarize_value(value))
+                );
+            }
+
+            let mut attrs = format!("label=\"{}\"", label);
+            if *node_ref == self.output() {
+                attrs.push_str(", penwidth=3");
+            }
+            if let Some(overlay) = overlay {
+                if overlay.dirty.contains(node_ref) {
+                    attrs.push_str(", style=filled, fillcolor=\"#ffd0d0\"");
+                } else if overlay.cached.contains(node_ref) {
+                    attrs.push_str(", style=filled, fillcolor=\"#d0ffd0\"");
+                }
+            }
+            let _ = writeln!(out, "    n{} [{}];", node_ref.0, attrs);
+        }
+
+        for ((out_node, out_prop), (in_node, in_prop)) in self.iter_links() {
+            let _ = writeln!(
+                out,
+                "    n{} -> n{} [label=\"{} → {}\"];",
+                out_node.0,
+                in_node.0,
+                escape(&self.port_label(schemas, out_node, out_prop, true)),
+                escape(&self.port_label(schemas, in_node, in_prop, false))
+            );
+        }
+
+        out.push_str("}\n");
+        out
+    }
+
+    /// Writes the graph as JSON with the same information as [Graph::to_dot].
+    ///
+    /// The format is an object with an `output` node ID, a `nodes` array, and a `links` array.
+    /// Port names are null if the node type has no schema. Nodes only have `dirty` and `cached`
+    /// fields if an overlay is given.
+    pub fn to_json(&self, schemas: &SchemaRegistry, overlay: Option<&DebugOverlay>) -> String {
+        let nodes = self
+            .iter_nodes()
+            .map(|(node_ref, node)| JsonNode {
+                id: *node_ref,
+                node_type: &node.node_type,
+                enabled: node.enabled,
+                properties: node
+                    .props
+                    .iter()
+                    .map(|(prop, value)| JsonProperty {
+                        index: *prop,
+                        name: self.port_name(schemas, *node_ref, *prop, false),
+                        value_type: value.value_type().to_string(),
+                        summary: summarize_value(value),
+                    })
+                    .collect(),
+                state: overlay.map(|overlay| overlay.state(*node_ref)),
+            })
+            .collect();
+
+        let links = self
+            .iter_links()
+            .map(|((out_node, out_prop), (in_node, in_prop))| JsonLink {
+                from: out_node,
+                from_port: out_prop,
+                from_name: self.port_name(schemas, out_node, out_prop, true),
+                to: in_node,
+                to_port: in_prop,
+                to_name: self.port_name(schemas, in_node, in_prop, false),
+            })
+            .collect();
+
+        let graph = JsonGraph {
+            output: self.output(),
+            nodes,
+            links,
+        };
+        serde_json::to_string(&graph).expect("failed to serialize graph")
+    }
+}
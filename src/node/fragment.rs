@@ -0,0 +1,164 @@
+use crate::node::{Graph, GraphId, Node, NodeRef};
+use std::collections::{HashMap, HashSet};
+
+/// A detached selection of nodes along with the links among them, used for copy and paste.
+///
+/// Node references in a fragment refer to the graph it was extracted from.
+#[derive(Debug, Clone, Default)]
+pub struct Fragment {
+    /// The graph the fragment was extracted from.
+    pub source: Option<GraphId>,
+
+    /// Selected nodes.
+    pub nodes: Vec<(NodeRef, Node)>,
+
+    /// Links between selected nodes (out node, out prop, in node, in prop).
+    pub links: Vec<(NodeRef, usize, NodeRef, usize)>,
+
+    /// Links from nodes outside the selection to selected nodes (out node, out prop, in node,
+    /// in prop).
+    pub incoming: Vec<(NodeRef, usize, NodeRef, usize)>,
+}
+
+impl Fragment {
+    /// Returns true if the fragment contains no nodes.
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+}
+
+impl Graph {
+    /// Copies the selected nodes and the links among them into a detached fragment.
+    ///
+    /// Nodes that do not exist are ignored. Links from outside the selection are recorded
+    /// separately so that they may optionally be restored when the fragment is inserted.
+    pub fn extract(&self, selection: &[NodeRef]) -> Fragment {
+        let selected: HashSet<_> = selection
+            .iter()
+            .filter(|node| self.node(node).is_some())
+            .collect();
+
+        let mut fragment = Fragment::default();
+        fragment.source = Some(self.id());
+        for (node_ref, node) in self.iter_nodes() {
+            if !selected.contains(node_ref) {
+                continue;
+            }
+            fragment.nodes.push((*node_ref, node.clone()));
+
+            for (other, out_prop, in_prop) in self.node_inputs(*node_ref) {
+                let link = (other, out_prop, *node_ref, in_prop);
+                if selected.contains(&other) {
+                    fragment.links.push(link);
+                } else {
+                    fragment.incoming.push(link);
+                }
+            }
+        }
+        fragment
+    }
+
+    /// Inserts a fragment with freshly allocated node references, and returns the mapping from
+    /// fragment node references to inserted node references.
+    ///
+    /// If `keep_incoming` is true and the fragment was extracted from this graph, links from
+    /// outside the original selection are restored for all source nodes that still exist.
+    /// Fragments from other graphs never keep incoming links, since their node references do not
+    /// refer to nodes in this graph.
+    pub fn insert_fragment(
+        &mut self,
+        fragment: &Fragment,
+        keep_incoming: bool,
+    ) -> HashMap<NodeRef, NodeRef> {
+        let mut mapping = HashMap::new();
+        for (node_ref, node) in &fragment.nodes {
+            mapping.insert(*node_ref, self.add_node(node.clone()));
+        }
+
+        for (out_node, out_prop, in_node, in_prop) in &fragment.links {
+            self.link(mapping[out_node], *out_prop, mapping[in_node], *in_prop);
+        }
+
+        if keep_incoming && fragment.source == Some(self.id()) {
+            let inserted: HashSet<_> = mapping.values().cloned().collect();
+            for (out_node, out_prop, in_node, in_prop) in &fragment.incoming {
+                if self.node(out_node).is_some() && !inserted.contains(out_node) {
+                    self.link(*out_node, *out_prop, mapping[in_node], *in_prop);
+                }
+            }
+        }
+
+        mapping
+    }
+
+    /// Duplicates the selected nodes and the links among them, and returns the mapping from
+    /// selected node references to their copies (see [Graph::insert_fragment]).
+    pub fn duplicate(
+        &mut self,
+        selection: &[NodeRef],
+        keep_incoming: bool,
+    ) -> HashMap<NodeRef, NodeRef> {
+        let fragment = self.extract(selection);
+        self.insert_fragment(&fragment, keep_incoming)
+    }
+}
+
+#[test]
+fn copy_paste() {
+    let mut graph = Graph::new();
+    let source = graph.add_node(Node::empty("source".into()));
+    let a = graph.add_node(Node::empty("a".into()));
+    let b = graph.add_node(Node::empty("b".into()));
+    let sink = graph.add_node(Node::empty("sink".into()));
+    graph.link(source, 0, a, 0);
+    graph.link(a, 0, b, 1);
+    graph.link(b, 0, sink, 0);
+
+    let fragment = graph.extract(&[a, b]);
+    assert_eq!(fragment.nodes.len(), 2);
+    assert_eq!(fragment.links, vec![(a, 0, b, 1)]);
+    assert_eq!(fragment.incoming, vec![(source, 0, a, 0)]);
+
+    let mapping = graph.duplicate(&[a, b], true);
+    let (a2, b2) = (mapping[&a], mapping[&b]);
+    assert!(a2 != a && a2 != b && b2 != a && b2 != b);
+    assert_eq!(
+        graph.node_inputs(a2).collect::<Vec<_>>(),
+        vec![(source, 0, 0)]
+    );
+    assert_eq!(graph.node_inputs(b2).collect::<Vec<_>>(), vec![(a2, 0, 1)]);
+    assert_eq!(graph.node_outputs(b2).count(), 0);
+
+    let mut other = Graph::new();
+    let mapping = other.insert_fragment(&fragment, true);
+    assert_eq!(other.iter_nodes().count(), 2);
+    assert_eq!(other.node_inputs(mapping[&a]).count(), 0);
+}
+
+#[test]
+fn paste_into_other_graph() {
+    let mut graph = Graph::new();
+    let source = graph.add_node(Node::empty("source".into()));
+    let a = graph.add_node(Node::empty("a".into()));
+    graph.link(source, 0, a, 0);
+    let fragment = graph.extract(&[a]);
+
+    // `source` refers to an unrelated node in this graph
+    let mut other = Graph::new();
+    let other_source = other.add_node(Node::empty("other".into()));
+    assert_eq!(other_source, source);
+    let mapping = other.insert_fragment(&fragment, true);
+    assert_eq!(other.node_inputs(mapping[&a]).count(), 0);
+    assert_eq!(other.node_outputs(other_source).count(), 0);
+
+    // a clone diverges from the original graph
+    let mut clone = graph.clone();
+    let mapping = clone.insert_fragment(&fragment, true);
+    assert_eq!(clone.node_inputs(mapping[&a]).count(), 0);
+
+    let mapping = graph.insert_fragment(&fragment, true);
+    assert_eq!(
+        graph.node_inputs(mapping[&a]).collect::<Vec<_>>(),
+        vec![(source, 0, 0)]
+    );
+}
@@ -1,12 +1,73 @@
-use crate::node::Node;
+use crate::data::{Value, ValueType};
+use crate::eval::{Multiplicity, NodeSchema, SchemaRegistry};
+use crate::node::events::{Events, GraphEvent};
+use crate::node::history::History;
+use crate::node::{Node, PropertyError};
 use crate::util::{BSMap, ValueSet};
-use std::collections::{HashMap, HashSet, VecDeque};
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::{cmp, ops};
 
+/// The greatest ID that may be allocated for a node. Greater IDs are reserved for the contents of
+/// flattened groups.
+pub const MAX_NODE_ID: u64 = (1 << 63) - 1;
+
 /// A reference to a node in a graph.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+///
+/// References have no generation, so a reference to a removed node can only be told apart from
+/// other nodes as long as the graph doesn’t reuse IDs (see [Graph::add_node]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
 pub struct NodeRef(pub(crate) u64);
 
+impl NodeRef {
+    /// Creates a node reference from a raw ID, e.g. when loading a file.
+    pub fn from_id(id: u64) -> NodeRef {
+        NodeRef(id)
+    }
+
+    /// Returns the raw ID.
+    pub fn id(self) -> u64 {
+        self.0
+    }
+}
+
+/// Identifies a graph instance, e.g. to tell whether node references in a fragment refer to it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct GraphId(u64);
+
+/// A unique [GraphId] for a graph.
+///
+/// Cloning a graph assigns a new ID because the clones may allocate the same node IDs to different
+/// nodes from then on.
+#[derive(Debug)]
+struct UniqueGraphId(GraphId);
+
+impl UniqueGraphId {
+    fn new() -> UniqueGraphId {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+        UniqueGraphId(GraphId(NEXT_ID.fetch_add(1, Ordering::Relaxed)))
+    }
+}
+
+impl Clone for UniqueGraphId {
+    fn clone(&self) -> UniqueGraphId {
+        UniqueGraphId::new()
+    }
+}
+
+/// Node ID errors.
+#[derive(Debug, Fail, Clone, PartialEq)]
+pub enum NodeIdError {
+    /// A node with the ID already exists.
+    #[fail(display = "node ID {:?} is already in use", _0)]
+    Occupied(NodeRef),
+
+    /// The ID is zero or greater than [MAX_NODE_ID].
+    #[fail(display = "node ID {:?} is reserved", _0)]
+    Reserved(NodeRef),
+}
+
 /// Link metadata.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 struct Link {
@@ -74,6 +135,11 @@ impl Links {
             })
     }
 
+    /// Returns true if the link exists.
+    pub fn contains(&self, from_out: NodeRef, to_in: NodeRef, link: Link) -> bool {
+        self.0.contains_entry(&LinkKey { from_out, to_in }, &link)
+    }
+
     /// Inserts a link.
     pub fn insert(&mut self, from_out: NodeRef, to_in: NodeRef, link: Link) {
         self.0.insert_value(LinkKey { from_out, to_in }, link);
@@ -122,16 +188,18 @@ impl Links {
         }
     }
 
-    /// Remaps node properties. Returns true if some links were dropped.
+    /// Remaps node properties using separate mappings for output and input properties. Returns
+    /// true if some links were dropped.
     pub fn remap_with_mapping(
         &mut self,
-        nodes: HashSet<NodeRef>,
-        mapping: HashMap<usize, usize>,
+        nodes: &HashSet<NodeRef>,
+        out_mapping: &HashMap<usize, usize>,
+        in_mapping: &HashMap<usize, usize>,
     ) -> bool {
         let mut links_to_drop = Vec::new();
         for (k, v) in self.0.iter_mut() {
             if nodes.contains(&k.from_out) {
-                if let Some(new) = mapping.get(&v.out_prop) {
+                if let Some(new) = out_mapping.get(&v.out_prop) {
                     v.out_prop = *new;
                 } else {
                     links_to_drop.push((*k, *v));
@@ -139,7 +207,7 @@ impl Links {
                 }
             }
             if nodes.contains(&k.to_in) {
-                if let Some(new) = mapping.get(&v.in_prop) {
+                if let Some(new) = in_mapping.get(&v.in_prop) {
                     v.in_prop = *new;
                 } else {
                     links_to_drop.push((*k, *v));
@@ -165,6 +233,95 @@ pub enum OrderError {
     Cycle(Vec<NodeRef>),
 }
 
+/// Link validation errors.
+#[derive(Debug, Fail, Clone, PartialEq)]
+pub enum LinkError {
+    /// A linked node does not exist.
+    #[fail(display = "missing node: {:?}", _0)]
+    MissingNode(NodeRef),
+
+    /// A linked node has an unknown node type.
+    #[fail(display = "missing node type: {}", _0)]
+    MissingNodeType(String),
+
+    /// The output port does not exist on the node.
+    #[fail(display = "node {:?} has no output #{}", _0, _1)]
+    NoSuchOutput(NodeRef, usize),
+
+    /// The input port does not exist on the node.
+    #[fail(display = "node {:?} has no input #{}", _0, _1)]
+    NoSuchInput(NodeRef, usize),
+
+    /// The output produces none of the types that the input accepts (output types, input types).
+    #[fail(
+        display = "output types {:?} are not accepted by input types {:?}",
+        _0, _1
+    )]
+    TypeMismatch(Vec<ValueType>, Vec<ValueType>),
+
+    /// The input only accepts one link and is already linked.
+    #[fail(display = "input #{} on node {:?} is already linked", _1, _0)]
+    InputOccupied(NodeRef, usize),
+}
+
+/// A problem found by graph validation.
+#[derive(Debug, Clone, PartialEq)]
+pub enum GraphProblem {
+    /// The nodes form a cycle.
+    Cycle(Vec<NodeRef>),
+
+    /// The output node does not exist.
+    MissingOutput(NodeRef),
+
+    /// A link (out node, out prop, in node, in prop) refers to a node that does not exist.
+    DanglingLink(NodeRef, usize, NodeRef, usize),
+
+    /// The node does not contribute to the output.
+    Unreachable(NodeRef),
+
+    /// The node’s type is not known to the renderer.
+    UnknownNodeType(NodeRef, String),
+
+    /// A link (out node, out prop, in node, in prop) refers to a port that does not exist on its
+    /// node.
+    NoSuchPort(NodeRef, usize, NodeRef, usize),
+
+    /// A link (out node, out prop, in node, in prop) connects incompatible types.
+    TypeMismatch(NodeRef, usize, NodeRef, usize),
+
+    /// An input port (node, prop) that accepts only one link has multiple.
+    InputOccupied(NodeRef, usize),
+
+    /// A node that is not a data node is an input to the camera.
+    NonDataCameraInput(NodeRef),
+}
+
+impl GraphProblem {
+    /// Returns all nodes involved in this problem.
+    pub fn nodes(&self) -> Vec<NodeRef> {
+        match self {
+            GraphProblem::Cycle(nodes) => nodes.clone(),
+            GraphProblem::MissingOutput(node)
+            | GraphProblem::Unreachable(node)
+            | GraphProblem::UnknownNodeType(node, _)
+            | GraphProblem::InputOccupied(node, _)
+            | GraphProblem::NonDataCameraInput(node) => vec![*node],
+            GraphProblem::DanglingLink(a, _, b, _)
+            | GraphProblem::NoSuchPort(a, _, b, _)
+            | GraphProblem::TypeMismatch(a, _, b, _) => vec![*a, *b],
+        }
+    }
+}
+
+/// State for Tarjan’s strongly connected components algorithm.
+struct SccState {
+    index: u64,
+    indices: BSMap<NodeRef, (u64, u64)>,
+    stack: Vec<NodeRef>,
+    on_stack: BSMap<NodeRef, ()>,
+    components: Vec<Vec<NodeRef>>,
+}
+
 struct ToposortState<'a> {
     order: &'a mut Vec<NodeRef>,
     visiting: BSMap<NodeRef, ()>,
@@ -175,53 +332,145 @@ struct ToposortState<'a> {
 /// A node graph.
 #[derive(Debug, Clone)]
 pub struct Graph {
-    nodes: BSMap<NodeRef, Node>,
+    id: UniqueGraphId,
+    pub(super) nodes: BSMap<NodeRef, Node>,
     links: Links,
     io_node: NodeRef,
     order: Option<Vec<NodeRef>>,
     dirty_nodes: BSMap<NodeRef, ()>,
+    next_id: Option<u64>,
+    reserved_ids: BTreeSet<u64>,
+    animated: BSMap<NodeRef, Vec<(usize, Value)>>,
+    pub(super) history: History,
+    pub(super) events: Events,
 }
 
 impl Graph {
     /// Creates a new node graph.
     pub fn new() -> Graph {
         Graph {
+            id: UniqueGraphId::new(),
             nodes: BSMap::new(),
             links: Links::new(),
             io_node: NodeRef(0),
             order: None,
             dirty_nodes: BSMap::new(),
+            next_id: Some(1),
+            reserved_ids: BTreeSet::new(),
+            animated: BSMap::new(),
+            history: History::new(),
+            events: Events::new(),
         }
     }
 
+    /// Returns the ID of this graph instance.
+    pub fn id(&self) -> GraphId {
+        self.id.0
+    }
+
     /// Invalidates topological sorting.
     fn invalidate_order(&mut self) {
         self.order = None;
     }
 
-    /// Adds a node to the graph and returns a (weak) reference.
-    pub fn add_node(&mut self, node: Node) -> NodeRef {
-        let next_id = self
-            .nodes
-            .greatest_key()
-            .map_or(Some(1), |k| k.0.checked_add(1));
+    /// Allocates a node ID.
+    ///
+    /// IDs are allocated in increasing order and are not reused, so stale references to removed
+    /// nodes will never refer to a different node. IDs that were chosen by the caller are skipped.
+    /// Only once all IDs up to [MAX_NODE_ID] have been used, the smallest unused ID is reused;
+    /// from then on, a stale reference may silently refer to a new node.
+    fn allocate_id(&mut self) -> NodeRef {
+        if let Some(id) = self.next_id {
+            self.advance_next_id();
+            return NodeRef(id);
+        }
 
-        let node_ref = if let Some(next_id) = next_id {
-            NodeRef(next_id)
-        } else {
-            // TODO: handle overflow
-            unimplemented!("ID overflow");
-        };
+        // the graph can’t possibly hold every ID, so there must be a gap
+        let mut id = 1;
+        for (node_ref, _) in self.nodes.iter() {
+            if node_ref.0 != id {
+                break;
+            }
+            id += 1;
+        }
+        NodeRef(id)
+    }
+
+    /// Advances the allocation counter past the next ID and all reserved IDs following it.
+    fn advance_next_id(&mut self) {
+        while let Some(id) = self.next_id {
+            self.next_id = if id < MAX_NODE_ID { Some(id + 1) } else { None };
+            match self.next_id {
+                Some(next_id) if self.reserved_ids.remove(&next_id) => (),
+                _ => break,
+            }
+        }
+    }
 
+    /// Records that an ID is in use so that it won’t be allocated, even after the node has been
+    /// removed.
+    fn reserve_id(&mut self, node_ref: NodeRef) {
+        match self.next_id {
+            Some(next_id) if node_ref.0 == next_id => self.advance_next_id(),
+            Some(next_id) if node_ref.0 > next_id && node_ref.0 <= MAX_NODE_ID => {
+                self.reserved_ids.insert(node_ref.0);
+            }
+            _ => (),
+        }
+    }
+
+    /// Adds a node to the graph and returns a (weak) reference.
+    ///
+    /// The reference will not refer to another node after this one is removed, unless all IDs up
+    /// to [MAX_NODE_ID] have been allocated. IDs are reused from then on, and stale references
+    /// are not detected.
+    pub fn add_node(&mut self, mut node: Node) -> NodeRef {
+        let node_ref = self.allocate_id();
+
+        node.take_changed();
         self.nodes.insert(node_ref, node);
         self.dirty_nodes.insert(node_ref, ());
+        self.emit(GraphEvent::NodeAdded(node_ref));
         node_ref
     }
 
+    /// Adds a node with a caller-chosen ID, e.g. when loading a file. Subsequently added nodes
+    /// will not be allocated this ID.
+    pub fn add_node_with_id(&mut self, node_ref: NodeRef, node: Node) -> Result<(), NodeIdError> {
+        if node_ref.0 == 0 || node_ref.0 > MAX_NODE_ID {
+            return Err(NodeIdError::Reserved(node_ref));
+        }
+        if self.nodes.contains_key(&node_ref) {
+            return Err(NodeIdError::Occupied(node_ref));
+        }
+        self.insert_node(node_ref, node);
+        Ok(())
+    }
+
+    /// Returns true if the graph contains the node. This is false for stale references to
+    /// removed nodes.
+    pub fn contains_node(&self, node: NodeRef) -> bool {
+        self.nodes.contains_key(&node)
+    }
+
+    /// Inserts a node with a specific reference, replacing any existing node.
+    pub(super) fn insert_node(&mut self, node_ref: NodeRef, mut node: Node) {
+        self.invalidate_order();
+        self.reserve_id(node_ref);
+        node.take_changed();
+        self.nodes.insert(node_ref, node);
+        self.dirty_nodes.insert(node_ref, ());
+        self.emit(GraphEvent::NodeAdded(node_ref));
+    }
+
     /// Sets the output node.
     pub fn set_output(&mut self, node: NodeRef) {
+        let changed = self.io_node != node;
         self.io_node = node;
         self.invalidate_order();
+        if changed {
+            self.emit(GraphEvent::OutputNodeChanged(node));
+        }
     }
 
     /// Returns the output node ID.
@@ -240,21 +489,193 @@ impl Graph {
         // without using a proxy object or something, so just assume it will
         // always be mutated with node_mut
         self.dirty_nodes.insert(*node, ());
-        self.nodes.get_mut(node)
+        let node_ref = *node;
+        let node = self.nodes.get_mut(node)?;
+        self.events.touch(node_ref, node.enabled);
+        Some(node)
     }
 
     /// Removes a node from the graph, along with any links.
     pub fn remove_node(&mut self, node: NodeRef) -> Option<Node> {
         self.invalidate_order();
+        let links: Vec<_> = self
+            .node_inputs(node)
+            .map(|(other, out_prop, in_prop)| (other, out_prop, node, in_prop))
+            .chain(
+                self.node_outputs(node)
+                    .filter(|(other, ..)| *other != node)
+                    .map(|(other, out_prop, in_prop)| (node, out_prop, other, in_prop)),
+            )
+            .collect();
         self.links.remove_node(node);
-        self.nodes.remove(&node)
+        for (out_node, out_prop, in_node, in_prop) in links {
+            self.emit(GraphEvent::Unlinked(out_node, out_prop, in_node, in_prop));
+        }
+
+        let removed = self.nodes.remove(&node);
+        if removed.is_some() {
+            self.emit(GraphEvent::NodeRemoved(node));
+        }
+        removed
     }
 
     /// Links two node properties.
     pub fn link(&mut self, out_node: NodeRef, out_prop: usize, in_node: NodeRef, in_prop: usize) {
         self.invalidate_order();
-        self.links
-            .insert(out_node, in_node, Link { out_prop, in_prop });
+        let link = Link { out_prop, in_prop };
+        if !self.links.contains(out_node, in_node, link) {
+            self.links.insert(out_node, in_node, link);
+            self.emit(GraphEvent::Linked(out_node, out_prop, in_node, in_prop));
+        }
+    }
+
+    /// Checks whether two node properties may be linked according to the node types’ schemas.
+    ///
+    /// Links from and to nodes whose type has no schema in the registry (or an empty one) and
+    /// group instances are not checked any further. Node types are not known here, so inputs that
+    /// accept textures are assumed to rasterize drawables, as graphics nodes do.
+    pub fn check_link(
+        &self,
+        schemas: &SchemaRegistry,
+        out_node: NodeRef,
+        out_prop: usize,
+        in_node: NodeRef,
+        in_prop: usize,
+    ) -> Result<(), LinkError> {
+        let schema = |node_ref: NodeRef| match self.node(&node_ref) {
+            // groups don’t have a fixed schema
+            Some(node) if node.group_def().is_some() => Ok(None),
+            Some(node) => Ok(schemas
+                .get(&node.node_type)
+                .filter(|schema| !schema.is_empty())),
+            None => Err(LinkError::MissingNode(node_ref)),
+        };
+        let out_schema = schema(out_node)?;
+        let in_schema = schema(in_node)?;
+
+        let out_port = match out_schema {
+            Some(schema) => match schema.output(out_prop) {
+                Some(port) => Some(port),
+                None => return Err(LinkError::NoSuchOutput(out_node, out_prop)),
+            },
+            None => None,
+        };
+        let in_port = match in_schema {
+            Some(schema) => match schema.input(in_prop) {
+                Some(port) => port,
+                None => return Err(LinkError::NoSuchInput(in_node, in_prop)),
+            },
+            None => return Ok(()),
+        };
+
+        if in_port.multiplicity == Multiplicity::One {
+            let occupied = self
+                .node_inputs(in_node)
+                .any(|(node, prop, p)| p == in_prop && (node, prop) != (out_node, out_prop));
+            if occupied {
+                return Err(LinkError::InputOccupied(in_node, in_prop));
+            }
+        }
+
+        if let Some(out_port) = out_port {
+            if !out_port.can_link_to(in_port, true) {
+                return Err(LinkError::TypeMismatch(
+                    out_port.types.clone(),
+                    in_port.types.clone(),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Validates and links two node properties (see [Graph::check_link]).
+    pub fn link_checked(
+        &mut self,
+        schemas: &SchemaRegistry,
+        out_node: NodeRef,
+        out_prop: usize,
+        in_node: NodeRef,
+        in_prop: usize,
+    ) -> Result<(), LinkError> {
+        self.check_link(schemas, out_node, out_prop, in_node, in_prop)?;
+        self.link(out_node, out_prop, in_node, in_prop);
+        Ok(())
+    }
+
+    /// Links two node properties by name, using the node types’ schemas.
+    pub fn link_named(
+        &mut self,
+        schemas: &SchemaRegistry,
+        out_node: NodeRef,
+        out_name: &str,
+        in_node: NodeRef,
+        in_name: &str,
+    ) -> Result<(), PropertyError> {
+        let out_prop = self
+            .node(&out_node)
+            .ok_or(PropertyError::MissingNode(out_node))?
+            .output_index(schemas, out_name)?;
+        let in_prop = self
+            .node(&in_node)
+            .ok_or(PropertyError::MissingNode(in_node))?
+            .input_index(schemas, in_name)?;
+        self.link(out_node, out_prop, in_node, in_prop);
+        Ok(())
+    }
+
+    /// Remaps property indices of all nodes of a type, e.g. because the node type’s ports were
+    /// renumbered. Property values, keyframe tracks, and links whose index is not in the
+    /// respective mapping are dropped. Returns true if anything was dropped.
+    ///
+    /// Moved and dropped property values are reported as property changes at both their old and
+    /// new indices.
+    pub fn remap_properties(
+        &mut self,
+        node_type: &str,
+        out_mapping: &HashMap<usize, usize>,
+        in_mapping: &HashMap<usize, usize>,
+    ) -> bool {
+        let nodes: HashSet<_> = self
+            .nodes
+            .iter()
+            .filter(|(_, node)| node.node_type == node_type)
+            .map(|(node_ref, _)| *node_ref)
+            .collect();
+
+        let mut dropped = false;
+        for node in &nodes {
+            // through node_mut so that moved values are reported as property changes
+            dropped |= self.node_mut(node).unwrap().remap(in_mapping);
+            // sampled values use the old indices; they will be sampled again by Graph::animate
+            self.animated.remove(node);
+        }
+        self.invalidate_order();
+
+        let dropped_links = self
+            .links
+            .remap_with_mapping(&nodes, out_mapping, in_mapping);
+        dropped || dropped_links
+    }
+
+    /// Remaps property indices of all nodes of a type by matching port names between the schema
+    /// the graph was created with and the current schema. This allows loading serialized graphs
+    /// after a node type’s ports were renumbered. Returns true if anything was dropped.
+    pub fn remap_with_schema(
+        &mut self,
+        node_type: &str,
+        saved: &NodeSchema,
+        current: &NodeSchema,
+    ) -> bool {
+        let out_mapping = saved
+            .outputs()
+            .filter_map(|port| Some((port.index, current.output_named(&port.name)?.index)))
+            .collect();
+        let in_mapping = saved
+            .inputs()
+            .filter_map(|port| Some((port.index, current.input_named(&port.name)?.index)))
+            .collect();
+        self.remap_properties(node_type, &out_mapping, &in_mapping)
     }
 
     /// Returns an iterator over all inputs of a node in (other node, output prop, input prop on
@@ -297,8 +718,13 @@ impl Graph {
         in_prop: usize,
     ) -> bool {
         self.invalidate_order();
-        self.links
-            .remove(out_node, in_node, Link { out_prop, in_prop })
+        let removed = self
+            .links
+            .remove(out_node, in_node, Link { out_prop, in_prop });
+        if removed {
+            self.emit(GraphEvent::Unlinked(out_node, out_prop, in_node, in_prop));
+        }
+        removed
     }
 
     /// Iterates over all nodes.
@@ -383,6 +809,127 @@ impl Graph {
         Ok(())
     }
 
+    /// Finds the strongly connected components containing the given node and its inputs.
+    fn strong_connect(&self, node: NodeRef, state: &mut SccState) {
+        let index = state.index;
+        state.index += 1;
+        state.indices.insert(node, (index, index));
+        state.stack.push(node);
+        state.on_stack.insert(node, ());
+
+        for (input, ..) in self.node_inputs(node) {
+            if !state.indices.contains_key(&input) {
+                self.strong_connect(input, state);
+                let input_low = state.indices[&input].1;
+                let entry = &mut state.indices[&node];
+                entry.1 = entry.1.min(input_low);
+            } else if state.on_stack.contains_key(&input) {
+                let input_index = state.indices[&input].0;
+                let entry = &mut state.indices[&node];
+                entry.1 = entry.1.min(input_index);
+            }
+        }
+
+        if state.indices[&node].0 == state.indices[&node].1 {
+            let mut component = Vec::new();
+            while let Some(member) = state.stack.pop() {
+                state.on_stack.remove(&member);
+                component.push(member);
+                if member == node {
+                    break;
+                }
+            }
+            state.components.push(component);
+        }
+    }
+
+    /// Returns all cycles in the graph, including those that don’t affect the output.
+    pub fn cycles(&self) -> Vec<Vec<NodeRef>> {
+        let mut state = SccState {
+            index: 0,
+            indices: BSMap::new(),
+            stack: Vec::new(),
+            on_stack: BSMap::new(),
+            components: Vec::new(),
+        };
+
+        for (node, _) in self.nodes.iter() {
+            if !state.indices.contains_key(node) {
+                self.strong_connect(*node, &mut state);
+            }
+        }
+
+        state
+            .components
+            .into_iter()
+            .filter(|component| {
+                let node = component[0];
+                component.len() > 1 || self.node_inputs(node).any(|(n, ..)| n == node)
+            })
+            .collect()
+    }
+
+    /// Returns all nodes that the output node depends on, including the output node.
+    pub fn reachable_nodes(&self) -> HashSet<NodeRef> {
+        let mut reachable = HashSet::new();
+        let mut queue = VecDeque::new();
+        queue.push_back(self.io_node);
+        while let Some(node) = queue.pop_front() {
+            if reachable.insert(node) {
+                for (input, ..) in self.node_inputs(node) {
+                    queue.push_back(input);
+                }
+            }
+        }
+        reachable
+    }
+
+    /// Checks the graph structure and returns all problems found.
+    ///
+    /// This finds cycles anywhere in the graph, links to nodes that don’t exist, links that are
+    /// invalid according to the node types’ schemas (see [Graph::check_link]), and nodes that
+    /// don’t contribute to the output. Problems that depend on node types are found by
+    /// [crate::render::Renderer::validate].
+    pub fn validate(&self, schemas: &SchemaRegistry) -> Vec<GraphProblem> {
+        let mut problems: Vec<_> = self.cycles().into_iter().map(GraphProblem::Cycle).collect();
+
+        for ((out_node, out_prop), (in_node, in_prop)) in self.iter_links() {
+            let problem = match self.check_link(schemas, out_node, out_prop, in_node, in_prop) {
+                Ok(()) => continue,
+                Err(LinkError::MissingNode(_)) => {
+                    GraphProblem::DanglingLink(out_node, out_prop, in_node, in_prop)
+                }
+                // node types are not known here
+                Err(LinkError::MissingNodeType(_)) => continue,
+                Err(LinkError::NoSuchOutput(..)) | Err(LinkError::NoSuchInput(..)) => {
+                    GraphProblem::NoSuchPort(out_node, out_prop, in_node, in_prop)
+                }
+                Err(LinkError::TypeMismatch(..)) => {
+                    GraphProblem::TypeMismatch(out_node, out_prop, in_node, in_prop)
+                }
+                Err(LinkError::InputOccupied(node, prop)) => {
+                    GraphProblem::InputOccupied(node, prop)
+                }
+            };
+            if !problems.contains(&problem) {
+                problems.push(problem);
+            }
+        }
+
+        if self.nodes.contains_key(&self.io_node) {
+            let reachable = self.reachable_nodes();
+            for (node, _) in self.nodes.iter() {
+                if !reachable.contains(node) {
+                    problems.push(GraphProblem::Unreachable(*node));
+                }
+            }
+        } else {
+            problems.push(GraphProblem::MissingOutput(self.io_node));
+        }
+
+        problems
+    }
+
     /// Propagates dirtiness of dirty nodes to all connected nodes.
     pub fn propagate_dirtiness(&mut self) {
         let mut dirty_nodes: VecDeque<_> = self.dirty_nodes.iter().map(|(k, _)| *k).collect();
@@ -401,6 +948,60 @@ impl Graph {
         }
     }
 
+    /// Samples all animated properties at the given time (in seconds), and returns the nodes
+    /// whose animated values changed. Only these nodes are marked dirty.
+    ///
+    /// Sampled values override property values during evaluation (see
+    /// [Graph::property_values]), but the authored property values are left untouched.
+    pub fn animate(&mut self, time: f64) -> Vec<NodeRef> {
+        let mut animated = BSMap::new();
+        let mut changed = Vec::new();
+        for (node_ref, node) in self.nodes.iter() {
+            if !node.is_animated() {
+                continue;
+            }
+            let values = node.sample_tracks(time);
+            if self.animated.get(node_ref) != Some(&values) {
+                changed.push(*node_ref);
+            }
+            animated.insert(*node_ref, values);
+        }
+        for (node_ref, _) in self.animated.iter() {
+            // tracks were removed
+            if !animated.contains_key(node_ref) && self.nodes.contains_key(node_ref) {
+                changed.push(*node_ref);
+            }
+        }
+        self.animated = animated;
+
+        for node_ref in &changed {
+            self.dirty_nodes.insert(*node_ref, ());
+        }
+        changed
+    }
+
+    /// Returns the property values of a node as they should be evaluated: values sampled by
+    /// [Graph::animate], then property values that are not animated.
+    pub fn property_values<'a>(
+        &'a self,
+        node: &NodeRef,
+    ) -> impl Iterator<Item = (usize, &'a Value)> + 'a {
+        let animated = self
+            .animated
+            .get(node)
+            .map_or(&[][..], |values| &values[..]);
+        let props = self
+            .nodes
+            .get(node)
+            .into_iter()
+            .flat_map(|node| node.props.iter());
+        animated.iter().map(|(k, v)| (*k, v)).chain(
+            props
+                .filter(move |(k, _)| !animated.iter().any(|(prop, _)| prop == k))
+                .map(|(k, v)| (*k, v)),
+        )
+    }
+
     /// Returns whether a node is marked dirty.
     pub fn is_dirty(&self, node: &NodeRef) -> bool {
         self.dirty_nodes.contains_key(&node)
@@ -523,3 +1124,337 @@ fn ordering() {
         }
     }
 }
+
+#[test]
+fn events() {
+    let mut graph = Graph::new();
+    let subscriber = graph.subscribe();
+
+    let a = graph.add_node(Node::empty("a".into()));
+    let b = graph.add_node(Node::empty("b".into()));
+    graph.link(a, 0, b, 0);
+    graph.link(a, 0, b, 0);
+    graph.node_mut(&b).unwrap().set(1, 2.);
+    graph.node_mut(&a).unwrap().enabled = false;
+    graph.set_output(b);
+    graph.remove_node(a);
+
+    assert_eq!(
+        graph.drain_events(subscriber),
+        vec![
+            GraphEvent::NodeAdded(a),
+            GraphEvent::NodeAdded(b),
+            GraphEvent::Linked(a, 0, b, 0),
+            GraphEvent::PropertyChanged(b, 1),
+            GraphEvent::EnabledChanged(a, false),
+            GraphEvent::OutputNodeChanged(b),
+            GraphEvent::Unlinked(a, 0, b, 0),
+            GraphEvent::NodeRemoved(a),
+        ]
+    );
+    assert!(graph.drain_events(subscriber).is_empty());
+
+    // subscriptions are not confused with those of other graphs
+    let mut other = Graph::new();
+    assert!(!other.is_subscribed(subscriber));
+    assert_ne!(other.subscribe(), subscriber);
+}
+
+#[test]
+fn remapping() {
+    use crate::data::{Interpolation, Track, Value};
+    use crate::eval::PortSchema;
+
+    let saved = NodeSchema::new(vec![
+        PortSchema::input(0_usize, "a", &[]),
+        PortSchema::input(1_usize, "b", &[]),
+        PortSchema::output(2_usize, "out", &[]),
+    ]);
+    let current = NodeSchema::new(vec![
+        PortSchema::input(1_usize, "a", &[]),
+        PortSchema::output(0_usize, "out", &[]),
+    ]);
+
+    let mut graph = Graph::new();
+    let mut a = Node::empty("x".into());
+    a.set(0, 1.);
+    a.set(1, 2.);
+    let a = graph.add_node(a);
+    let mut b = Node::empty("x".into());
+    let mut track = Track::new();
+    track.insert(0., 3., Interpolation::Linear);
+    b.set_track(0, track.clone());
+    b.set_track(1, track.clone());
+    let b = graph.add_node(b);
+    graph.link(a, 2, b, 0);
+    graph.link(a, 2, b, 1);
+    graph.animate(0.);
+    let subscriber = graph.subscribe();
+
+    // property b no longer exists, so things must have been dropped
+    assert!(graph.remap_with_schema("x", &saved, &current));
+    assert_eq!(graph[a].get(0), None);
+    assert_eq!(graph[a].get(1), Some(&Value::Float(1.)));
+    assert_eq!(graph.node_inputs(b).collect::<Vec<_>>(), vec![(a, 0, 1)]);
+    assert_eq!(graph[b].tracks().collect::<Vec<_>>(), vec![(1, &track)]);
+    assert_eq!(graph.animate(0.), vec![b]);
+    assert_eq!(
+        graph.property_values(&b).collect::<Vec<_>>(),
+        vec![(1, &Value::Float(3.))]
+    );
+
+    // moved values are reported at both indices
+    assert!(graph.is_dirty(&a));
+    assert_eq!(
+        graph.drain_events(subscriber),
+        vec![
+            GraphEvent::PropertyChanged(a, 0),
+            GraphEvent::PropertyChanged(a, 1),
+        ]
+    );
+}
+
+#[test]
+fn validation() {
+    use crate::eval::PortSchema;
+
+    let mut graph = Graph::new();
+
+    // a --> out     b <--> c
+    let a = graph.add_node(Node::empty("a".into()));
+    let out = graph.add_node(Node::empty("out".into()));
+    let b = graph.add_node(Node::empty("b".into()));
+    let c = graph.add_node(Node::empty("c".into()));
+    graph.link(a, 0, out, 0);
+    graph.link(b, 0, c, 0);
+    graph.link(c, 0, b, 0);
+    graph.link(NodeRef(100), 0, out, 1);
+    graph.set_output(out);
+
+    let problems = graph.validate(&SchemaRegistry::new());
+    assert_eq!(problems.len(), 4);
+    assert!(problems.contains(&GraphProblem::DanglingLink(NodeRef(100), 0, out, 1)));
+    assert!(problems.contains(&GraphProblem::Unreachable(b)));
+    assert!(problems.contains(&GraphProblem::Unreachable(c)));
+    let cycle = problems
+        .iter()
+        .find_map(|problem| match problem {
+            GraphProblem::Cycle(nodes) => Some(nodes),
+            _ => None,
+        })
+        .expect("cycle not found");
+    assert_eq!(cycle.len(), 2);
+    assert!(cycle.contains(&b) && cycle.contains(&c));
+
+    // links are checked against schemas, also if they were not made with Graph::link_checked
+    let mut schemas = SchemaRegistry::new();
+    schemas.insert(
+        "number",
+        NodeSchema::new(vec![PortSchema::output(
+            0_usize,
+            "out",
+            &[ValueType::Float],
+        )]),
+    );
+    schemas.insert(
+        "text",
+        NodeSchema::new(vec![PortSchema::input(0_usize, "in", &[ValueType::String])]),
+    );
+    let mut graph = Graph::new();
+    let number = graph.add_node(Node::empty("number".into()));
+    let text = graph.add_node(Node::empty("text".into()));
+    graph.set_output(text);
+    match graph.link_checked(&schemas, number, 0, text, 0) {
+        Err(LinkError::TypeMismatch(..)) => (),
+        res => panic!("unexpected result {:?}", res),
+    }
+    assert_eq!(graph.iter_links().count(), 0);
+    assert_eq!(
+        graph.link_checked(&schemas, number, 1, text, 0),
+        Err(LinkError::NoSuchOutput(number, 1))
+    );
+
+    graph.link(number, 0, text, 0);
+    assert!(graph.validate(&SchemaRegistry::new()).is_empty());
+    assert_eq!(
+        graph.validate(&schemas),
+        vec![GraphProblem::TypeMismatch(number, 0, text, 0)]
+    );
+}
+
+#[test]
+fn debug_export() {
+    use crate::node::DebugOverlay;
+
+    let mut graph = Graph::new();
+    let mut a = Node::empty("a \"quoted\" \\\u{1}".into());
+    a.set(0, 1.5);
+    let a = graph.add_node(a);
+    let b = graph.add_node(Node::empty("b".into()));
+    graph.link(a, 0, b, 1);
+    graph.set_output(b);
+
+    let schemas = SchemaRegistry::new();
+    let dot = graph.to_dot(&schemas, None);
+    assert!(dot.contains("n1 -> n2 [label=\"#0 → #1\"]"));
+    assert!(dot.contains("a \\\"quoted\\\" \\\\"));
+
+    let mut overlay = DebugOverlay::default();
+    overlay.dirty.insert(a);
+    let json = graph.to_json(&schemas, Some(&overlay));
+    let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(json["output"], 2);
+    assert_eq!(json["nodes"][0]["type"], "a \"quoted\" \\\u{1}");
+    assert_eq!(json["nodes"][0]["properties"][0]["summary"], "1.5");
+    assert_eq!(json["nodes"][0]["dirty"], true);
+    assert_eq!(json["nodes"][1]["cached"], false);
+    assert_eq!(
+        json["links"][0],
+        serde_json::json!({
+            "from": 1,
+            "from_port": 0,
+            "from_name": null,
+            "to": 2,
+            "to_port": 1,
+            "to_name": null,
+        })
+    );
+}
+
+#[test]
+fn node_ids() {
+    let mut graph = Graph::new();
+    let a = graph.add_node(Node::empty("a".into()));
+    graph.remove_node(a);
+    let b = graph.add_node(Node::empty("b".into()));
+    assert_ne!(a, b);
+    assert!(!graph.contains_node(a));
+
+    let c = NodeRef::from_id(MAX_NODE_ID - 1);
+    assert_eq!(graph.add_node_with_id(c, Node::empty("c".into())), Ok(()));
+    assert_eq!(
+        graph.add_node_with_id(c, Node::empty("c".into())),
+        Err(NodeIdError::Occupied(c))
+    );
+    assert_eq!(
+        graph.add_node_with_id(NodeRef(0), Node::empty("d".into())),
+        Err(NodeIdError::Reserved(NodeRef(0)))
+    );
+
+    // a high ID doesn’t exhaust the allocator, and stale references stay stale
+    assert_eq!(graph.add_node(Node::empty("e".into())).id(), 3);
+    assert!(!graph.contains_node(a));
+
+    // caller-chosen IDs are skipped, even if the node was removed
+    let d = NodeRef::from_id(5);
+    assert_eq!(graph.add_node_with_id(d, Node::empty("d".into())), Ok(()));
+    graph.remove_node(d);
+    assert_eq!(graph.add_node(Node::empty("f".into())).id(), 4);
+    assert_eq!(graph.add_node(Node::empty("g".into())).id(), 6);
+    assert!(!graph.contains_node(d));
+
+    // once all IDs were allocated, unused ones are reused
+    graph.next_id = Some(MAX_NODE_ID - 2);
+    assert_eq!(
+        graph.add_node(Node::empty("h".into())).id(),
+        MAX_NODE_ID - 2
+    );
+    assert_eq!(graph.add_node(Node::empty("i".into())).id(), MAX_NODE_ID);
+    assert_eq!(graph.add_node(Node::empty("j".into())), a);
+    assert_eq!(graph.add_node(Node::empty("k".into())), d);
+}
+
+#[test]
+fn animation() {
+    use crate::data::{Interpolation, Track};
+
+    let mut graph = Graph::new();
+    let mut track = Track::new();
+    track.insert(0., 0., Interpolation::Linear);
+    track.insert(1., 10., Interpolation::Linear);
+    let mut a = Node::empty("a".into());
+    a.set_track(0, track);
+    let a = graph.add_node(a);
+    let b = graph.add_node(Node::empty("b".into()));
+    let subscriber = graph.subscribe();
+
+    assert_eq!(graph.animate(0.5), vec![a]);
+    assert_eq!(
+        graph.property_values(&a).collect::<Vec<_>>(),
+        vec![(0, &Value::Float(5.))]
+    );
+    // authored values are kept
+    assert_eq!(graph[a].get(0), None);
+    assert!(graph.drain_events(subscriber).is_empty());
+
+    graph.mark_clean(&a);
+    graph.mark_clean(&b);
+    assert!(graph.animate(0.5).is_empty());
+    assert!(!graph.is_dirty(&a));
+    assert_eq!(graph.animate(2.), vec![a]);
+    assert!(graph.animate(3.).is_empty());
+    assert!(graph.is_dirty(&a));
+    assert!(!graph.is_dirty(&b));
+
+    graph.node_mut(&a).unwrap().set(0, 1.);
+    graph.node_mut(&a).unwrap().remove_track(0);
+    graph.mark_clean(&a);
+    assert_eq!(graph.animate(3.), vec![a]);
+    assert_eq!(
+        graph.property_values(&a).collect::<Vec<_>>(),
+        vec![(0, &Value::Float(1.))]
+    );
+}
+
+#[test]
+fn named_properties() {
+    use crate::data::Value;
+    use crate::eval::PortSchema;
+
+    let mut schemas = SchemaRegistry::new();
+    schemas.insert(
+        "a",
+        NodeSchema::new(vec![
+            PortSchema::input(0_usize, "size", &[ValueType::Float]),
+            PortSchema::output(1_usize, "out", &[ValueType::Float]),
+        ]),
+    );
+    schemas.insert(
+        "b",
+        NodeSchema::new(vec![PortSchema::input(2_usize, "in", &[ValueType::Float])]),
+    );
+
+    let mut a = Node::empty("a".into());
+    a.set_named(&schemas, "size", 2.).unwrap();
+    assert_eq!(a.get(0), Some(&Value::Float(2.)));
+    assert_eq!(
+        a.get_named(&schemas, "size").unwrap(),
+        Some(&Value::Float(2.))
+    );
+    match a.set_named(&schemas, "out", 1.) {
+        Err(PropertyError::NoSuchInput(node_type, name)) => {
+            assert_eq!((&*node_type, &*name), ("a", "out"))
+        }
+        res => panic!("unexpected result {:?}", res),
+    }
+    let mut c = Node::empty("c".into());
+    match c.set_named(&schemas, "size", 1.) {
+        Err(PropertyError::UnknownNodeType(node_type)) => assert_eq!(node_type, "c"),
+        res => panic!("unexpected result {:?}", res),
+    }
+
+    let mut graph = Graph::new();
+    let a = graph.add_node(a);
+    let b = graph.add_node(Node::empty("b".into()));
+    graph.link_named(&schemas, a, "out", b, "in").unwrap();
+    assert_eq!(
+        graph.iter_links().collect::<Vec<_>>(),
+        vec![((a, 1), (b, 2))]
+    );
+    assert!(graph.link_named(&schemas, a, "size", b, "in").is_err());
+    assert!(graph.link_named(&schemas, b, "in", a, "size").is_err());
+    match graph.link_named(&schemas, a, "out", NodeRef(100), "in") {
+        Err(PropertyError::MissingNode(node)) => assert_eq!(node, NodeRef(100)),
+        res => panic!("unexpected result {:?}", res),
+    }
+}
@@ -0,0 +1,392 @@
+use crate::data::Value;
+use crate::node::{Graph, Node, NodeRef, MAX_NODE_ID};
+use fnv::FnvHasher;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::hash::Hasher;
+
+/// Node type name of group instances.
+pub const GROUP_NAME: &str = "narwhal.group";
+
+/// The property that holds a group instance’s [Group].
+///
+/// This is outside the range of regular port indices so that group ports may use any index.
+pub const GROUP_DEF_PROP: usize = usize::MAX;
+
+/// Flattened node references of group contents have this bit set to avoid collisions with
+/// top-level nodes.
+const GROUP_NODE_BIT: u64 = MAX_NODE_ID + 1;
+
+/// A group definition: an inner graph with designated input and output nodes.
+///
+/// A group instance node’s input ports correspond to the output ports of the inner input node,
+/// and its output ports correspond to the input ports of the inner output node. Input and output
+/// nodes are placeholders and are never evaluated, so their node type does not matter.
+///
+/// Values set on a group instance’s input ports are passed to inner nodes linked to the
+/// corresponding input node port, which makes it possible to instantiate one group multiple
+/// times with different parameters.
+#[derive(Debug, Clone)]
+pub struct Group {
+    pub graph: Graph,
+    pub input: NodeRef,
+    pub output: NodeRef,
+}
+
+impl Group {
+    /// Creates a new group definition.
+    pub fn new(graph: Graph, input: NodeRef, output: NodeRef) -> Group {
+        Group {
+            graph,
+            input,
+            output,
+        }
+    }
+}
+
+impl Node {
+    /// Creates a new group instance node.
+    pub fn group(group: Group) -> Node {
+        let mut node = Node::empty(GROUP_NAME.into());
+        node.set_any(GROUP_DEF_PROP, group);
+        node
+    }
+
+    /// Returns the group definition if this is a group instance.
+    pub fn group_def(&self) -> Option<&Group> {
+        if self.node_type == GROUP_NAME {
+            self.get_any::<Group>(GROUP_DEF_PROP)
+        } else {
+            None
+        }
+    }
+}
+
+/// Allocates flattened references for group contents and prefixes for group instances.
+///
+/// Both are derived by hashing the path of group instances, so they are stable across calls. In
+/// the unlikely case of a collision, the hash is mixed again until it is unique.
+#[derive(Default)]
+struct FlatIds {
+    /// Flattened references by (prefix, node).
+    refs: HashMap<(u64, u64), NodeRef>,
+    used_refs: HashSet<NodeRef>,
+
+    /// Prefixes by (enclosing prefix, group instance).
+    prefixes: HashMap<(Option<u64>, u64), u64>,
+    used_prefixes: HashSet<u64>,
+}
+
+impl FlatIds {
+    fn flat_ref(&mut self, prefix: u64, node: NodeRef) -> NodeRef {
+        if let Some(flat_ref) = self.refs.get(&(prefix, node.0)) {
+            return *flat_ref;
+        }
+        let mut hash = mix(prefix, node.0);
+        while self.used_refs.contains(&NodeRef(GROUP_NODE_BIT | (hash >> 1))) {
+            hash = mix(hash, node.0);
+        }
+        let flat_ref = NodeRef(GROUP_NODE_BIT | (hash >> 1));
+        self.used_refs.insert(flat_ref);
+        self.refs.insert((prefix, node.0), flat_ref);
+        flat_ref
+    }
+
+    fn prefix(&mut self, outer: Option<u64>, instance: NodeRef) -> u64 {
+        if let Some(prefix) = self.prefixes.get(&(outer, instance.0)) {
+            return *prefix;
+        }
+        let mut prefix = mix(outer.unwrap_or(0), instance.0);
+        while self.used_prefixes.contains(&prefix) {
+            prefix = mix(prefix, instance.0);
+        }
+        self.used_prefixes.insert(prefix);
+        self.prefixes.insert((outer, instance.0), prefix);
+        prefix
+    }
+}
+
+/// A graph being flattened, with a reference to the enclosing group instance, if any.
+struct Scope<'a> {
+    graph: &'a Graph,
+    prefix: Option<u64>,
+    parent: Option<(&'a Scope<'a>, NodeRef, &'a Group)>,
+    ids: &'a RefCell<FlatIds>,
+}
+
+impl<'a> Scope<'a> {
+    /// Returns the flattened reference for a node in this scope.
+    fn flat_ref(&self, node: NodeRef) -> NodeRef {
+        match self.prefix {
+            Some(prefix) => self.ids.borrow_mut().flat_ref(prefix, node),
+            None => node,
+        }
+    }
+
+    /// Returns the prefix for the contents of a group instance in this scope.
+    fn inner_prefix(&self, instance: NodeRef) -> u64 {
+        self.ids.borrow_mut().prefix(self.prefix, instance)
+    }
+
+    /// Returns true if the node is a placeholder of the enclosing group.
+    fn is_placeholder(&self, node: NodeRef) -> bool {
+        match self.parent {
+            Some((_, _, group)) => node == group.input || node == group.output,
+            None => false,
+        }
+    }
+
+    /// Returns false if any enclosing group instance is disabled.
+    fn is_enabled(&self) -> bool {
+        match self.parent {
+            Some((parent, instance, _)) => parent.graph[instance].enabled && parent.is_enabled(),
+            None => true,
+        }
+    }
+
+    /// Resolves the flattened nodes producing the given output, and a constant value if the
+    /// output is a group input that was set to a value on its instance.
+    fn resolve(&self, node: NodeRef, prop: usize) -> (Vec<(NodeRef, usize)>, Option<Value>) {
+        if let Some((parent, instance, group)) = self.parent {
+            if node == group.input {
+                let mut sources = Vec::new();
+                let mut constant = None;
+                for (other, out_prop, in_prop) in parent.graph.node_inputs(instance) {
+                    if in_prop == prop {
+                        let (s, c) = parent.resolve(other, out_prop);
+                        sources.extend(s);
+                        constant = constant.or(c);
+                    }
+                }
+                if sources.is_empty() && constant.is_none() {
+                    constant = parent.graph[instance].get(prop).cloned();
+                }
+                return (sources, constant);
+            }
+        }
+
+        if let Some(group) = self.graph.node(&node).and_then(|n| n.group_def()) {
+            let inner = Scope {
+                graph: &group.graph,
+                prefix: Some(self.inner_prefix(node)),
+                parent: Some((self, node, group)),
+                ids: self.ids,
+            };
+            let mut sources = Vec::new();
+            let mut constant = None;
+            for (other, out_prop, in_prop) in group.graph.node_inputs(group.output) {
+                if in_prop == prop {
+                    let (s, c) = inner.resolve(other, out_prop);
+                    sources.extend(s);
+                    constant = constant.or(c);
+                }
+            }
+            return (sources, constant);
+        }
+
+        (vec![(self.flat_ref(node), prop)], None)
+    }
+
+    /// Adds all nodes in this scope to the output graph, recording the top-level node each
+    /// flattened node originates from.
+    fn flatten(
+        &self,
+        owner: Option<NodeRef>,
+        out: &mut Graph,
+        origins: &mut HashMap<NodeRef, Vec<NodeRef>>,
+    ) {
+        for (node_ref, node) in self.graph.iter_nodes() {
+            let node_ref = *node_ref;
+            if self.is_placeholder(node_ref) {
+                continue;
+            }
+            let owner = owner.unwrap_or(node_ref);
+
+            if let Some(group) = node.group_def() {
+                let inner = Scope {
+                    graph: &group.graph,
+                    prefix: Some(self.inner_prefix(node_ref)),
+                    parent: Some((self, node_ref, group)),
+                    ids: self.ids,
+                };
+                inner.flatten(Some(owner), out, origins);
+                continue;
+            }
+
+            let flat_ref = self.flat_ref(node_ref);
+            let mut flat_node = node.clone();
+            flat_node.enabled = flat_node.enabled && self.is_enabled();
+
+            for (other, out_prop, in_prop) in self.graph.node_inputs(node_ref) {
+                let (sources, constant) = self.resolve(other, out_prop);
+                for (source, source_prop) in sources {
+                    out.link(source, source_prop, flat_ref, in_prop);
+                }
+                if let Some(value) = constant {
+                    flat_node.set(in_prop, value);
+                }
+            }
+
+            out.insert_node(flat_ref, flat_node);
+            origins.entry(owner).or_insert_with(Vec::new).push(flat_ref);
+        }
+    }
+}
+
+/// Mixes a node reference into a group prefix.
+fn mix(prefix: u64, node: u64) -> u64 {
+    let mut hasher = FnvHasher::default();
+    hasher.write_u64(prefix);
+    hasher.write_u64(node);
+    hasher.finish()
+}
+
+impl Graph {
+    /// Returns true if the graph contains group instances.
+    pub fn has_groups(&self) -> bool {
+        self.iter_nodes()
+            .any(|(_, node)| node.node_type == GROUP_NAME)
+    }
+
+    /// Returns a copy of this graph with all group instances recursively replaced by their
+    /// contents.
+    ///
+    /// Top-level nodes keep their references. Nodes inside groups get unique references that are
+    /// derived from the path of group instances that contains them, so they are stable across
+    /// calls.
+    /// Contents of disabled group instances are disabled.
+    pub fn flatten(&self) -> Graph {
+        self.flatten_with_origins().0
+    }
+
+    /// Flattens the graph (see [Graph::flatten]) and also returns, for every top-level node, the
+    /// flattened nodes that originate from it.
+    pub fn flatten_with_origins(&self) -> (Graph, HashMap<NodeRef, Vec<NodeRef>>) {
+        let mut out = Graph::new();
+        let mut origins = HashMap::new();
+        let ids = RefCell::new(FlatIds::default());
+        let scope = Scope {
+            graph: self,
+            prefix: None,
+            parent: None,
+            ids: &ids,
+        };
+        scope.flatten(None, &mut out, &mut origins);
+        out.set_output(self.output());
+        (out, origins)
+    }
+
+    /// Returns a top-level node as it appears in the flattened graph (see [Graph::flatten]), with
+    /// values that reach it through group instances set as properties.
+    pub(crate) fn flatten_node(&self, node: NodeRef) -> Option<Node> {
+        let ids = RefCell::new(FlatIds::default());
+        let scope = Scope {
+            graph: self,
+            prefix: None,
+            parent: None,
+            ids: &ids,
+        };
+        let mut flat_node = self.node(&node)?.clone();
+        for (other, out_prop, in_prop) in self.node_inputs(node) {
+            if let (_, Some(value)) = scope.resolve(other, out_prop) {
+                flat_node.set(in_prop, value);
+            }
+        }
+        Some(flat_node)
+    }
+}
+
+#[test]
+fn flattening() {
+    let mut inner = Graph::new();
+    let input = inner.add_node(Node::empty("in".into()));
+    let output = inner.add_node(Node::empty("out".into()));
+    let blur = inner.add_node(Node::empty("blur".into()));
+    inner.link(input, 0, blur, 0);
+    inner.link(input, 1, blur, 2);
+    inner.link(blur, 1, output, 0);
+    let group = Group::new(inner, input, output);
+
+    let mut graph = Graph::new();
+    let source = graph.add_node(Node::empty("source".into()));
+    let mut a = Node::group(group.clone());
+    a.set(1, 2.);
+    let a = graph.add_node(a);
+    let mut b = Node::group(group);
+    b.set(1, 3.);
+    let b = graph.add_node(b);
+    let sink = graph.add_node(Node::empty("sink".into()));
+    graph.link(source, 0, a, 0);
+    graph.link(source, 0, b, 0);
+    graph.link(a, 0, sink, 0);
+    graph.link(b, 0, sink, 0);
+    graph.set_output(sink);
+
+    let (flat, origins) = graph.flatten_with_origins();
+    assert!(!flat.has_groups());
+    assert_eq!(flat.iter_nodes().count(), 4);
+    assert_eq!(flat.output(), sink);
+
+    let a_blur = origins[&a][0];
+    let b_blur = origins[&b][0];
+    assert_ne!(a_blur, b_blur);
+    assert_eq!(flat.node_inputs(a_blur).collect::<Vec<_>>(), vec![(source, 0, 0)]);
+    assert_eq!(flat[a_blur].get(2), Some(&Value::Float(2.)));
+    assert_eq!(flat[b_blur].get(2), Some(&Value::Float(3.)));
+
+    let mut sink_inputs: Vec<_> = flat.node_inputs(sink).collect();
+    sink_inputs.sort();
+    let mut expected = vec![(a_blur, 1, 0), (b_blur, 1, 0)];
+    expected.sort();
+    assert_eq!(sink_inputs, expected);
+
+    // references must be stable
+    assert_eq!(graph.flatten_with_origins().1[&a], vec![a_blur]);
+
+    graph[b].enabled = false;
+    let flat = graph.flatten();
+    assert!(flat[a_blur].enabled);
+    assert!(!flat[b_blur].enabled);
+}
+
+#[test]
+fn flattened_nodes() {
+    // a group that passes its input through
+    let mut inner = Graph::new();
+    let input = inner.add_node(Node::empty("in".into()));
+    let output = inner.add_node(Node::empty("out".into()));
+    inner.link(input, 0, output, 0);
+
+    let mut graph = Graph::new();
+    let mut instance = Node::group(Group::new(inner, input, output));
+    instance.set(0, 2.);
+    let instance = graph.add_node(instance);
+    let mut sink = Node::empty("sink".into());
+    sink.set(1, 3.);
+    let sink = graph.add_node(sink);
+    graph.link(instance, 0, sink, 0);
+
+    let flat_sink = graph.flatten_node(sink).unwrap();
+    assert_eq!(flat_sink.get(0), Some(&Value::Float(2.)));
+    assert_eq!(flat_sink.get(1), Some(&Value::Float(3.)));
+    assert_eq!(graph.flatten()[sink].get(0), flat_sink.get(0));
+}
+
+#[test]
+fn flat_id_collisions() {
+    let mut ids = FlatIds::default();
+
+    // another path already uses the reference that (1, 2) hashes to
+    let hashed = NodeRef(GROUP_NODE_BIT | (mix(1, 2) >> 1));
+    ids.used_refs.insert(hashed);
+    let flat_ref = ids.flat_ref(1, NodeRef(2));
+    assert_ne!(flat_ref, hashed);
+    assert_ne!(flat_ref.0 & GROUP_NODE_BIT, 0);
+    assert_eq!(ids.flat_ref(1, NodeRef(2)), flat_ref);
+
+    let hashed = mix(0, 3);
+    ids.used_prefixes.insert(hashed);
+    let prefix = ids.prefix(None, NodeRef(3));
+    assert_ne!(prefix, hashed);
+    assert_eq!(ids.prefix(None, NodeRef(3)), prefix);
+}
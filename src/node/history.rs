@@ -0,0 +1,390 @@
+use crate::data::Value;
+use crate::node::{Graph, Node, NodeRef};
+use std::any::Any;
+use std::sync::Arc;
+use std::{mem, ops};
+
+/// An invertible graph operation.
+#[derive(Debug, Clone)]
+enum Op {
+    /// A node was added.
+    AddNode(NodeRef, Node),
+
+    /// A node was removed. Links are recorded separately.
+    RemoveNode(NodeRef, Node),
+
+    /// Two node properties were linked (out node, out prop, in node, in prop).
+    Link(NodeRef, usize, NodeRef, usize),
+
+    /// Two node properties were unlinked (out node, out prop, in node, in prop).
+    Unlink(NodeRef, usize, NodeRef, usize),
+
+    /// A property was changed (node, prop, old value, new value). None means unset.
+    SetProp(NodeRef, usize, Option<Value>, Option<Value>),
+
+    /// The enabled flag of a node was changed (node, old, new).
+    SetEnabled(NodeRef, bool, bool),
+
+    /// The output node was changed (old, new).
+    SetOutput(NodeRef, NodeRef),
+}
+
+impl Op {
+    /// Returns the operation that reverts this one.
+    fn inverse(&self) -> Op {
+        match self {
+            Op::AddNode(node, data) => Op::RemoveNode(*node, data.clone()),
+            Op::RemoveNode(node, data) => Op::AddNode(*node, data.clone()),
+            Op::Link(a, ap, b, bp) => Op::Unlink(*a, *ap, *b, *bp),
+            Op::Unlink(a, ap, b, bp) => Op::Link(*a, *ap, *b, *bp),
+            Op::SetProp(node, prop, old, new) => {
+                Op::SetProp(*node, *prop, new.clone(), old.clone())
+            }
+            Op::SetEnabled(node, old, new) => Op::SetEnabled(*node, *new, *old),
+            Op::SetOutput(old, new) => Op::SetOutput(*new, *old),
+        }
+    }
+
+    /// Applies this operation to a graph, marking affected nodes dirty.
+    fn apply(&self, graph: &mut Graph) {
+        match self {
+            Op::AddNode(node, data) => graph.insert_node(*node, data.clone()),
+            Op::RemoveNode(node, _) => {
+                graph.remove_node(*node);
+                graph.mark_clean(node);
+            }
+            Op::Link(a, ap, b, bp) => {
+                graph.link(*a, *ap, *b, *bp);
+                graph.mark_dirty(*b);
+            }
+            Op::Unlink(a, ap, b, bp) => {
+                graph.unlink(*a, *ap, *b, *bp);
+                graph.mark_dirty(*b);
+            }
+            Op::SetProp(node, prop, _, new) => {
+                if let Some(node) = graph.node_mut(node) {
+                    match new {
+                        Some(value) => node.set(*prop, value.clone()),
+                        None => {
+                            node.remove(*prop);
+                        }
+                    }
+                }
+            }
+            Op::SetEnabled(node, _, new) => {
+                if let Some(node) = graph.node_mut(node) {
+                    node.enabled = *new;
+                }
+            }
+            Op::SetOutput(_, new) => graph.set_output(*new),
+        }
+    }
+}
+
+/// A group of operations that is undone and redone as one step.
+#[derive(Debug, Clone)]
+struct Entry {
+    ops: Vec<Op>,
+    mergeable: bool,
+}
+
+impl Entry {
+    /// Attempts to merge a subsequent entry into this one. This only succeeds if both entries are
+    /// mergeable and set exactly the same properties.
+    fn merge(&mut self, other: &Entry) -> bool {
+        if !self.mergeable || !other.mergeable || self.ops.len() != other.ops.len() {
+            return false;
+        }
+
+        for (op, other) in self.ops.iter().zip(other.ops.iter()) {
+            match (op, other) {
+                (Op::SetProp(a, ap, ..), Op::SetProp(b, bp, ..)) if a == b && ap == bp => (),
+                _ => return false,
+            }
+        }
+
+        for (op, other) in self.ops.iter_mut().zip(other.ops.iter()) {
+            if let (Op::SetProp(_, _, _, new), Op::SetProp(_, _, _, other_new)) = (op, other) {
+                *new = other_new.clone();
+            }
+        }
+
+        true
+    }
+}
+
+/// Undo and redo stacks of a graph.
+#[derive(Debug, Clone)]
+pub(crate) struct History {
+    undo: Vec<Entry>,
+    redo: Vec<Entry>,
+}
+
+impl History {
+    pub(crate) fn new() -> History {
+        History {
+            undo: Vec::new(),
+            redo: Vec::new(),
+        }
+    }
+}
+
+/// A graph transaction.
+///
+/// Edits made through a transaction are applied to the graph immediately and are recorded as a
+/// single undo step when the transaction is committed or dropped. Edits made directly on the graph
+/// are not recorded; mixing both on the same nodes may make the history inconsistent.
+pub struct Transaction<'a> {
+    graph: &'a mut Graph,
+    ops: Vec<Op>,
+    mergeable: bool,
+}
+
+impl<'a> Transaction<'a> {
+    fn push(&mut self, op: Op) {
+        op.apply(self.graph);
+        self.ops.push(op);
+    }
+
+    /// Marks this transaction as mergeable: if the previous undo step was also a mergeable
+    /// transaction that set exactly the same properties, both will be merged into one step.
+    ///
+    /// This is intended for continuous edits, such as dragging a slider.
+    pub fn mergeable(mut self) -> Self {
+        self.mergeable = true;
+        self
+    }
+
+    /// Adds a node to the graph and returns a reference.
+    pub fn add_node(&mut self, node: Node) -> NodeRef {
+        let node_ref = self.graph.add_node(node.clone());
+        self.ops.push(Op::AddNode(node_ref, node));
+        node_ref
+    }
+
+    /// Removes a node and all of its links.
+    pub fn remove_node(&mut self, node: NodeRef) -> Option<Node> {
+        let data = self.graph.node(&node)?.clone();
+
+        let inputs: Vec<_> = self.graph.node_inputs(node).collect();
+        for (other, out_prop, in_prop) in inputs {
+            self.push(Op::Unlink(other, out_prop, node, in_prop));
+        }
+        let outputs: Vec<_> = self.graph.node_outputs(node).collect();
+        for (other, out_prop, in_prop) in outputs {
+            self.push(Op::Unlink(node, out_prop, other, in_prop));
+        }
+
+        self.push(Op::RemoveNode(node, data.clone()));
+        Some(data)
+    }
+
+    /// Links two node properties.
+    pub fn link(&mut self, out_node: NodeRef, out_prop: usize, in_node: NodeRef, in_prop: usize) {
+        let exists = self
+            .graph
+            .node_inputs(in_node)
+            .any(|link| link == (out_node, out_prop, in_prop));
+        if !exists {
+            self.push(Op::Link(out_node, out_prop, in_node, in_prop));
+        }
+    }
+
+    /// Removes a link between two node properties. Returns true if a link was removed.
+    pub fn unlink(
+        &mut self,
+        out_node: NodeRef,
+        out_prop: usize,
+        in_node: NodeRef,
+        in_prop: usize,
+    ) -> bool {
+        let exists = self
+            .graph
+            .node_inputs(in_node)
+            .any(|link| link == (out_node, out_prop, in_prop));
+        if exists {
+            self.push(Op::Unlink(out_node, out_prop, in_node, in_prop));
+        }
+        exists
+    }
+
+    /// Sets a node property. Returns false if the node does not exist.
+    pub fn set<T: Into<Value>>(&mut self, node: NodeRef, prop: usize, value: T) -> bool {
+        let old = match self.graph.node(&node) {
+            Some(data) => data.get(prop).cloned(),
+            None => return false,
+        };
+        self.push(Op::SetProp(node, prop, old, Some(value.into())));
+        true
+    }
+
+    /// Sets a node property with an Any value. Returns false if the node does not exist.
+    pub fn set_any<T: Any + Send + Sync>(&mut self, node: NodeRef, prop: usize, value: T) -> bool {
+        let value: Arc<Any + Send + Sync> = Arc::new(value);
+        self.set(node, prop, value)
+    }
+
+    /// Removes a node property. Returns the old value, if it existed.
+    pub fn unset(&mut self, node: NodeRef, prop: usize) -> Option<Value> {
+        let old = self.graph.node(&node)?.get(prop)?.clone();
+        self.push(Op::SetProp(node, prop, Some(old.clone()), None));
+        Some(old)
+    }
+
+    /// Enables or disables a node. Returns false if the node does not exist.
+    pub fn set_enabled(&mut self, node: NodeRef, enabled: bool) -> bool {
+        let old = match self.graph.node(&node) {
+            Some(data) => data.enabled,
+            None => return false,
+        };
+        if old != enabled {
+            self.push(Op::SetEnabled(node, old, enabled));
+        }
+        true
+    }
+
+    /// Sets the output node.
+    pub fn set_output(&mut self, node: NodeRef) {
+        let old = self.graph.output();
+        if old != node {
+            self.push(Op::SetOutput(old, node));
+        }
+    }
+
+    /// Commits the transaction. This is equivalent to dropping it.
+    pub fn commit(self) {}
+
+    /// Reverts all edits made in this transaction without recording them.
+    pub fn cancel(mut self) {
+        let ops = mem::replace(&mut self.ops, Vec::new());
+        for op in ops.iter().rev() {
+            op.inverse().apply(self.graph);
+        }
+    }
+}
+
+impl<'a> ops::Deref for Transaction<'a> {
+    type Target = Graph;
+    fn deref(&self) -> &Graph {
+        self.graph
+    }
+}
+
+impl<'a> Drop for Transaction<'a> {
+    fn drop(&mut self) {
+        if self.ops.is_empty() {
+            return;
+        }
+
+        let entry = Entry {
+            ops: mem::replace(&mut self.ops, Vec::new()),
+            mergeable: self.mergeable,
+        };
+
+        let history = &mut self.graph.history;
+        let merged = history.redo.is_empty()
+            && history
+                .undo
+                .last_mut()
+                .map_or(false, |last| last.merge(&entry));
+        if !merged {
+            history.undo.push(entry);
+        }
+        history.redo.clear();
+    }
+}
+
+impl Graph {
+    /// Begins a transaction. Edits made through the transaction are recorded as one undo step.
+    pub fn transaction(&mut self) -> Transaction {
+        Transaction {
+            graph: self,
+            ops: Vec::new(),
+            mergeable: false,
+        }
+    }
+
+    /// Reverts the last recorded transaction. Returns false if there was nothing to undo.
+    pub fn undo(&mut self) -> bool {
+        let entry = match self.history.undo.pop() {
+            Some(entry) => entry,
+            None => return false,
+        };
+        for op in entry.ops.iter().rev() {
+            op.inverse().apply(self);
+        }
+        self.history.redo.push(entry);
+        true
+    }
+
+    /// Re-applies the last undone transaction. Returns false if there was nothing to redo.
+    pub fn redo(&mut self) -> bool {
+        let entry = match self.history.redo.pop() {
+            Some(entry) => entry,
+            None => return false,
+        };
+        for op in &entry.ops {
+            op.apply(self);
+        }
+        self.history.undo.push(entry);
+        true
+    }
+
+    /// Returns true if there is a transaction that can be undone.
+    pub fn can_undo(&self) -> bool {
+        !self.history.undo.is_empty()
+    }
+
+    /// Returns true if there is a transaction that can be redone.
+    pub fn can_redo(&self) -> bool {
+        !self.history.redo.is_empty()
+    }
+
+    /// Clears the undo and redo history.
+    pub fn clear_history(&mut self) {
+        self.history = History::new();
+    }
+}
+
+#[test]
+fn undo_redo() {
+    let mut graph = Graph::new();
+
+    let (a, b) = {
+        let mut tx = graph.transaction();
+        let a = tx.add_node(Node::empty("a".into()));
+        let b = tx.add_node(Node::empty("b".into()));
+        tx.link(a, 0, b, 0);
+        tx.set(b, 1, 1.);
+        (a, b)
+    };
+
+    // consecutive mergeable edits of the same property form one step
+    for i in 2..5 {
+        graph.transaction().mergeable().set(b, 1, i as f64);
+    }
+    assert_eq!(graph[b].get(1), Some(&Value::Float(4.)));
+
+    graph.mark_clean(&b);
+    assert!(graph.undo());
+    assert_eq!(graph[b].get(1), Some(&Value::Float(1.)));
+    assert!(graph.is_dirty(&b));
+
+    graph.transaction().remove_node(a);
+    assert!(graph.node(&a).is_none());
+    assert_eq!(graph.node_inputs(b).count(), 0);
+
+    assert!(graph.undo());
+    assert!(graph.node(&a).is_some());
+    assert_eq!(graph.node_inputs(b).collect::<Vec<_>>(), vec![(a, 0, 0)]);
+    assert!(!graph.has_order());
+
+    assert!(graph.undo());
+    assert!(graph.node(&a).is_none());
+    assert!(graph.node(&b).is_none());
+    assert!(!graph.can_undo());
+
+    assert!(graph.redo());
+    assert_eq!(graph[b].get(1), Some(&Value::Float(1.)));
+    assert_eq!(graph.node_inputs(b).count(), 1);
+}
@@ -1,8 +1,20 @@
 //! Nodes.
 
 pub mod defs;
+mod events;
+mod export;
+mod fragment;
 mod graph;
+mod group;
+mod history;
 mod node;
+mod scene;
 
+pub use self::events::*;
+pub use self::export::*;
+pub use self::fragment::*;
 pub use self::graph::*;
+pub use self::group::*;
+pub use self::history::*;
 pub use self::node::*;
+pub use self::scene::*;
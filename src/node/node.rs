@@ -1,8 +1,31 @@
-use crate::data::Value;
+use crate::data::{Track, Value};
+use crate::eval::SchemaRegistry;
+use crate::node::NodeRef;
 use crate::util::BSMap;
 use std::any::Any;
+use std::collections::HashMap;
 use std::sync::Arc;
 
+/// Named property lookup errors.
+#[derive(Debug, Fail, Clone, PartialEq)]
+pub enum PropertyError {
+    /// There is no schema for the node type.
+    #[fail(display = "no schema for node type {}", _0)]
+    UnknownNodeType(String),
+
+    /// The node type has no input with the given name.
+    #[fail(display = "node type {} has no input named {}", _0, _1)]
+    NoSuchInput(String, String),
+
+    /// The node type has no output with the given name.
+    #[fail(display = "node type {} has no output named {}", _0, _1)]
+    NoSuchOutput(String, String),
+
+    /// The node does not exist.
+    #[fail(display = "missing node: {:?}", _0)]
+    MissingNode(NodeRef),
+}
+
 /// A node.
 #[derive(Debug, Clone)]
 pub struct Node {
@@ -14,6 +37,12 @@ pub struct Node {
 
     /// Property data.
     pub(crate) props: BSMap<usize, Value>,
+
+    /// Properties that were mutated since they were last taken by the graph.
+    changed: BSMap<usize, ()>,
+
+    /// Keyframe tracks of animated properties.
+    tracks: BSMap<usize, Track>,
 }
 
 impl Node {
@@ -23,6 +52,8 @@ impl Node {
             enabled: true,
             node_type,
             props: BSMap::new(),
+            changed: BSMap::new(),
+            tracks: BSMap::new(),
         }
     }
 
@@ -43,6 +74,9 @@ impl Node {
 
     /// Returns a mutable reference to a property value.
     pub fn get_mut(&mut self, property: usize) -> Option<&mut Value> {
+        if self.props.contains_key(&property) {
+            self.changed.insert(property, ());
+        }
         self.props.get_mut(&property)
     }
 
@@ -57,6 +91,16 @@ impl Node {
     /// Sets a property value.
     pub fn set<T: Into<Value>>(&mut self, property: usize, value: T) {
         self.props.insert(property, value.into());
+        self.changed.insert(property, ());
+    }
+
+    /// Removes a property value and returns it, if it existed.
+    pub fn remove(&mut self, property: usize) -> Option<Value> {
+        let value = self.props.remove(&property);
+        if value.is_some() {
+            self.changed.insert(property, ());
+        }
+        value
     }
 
     /// Sets a property value with an Any value.
@@ -65,6 +109,108 @@ impl Node {
         self.set(property, value);
     }
 
+    /// Resolves an input property name to its index using the node type’s schema.
+    pub fn input_index(
+        &self,
+        schemas: &SchemaRegistry,
+        name: &str,
+    ) -> Result<usize, PropertyError> {
+        let schema = schemas
+            .get(&self.node_type)
+            .ok_or_else(|| PropertyError::UnknownNodeType(self.node_type.clone()))?;
+        match schema.input_named(name) {
+            Some(port) => Ok(port.index),
+            None => Err(PropertyError::NoSuchInput(
+                self.node_type.clone(),
+                name.into(),
+            )),
+        }
+    }
+
+    /// Resolves an output property name to its index using the node type’s schema.
+    pub fn output_index(
+        &self,
+        schemas: &SchemaRegistry,
+        name: &str,
+    ) -> Result<usize, PropertyError> {
+        let schema = schemas
+            .get(&self.node_type)
+            .ok_or_else(|| PropertyError::UnknownNodeType(self.node_type.clone()))?;
+        match schema.output_named(name) {
+            Some(port) => Ok(port.index),
+            None => Err(PropertyError::NoSuchOutput(
+                self.node_type.clone(),
+                name.into(),
+            )),
+        }
+    }
+
+    /// Returns a property value by name.
+    pub fn get_named(
+        &self,
+        schemas: &SchemaRegistry,
+        name: &str,
+    ) -> Result<Option<&Value>, PropertyError> {
+        Ok(self.get(self.input_index(schemas, name)?))
+    }
+
+    /// Sets a property value by name.
+    pub fn set_named<T: Into<Value>>(
+        &mut self,
+        schemas: &SchemaRegistry,
+        name: &str,
+        value: T,
+    ) -> Result<(), PropertyError> {
+        let property = self.input_index(schemas, name)?;
+        self.set(property, value);
+        Ok(())
+    }
+
+    /// Sets a property value with an Any value by name.
+    pub fn set_named_any<T: Any + Send + Sync>(
+        &mut self,
+        schemas: &SchemaRegistry,
+        name: &str,
+        value: T,
+    ) -> Result<(), PropertyError> {
+        let property = self.input_index(schemas, name)?;
+        self.set_any(property, value);
+        Ok(())
+    }
+
+    /// Moves property values and keyframe tracks to new indices (see
+    /// [crate::node::Graph::remap_properties]). Values and tracks whose index is not in the
+    /// mapping are dropped. Both the old and the new indices of values are marked as changed.
+    /// Returns true if anything was dropped.
+    pub(crate) fn remap(&mut self, mapping: &HashMap<usize, usize>) -> bool {
+        let mut props = BSMap::new();
+        let mut dropped = false;
+        for (k, v) in self.props.iter() {
+            self.changed.insert(*k, ());
+            match mapping.get(k) {
+                Some(new) => {
+                    props.insert(*new, v.clone());
+                    self.changed.insert(*new, ());
+                }
+                None => dropped = true,
+            }
+        }
+        self.props = props;
+
+        let mut tracks = BSMap::new();
+        for (k, track) in self.tracks.iter() {
+            match mapping.get(k) {
+                Some(new) => {
+                    tracks.insert(*new, track.clone());
+                }
+                None => dropped = true,
+            }
+        }
+        self.tracks = tracks;
+
+        dropped
+    }
+
     /// Returns true if this node contains dynamically typed values.
     pub fn has_dynamic_values(&self) -> bool {
         for (_, v) in self.props.iter() {
@@ -79,12 +225,59 @@ impl Node {
 
     /// Iterates over dynamic values.
     pub fn iter_mut_dynamic_values(&mut self) -> impl Iterator<Item = (usize, &mut Value)> {
+        let changed = &mut self.changed;
         self.props
             .iter_mut()
             .filter(|(_, v)| match v {
                 Value::Any(_) => true,
                 _ => false,
             })
-            .map(|(k, v)| (*k, v))
+            .map(move |(k, v)| {
+                changed.insert(*k, ());
+                (*k, v)
+            })
+    }
+
+    /// Returns the keyframe track of a property.
+    pub fn track(&self, property: usize) -> Option<&Track> {
+        self.tracks.get(&property)
+    }
+
+    /// Iterates over all keyframe tracks.
+    pub fn tracks(&self) -> impl Iterator<Item = (usize, &Track)> {
+        self.tracks
+            .iter()
+            .map(|(property, track)| (*property, track))
+    }
+
+    /// Animates a property with a keyframe track (see [crate::node::Graph::animate]).
+    pub fn set_track(&mut self, property: usize, track: Track) {
+        self.tracks.insert(property, track);
+    }
+
+    /// Removes a keyframe track and returns it, if it existed. The property falls back to its
+    /// property value.
+    pub fn remove_track(&mut self, property: usize) -> Option<Track> {
+        self.tracks.remove(&property)
+    }
+
+    /// Returns true if any property is animated.
+    pub fn is_animated(&self) -> bool {
+        !self.tracks.is_empty()
+    }
+
+    /// Samples all tracks at the given time, ordered by property.
+    pub(crate) fn sample_tracks(&self, time: f64) -> Vec<(usize, Value)> {
+        self.tracks
+            .iter()
+            .filter_map(|(property, track)| Some((*property, track.sample(time)?)))
+            .collect()
+    }
+
+    /// Takes the list of properties that were mutated since the last call.
+    pub(crate) fn take_changed(&mut self) -> Vec<usize> {
+        let changed = self.changed.iter().map(|(k, _)| *k).collect();
+        self.changed.clear();
+        changed
     }
 }
@@ -0,0 +1,820 @@
+//! Scene files.
+//!
+//! A scene file is a JSON representation of a graph:
+//!
+//! ```json
+//! {
+//!     "output": 1,
+//!     "nodes": [
+//!         { "id": 1, "type": "narwhal.camera", "props": { "size": [400, 300] } },
+//!         {
+//!             "id": 2,
+//!             "type": "narwhal.gaussian-blur",
+//!             "enabled": false,
+//!             "props": { "radius": 4 },
+//!             "tracks": {
+//!                 "radius": [
+//!                     { "time": 0, "value": 4, "interpolation": { "bezier": [0.4, 0, 0.2, 1] } },
+//!                     { "time": 1, "value": 16 }
+//!                 ]
+//!             }
+//!         }
+//!     ],
+//!     "links": [{ "from": [2, "out"], "to": [1, "in"] }]
+//! }
+//! ```
+//!
+//! Ports are referred to by their schema name or by their index as a string. Values are encoded
+//! as follows:
+//!
+//! - numbers and strings as themselves
+//! - `Vec2`, `Vec3` and `Vec4` as arrays of 2, 3 or 4 numbers
+//! - `{ "mat4": [16 numbers] }` in column-major order
+//! - `{ "color": [r, g, b, a] }` in ACEScg
+//! - `{ "path": [["M", x, y], ["L", x, y], ["Q", x1, y1, x, y], ["C", x1, y1, x2, y2, x, y],
+//!   ["Z"]] }`
+//! - `{ "weight": [["L", x, y, z], ["Q", …], ["C", …]] }` (see [crate::data::WeightCmd])
+//! - `{ "drawables": [{ "id": 0, "path": […], "fill": [r, g, b, a], "stroke": { "weight": […],
+//!   "width": 1, "color": [r, g, b, a] }, "transform": [16 numbers] }] }`, where the ID is either
+//!   an index belonging to the containing node or a pair of a node ID and an index, and
+//!   everything but the path is optional
+//! - `{ "raw": [bytes] }`
+//!
+//! Keyframe interpolation is `"linear"` (the default), `"hold"`, `{ "steps": n }` or
+//! `{ "bezier": [x1, y1, x2, y2] }`. Group instances have an additional `group` key with the
+//! group's graph and its `input` and `output` node IDs.
+//!
+//! Textures and `Any` values can not be stored. Inputs that take an enum, such as a
+//! [MaskMode](crate::render::fx::MaskMode) or a
+//! [ToneMapOperator](crate::render::fx::ToneMapOperator), also accept its numeric value instead.
+
+use crate::data::{
+    Color, Drawable, Interpolation, Path2D, Path2DCmd, Shape, StrokeWeight, Track, Value,
+    ValueType, WeightCmd,
+};
+use crate::eval::SchemaRegistry;
+use crate::node::{
+    Graph, Group, LinkError, Node, NodeIdError, NodeRef, PropertyError, GROUP_DEF_PROP,
+};
+use cgmath::{BaseFloat, Matrix4, Vector2, Vector3, Vector4};
+use serde_json::{json, Map, Value as Json};
+
+/// Scene file errors.
+#[derive(Debug, Fail)]
+pub enum SceneError {
+    /// The file is not valid JSON.
+    #[fail(display = "invalid JSON: {}", _0)]
+    Json(serde_json::Error),
+
+    /// The file is not a valid scene.
+    #[fail(display = "invalid scene: {}", _0)]
+    Invalid(String),
+
+    /// A node ID is invalid or used twice.
+    #[fail(display = "{}", _0)]
+    NodeId(NodeIdError),
+
+    /// A port could not be resolved.
+    #[fail(display = "{}", _0)]
+    Property(PropertyError),
+
+    /// A link is invalid according to the node types’ schemas.
+    #[fail(display = "{}", _0)]
+    Link(LinkError),
+
+    /// A property value can not be stored.
+    #[fail(
+        display = "property #{} of node {:?} has type {}, which can not be saved",
+        _1, _0, _2
+    )]
+    Unsupported(NodeRef, usize, ValueType),
+}
+
+impl From<serde_json::Error> for SceneError {
+    fn from(err: serde_json::Error) -> SceneError {
+        SceneError::Json(err)
+    }
+}
+
+impl From<NodeIdError> for SceneError {
+    fn from(err: NodeIdError) -> SceneError {
+        SceneError::NodeId(err)
+    }
+}
+
+impl From<LinkError> for SceneError {
+    fn from(err: LinkError) -> SceneError {
+        SceneError::Link(err)
+    }
+}
+
+impl From<PropertyError> for SceneError {
+    fn from(err: PropertyError) -> SceneError {
+        SceneError::Property(err)
+    }
+}
+
+fn invalid<T: Into<String>>(message: T) -> SceneError {
+    SceneError::Invalid(message.into())
+}
+
+impl Graph {
+    /// Loads a graph from a scene file. Node IDs are preserved.
+    ///
+    /// Ports of node types in the schema registry (e.g. [crate::node::defs::builtin_schemas] or
+    /// [crate::render::Renderer::schemas]) may be referred to by name, others only by index.
+    pub fn from_scene(source: &str, schemas: &SchemaRegistry) -> Result<Graph, SceneError> {
+        read_graph(schemas, &serde_json::from_str(source)?)
+    }
+
+    /// Saves the graph as a scene file. Ports of node types in the schema registry are written by
+    /// name.
+    pub fn to_scene(&self, schemas: &SchemaRegistry) -> Result<String, SceneError> {
+        Ok(serde_json::to_string_pretty(&write_graph(schemas, self)?)?)
+    }
+}
+
+fn write_graph(schemas: &SchemaRegistry, graph: &Graph) -> Result<Json, SceneError> {
+    let mut nodes = Vec::new();
+    for (node_ref, node) in graph.iter_nodes() {
+        nodes.push(write_node(schemas, graph, *node_ref, node)?);
+    }
+
+    let port = |node: NodeRef, prop: usize, output: bool| {
+        graph
+            .port_name(schemas, node, prop, output)
+            .unwrap_or_else(|| prop.to_string())
+    };
+    let links: Vec<_> = graph
+        .iter_links()
+        .map(|((out_node, out_prop), (in_node, in_prop))| {
+            json!({
+                "from": [out_node.id(), port(out_node, out_prop, true)],
+                "to": [in_node.id(), port(in_node, in_prop, false)],
+            })
+        })
+        .collect();
+
+    let mut object = Map::new();
+    if graph.output() != NodeRef(0) {
+        object.insert("output".into(), json!(graph.output().id()));
+    }
+    object.insert("nodes".into(), Json::Array(nodes));
+    object.insert("links".into(), Json::Array(links));
+    Ok(Json::Object(object))
+}
+
+fn write_node(
+    schemas: &SchemaRegistry,
+    graph: &Graph,
+    node_ref: NodeRef,
+    node: &Node,
+) -> Result<Json, SceneError> {
+    let group = node.group_def();
+    let port = |prop: usize| {
+        graph
+            .port_name(schemas, node_ref, prop, false)
+            .unwrap_or_else(|| prop.to_string())
+    };
+
+    let mut props = Map::new();
+    for (prop, value) in node.props.iter() {
+        if group.is_some() && *prop == GROUP_DEF_PROP {
+            continue;
+        }
+        let json = write_value(node_ref, value)
+            .ok_or_else(|| SceneError::Unsupported(node_ref, *prop, value.value_type()))?;
+        props.insert(port(*prop), json);
+    }
+
+    let mut tracks = Map::new();
+    for (prop, track) in node.tracks() {
+        let mut keyframes = Vec::new();
+        for keyframe in track.keyframes() {
+            let value_type = keyframe.value.value_type();
+            let value = write_value(node_ref, &keyframe.value)
+                .ok_or_else(|| SceneError::Unsupported(node_ref, prop, value_type))?;
+            keyframes.push(json!({
+                "time": keyframe.time,
+                "value": value,
+                "interpolation": write_interpolation(keyframe.interpolation),
+            }));
+        }
+        tracks.insert(port(prop), Json::Array(keyframes));
+    }
+
+    let mut object = Map::new();
+    object.insert("id".into(), json!(node_ref.id()));
+    object.insert("type".into(), json!(node.node_type));
+    if !node.enabled {
+        object.insert("enabled".into(), json!(false));
+    }
+    if !props.is_empty() {
+        object.insert("props".into(), Json::Object(props));
+    }
+    if !tracks.is_empty() {
+        object.insert("tracks".into(), Json::Object(tracks));
+    }
+    if let Some(group) = group {
+        let mut json = write_graph(schemas, &group.graph)?;
+        json["input"] = json!(group.input.id());
+        json["output"] = json!(group.output.id());
+        object.insert("group".into(), json);
+    }
+    Ok(Json::Object(object))
+}
+
+/// Returns None if the value can not be stored.
+fn write_value(owner: NodeRef, value: &Value) -> Option<Json> {
+    Some(match value {
+        Value::Float(x) => json!(x),
+        Value::String(s) => json!(s),
+        Value::Vec2(v) => json!([v.x, v.y]),
+        Value::Vec3(v) => json!([v.x, v.y, v.z]),
+        Value::Vec4(v) => json!([v.x, v.y, v.z, v.w]),
+        Value::Mat4(m) => json!({ "mat4": write_matrix(m) }),
+        Value::Color(c) => json!({ "color": write_color(*c) }),
+        Value::Path2D(path) => json!({ "path": write_path(path) }),
+        Value::StrokeWeight(weight) => json!({ "weight": write_weight(weight) }),
+        Value::Drawables(drawables) => {
+            let drawables: Vec<_> = drawables
+                .iter()
+                .map(|drawable| write_drawable(owner, drawable))
+                .collect();
+            json!({ "drawables": drawables })
+        }
+        Value::Raw(data) => json!({ "raw": data }),
+        Value::Texture(_) | Value::Any(_) => return None,
+    })
+}
+
+fn write_matrix(matrix: &Matrix4<f64>) -> Json {
+    let values: &[f64; 16] = matrix.as_ref();
+    json!(values)
+}
+
+fn write_color(color: Color) -> Json {
+    json!([color.r, color.g, color.b, color.a])
+}
+
+fn write_path(path: &Path2D) -> Json {
+    let commands: Vec<_> = path
+        .commands()
+        .iter()
+        .map(|command| match command {
+            Path2DCmd::JumpTo(p) => json!(["M", p.x, p.y]),
+            Path2DCmd::LineTo(p) => json!(["L", p.x, p.y]),
+            Path2DCmd::QuadTo(a, p) => json!(["Q", a.x, a.y, p.x, p.y]),
+            Path2DCmd::CubicTo(a, b, p) => json!(["C", a.x, a.y, b.x, b.y, p.x, p.y]),
+            Path2DCmd::CloseShape => json!(["Z"]),
+        })
+        .collect();
+    json!(commands)
+}
+
+fn write_weight(weight: &StrokeWeight) -> Json {
+    let commands: Vec<_> = weight
+        .commands()
+        .iter()
+        .map(|command| match command {
+            WeightCmd::LineTo(p) => json!(["L", p.x, p.y, p.z]),
+            WeightCmd::QuadTo(a, p) => json!(["Q", a.x, a.y, a.z, p.x, p.y, p.z]),
+            WeightCmd::CubicTo(a, b, p) => {
+                json!(["C", a.x, a.y, a.z, b.x, b.y, b.z, p.x, p.y, p.z])
+            }
+        })
+        .collect();
+    json!(commands)
+}
+
+fn write_drawable(owner: NodeRef, drawable: &Drawable) -> Json {
+    let (node, index) = drawable.id;
+    let shape = &drawable.shape;
+
+    let mut object = Map::new();
+    if node == owner {
+        object.insert("id".into(), json!(index));
+    } else {
+        object.insert("id".into(), json!([node.id(), index]));
+    }
+    object.insert("path".into(), write_path(&shape.path));
+    if let Some(fill) = shape.fill {
+        object.insert("fill".into(), write_color(fill));
+    }
+    if let Some((weight, width, color)) = &shape.stroke {
+        object.insert(
+            "stroke".into(),
+            json!({
+                "weight": write_weight(weight),
+                "width": width,
+                "color": write_color(*color),
+            }),
+        );
+    }
+    if let Some(transform) = &shape.transform {
+        let values: &[f32; 16] = transform.as_ref();
+        object.insert("transform".into(), json!(values));
+    }
+    Json::Object(object)
+}
+
+fn write_interpolation(interpolation: Interpolation) -> Json {
+    match interpolation {
+        Interpolation::Linear => json!("linear"),
+        Interpolation::Hold => json!("hold"),
+        Interpolation::Step(steps) => json!({ "steps": steps }),
+        Interpolation::Bezier(x1, y1, x2, y2) => json!({ "bezier": [x1, y1, x2, y2] }),
+    }
+}
+
+fn as_object<'a>(json: &'a Json, what: &str) -> Result<&'a Map<String, Json>, SceneError> {
+    json.as_object()
+        .ok_or_else(|| invalid(format!("expected {} to be an object", what)))
+}
+
+fn as_array<'a>(json: &'a Json, what: &str) -> Result<&'a [Json], SceneError> {
+    match json.as_array() {
+        Some(array) => Ok(array),
+        None => Err(invalid(format!("expected {} to be an array", what))),
+    }
+}
+
+fn read_graph(schemas: &SchemaRegistry, json: &Json) -> Result<Graph, SceneError> {
+    let object = as_object(json, "graph")?;
+    let mut graph = Graph::new();
+
+    if let Some(nodes) = object.get("nodes") {
+        for node in as_array(nodes, "nodes")? {
+            let (node_ref, node) = read_node(schemas, node)?;
+            graph.add_node_with_id(node_ref, node)?;
+        }
+    }
+
+    if let Some(links) = object.get("links") {
+        for link in as_array(links, "links")? {
+            let (out_node, out_prop) = read_port(schemas, &graph, &link["from"], true)?;
+            let (in_node, in_prop) = read_port(schemas, &graph, &link["to"], false)?;
+            graph.link_checked(schemas, out_node, out_prop, in_node, in_prop)?;
+        }
+    }
+
+    match object.get("output") {
+        Some(Json::Null) | None => (),
+        Some(output) => graph.set_output(read_node_ref(output)?),
+    }
+
+    Ok(graph)
+}
+
+fn read_node_ref(json: &Json) -> Result<NodeRef, SceneError> {
+    match json.as_u64() {
+        Some(id) => Ok(NodeRef::from_id(id)),
+        None => Err(invalid(format!("invalid node ID {}", json))),
+    }
+}
+
+/// Resolves a port by name, or by index if there is no port with that name.
+fn port_index(
+    schemas: &SchemaRegistry,
+    node: &Node,
+    key: &str,
+    output: bool,
+) -> Result<usize, SceneError> {
+    let named = if output {
+        node.output_index(schemas, key)
+    } else {
+        node.input_index(schemas, key)
+    };
+    match named {
+        Ok(index) => Ok(index),
+        Err(err) => key.parse().map_err(|_| err.into()),
+    }
+}
+
+fn read_port(
+    schemas: &SchemaRegistry,
+    graph: &Graph,
+    json: &Json,
+    output: bool,
+) -> Result<(NodeRef, usize), SceneError> {
+    let pair = match json.as_array() {
+        Some(pair) if pair.len() == 2 => pair,
+        _ => {
+            return Err(invalid(format!(
+                "expected a [node, port] pair, got {}",
+                json
+            )))
+        }
+    };
+    let node_ref = read_node_ref(&pair[0])?;
+    let node = graph
+        .node(&node_ref)
+        .ok_or(PropertyError::MissingNode(node_ref))?;
+    let prop = match &pair[1] {
+        Json::String(key) => port_index(schemas, node, key, output)?,
+        Json::Number(_) => port_index(schemas, node, &pair[1].to_string(), output)?,
+        port => return Err(invalid(format!("invalid port {}", port))),
+    };
+    Ok((node_ref, prop))
+}
+
+fn read_node(schemas: &SchemaRegistry, json: &Json) -> Result<(NodeRef, Node), SceneError> {
+    let object = as_object(json, "node")?;
+    let node_ref = read_node_ref(&json["id"])?;
+    let node_type = match object.get("type").and_then(Json::as_str) {
+        Some(node_type) => node_type,
+        None => return Err(invalid(format!("node {} has no type", node_ref.id()))),
+    };
+    let mut node = Node::empty(node_type.into());
+
+    if let Some(enabled) = object.get("enabled") {
+        node.enabled = enabled
+            .as_bool()
+            .ok_or_else(|| invalid(format!("node {}: enabled must be a bool", node_ref.id())))?;
+    }
+
+    if let Some(group) = object.get("group") {
+        let graph = read_graph(schemas, group)?;
+        let input = read_node_ref(&group["input"])?;
+        let output = read_node_ref(&group["output"])?;
+        node.set_any(GROUP_DEF_PROP, Group::new(graph, input, output));
+    }
+
+    if let Some(props) = object.get("props") {
+        for (key, value) in as_object(props, "props")? {
+            let prop = port_index(schemas, &node, key, false)?;
+            node.set(prop, read_value(node_ref, value)?);
+        }
+    }
+
+    if let Some(tracks) = object.get("tracks") {
+        for (key, keyframes) in as_object(tracks, "tracks")? {
+            let prop = port_index(schemas, &node, key, false)?;
+            let mut track = Track::new();
+            for keyframe in as_array(keyframes, "keyframes")? {
+                let time = keyframe["time"]
+                    .as_f64()
+                    .ok_or_else(|| invalid(format!("keyframe {} has no time", keyframe)))?;
+                let value = read_value(node_ref, &keyframe["value"])?;
+                let interpolation = match keyframe.get("interpolation") {
+                    Some(interpolation) => read_interpolation(interpolation)?,
+                    None => Interpolation::default(),
+                };
+                track.insert(time, value, interpolation);
+            }
+            node.set_track(prop, track);
+        }
+    }
+
+    Ok((node_ref, node))
+}
+
+fn read_floats(json: &Json, len: usize, what: &str) -> Result<Vec<f64>, SceneError> {
+    let values: Option<Vec<_>> = match json.as_array() {
+        Some(values) if values.len() == len => values.iter().map(Json::as_f64).collect(),
+        _ => None,
+    };
+    values.ok_or_else(|| invalid(format!("expected {} to be {} numbers", what, len)))
+}
+
+fn read_value(owner: NodeRef, json: &Json) -> Result<Value, SceneError> {
+    match json {
+        Json::Number(x) => Ok(Value::Float(x.as_f64().unwrap_or(0.))),
+        Json::String(s) => Ok(Value::String(s.clone())),
+        Json::Array(values) => {
+            let v = read_floats(json, values.len(), "vector")?;
+            match v.len() {
+                2 => Ok(Value::Vec2(Vector2::new(v[0], v[1]))),
+                3 => Ok(Value::Vec3(Vector3::new(v[0], v[1], v[2]))),
+                4 => Ok(Value::Vec4(Vector4::new(v[0], v[1], v[2], v[3]))),
+                _ => Err(invalid("vectors must have 2, 3 or 4 components")),
+            }
+        }
+        Json::Object(object) if object.len() == 1 => {
+            let (key, value) = object.iter().next().unwrap();
+            match &**key {
+                "mat4" => Ok(Value::Mat4(read_matrix(value)?)),
+                "color" => Ok(Value::Color(read_color(value)?)),
+                "path" => Ok(Value::Path2D(read_path(value)?)),
+                "weight" => Ok(Value::StrokeWeight(read_weight(value)?)),
+                "drawables" => {
+                    let mut drawables = Vec::new();
+                    for drawable in as_array(value, "drawables")? {
+                        drawables.push(read_drawable(owner, drawable)?);
+                    }
+                    Ok(Value::Drawables(drawables))
+                }
+                "raw" => {
+                    let bytes: Option<Vec<_>> = as_array(value, "raw")?
+                        .iter()
+                        .map(|x| x.as_u64().filter(|x| *x <= 255).map(|x| x as u8))
+                        .collect();
+                    bytes
+                        .map(Value::Raw)
+                        .ok_or_else(|| invalid("raw data must be an array of bytes"))
+                }
+                key => Err(invalid(format!("unknown value type {:?}", key))),
+            }
+        }
+        json => Err(invalid(format!("invalid value {}", json))),
+    }
+}
+
+fn matrix<S: BaseFloat>(v: &[S]) -> Matrix4<S> {
+    let column = |i: usize| Vector4::new(v[4 * i], v[4 * i + 1], v[4 * i + 2], v[4 * i + 3]);
+    Matrix4::from_cols(column(0), column(1), column(2), column(3))
+}
+
+fn read_matrix(json: &Json) -> Result<Matrix4<f64>, SceneError> {
+    Ok(matrix(&read_floats(json, 16, "matrix")?))
+}
+
+fn read_color(json: &Json) -> Result<Color, SceneError> {
+    let v = read_floats(json, 4, "color")?;
+    Ok(Color {
+        r: v[0] as f32,
+        g: v[1] as f32,
+        b: v[2] as f32,
+        a: v[3] as f32,
+    })
+}
+
+/// Splits a path command into its name and arguments.
+fn read_command(json: &Json) -> Result<(&str, Vec<f64>), SceneError> {
+    let command = match json.as_array() {
+        Some(command) if !command.is_empty() => command,
+        _ => return Err(invalid(format!("invalid command {}", json))),
+    };
+    let name = command[0]
+        .as_str()
+        .ok_or_else(|| invalid(format!("invalid command {}", json)))?;
+    let args: Option<Vec<_>> = command[1..].iter().map(Json::as_f64).collect();
+    match args {
+        Some(args) => Ok((name, args)),
+        None => Err(invalid(format!("invalid command {}", json))),
+    }
+}
+
+fn read_path(json: &Json) -> Result<Path2D, SceneError> {
+    let mut commands = Vec::new();
+    for command in as_array(json, "path")? {
+        let (name, args) = read_command(command)?;
+        let p = |i: usize| Vector2::new(args[2 * i], args[2 * i + 1]);
+        commands.push(match (name, args.len()) {
+            ("M", 2) => Path2DCmd::JumpTo(p(0)),
+            ("L", 2) => Path2DCmd::LineTo(p(0)),
+            ("Q", 4) => Path2DCmd::QuadTo(p(0), p(1)),
+            ("C", 6) => Path2DCmd::CubicTo(p(0), p(1), p(2)),
+            ("Z", 0) => Path2DCmd::CloseShape,
+            _ => return Err(invalid(format!("invalid path command {}", command))),
+        });
+    }
+    Ok(commands.into())
+}
+
+fn read_weight(json: &Json) -> Result<StrokeWeight, SceneError> {
+    let mut commands = Vec::new();
+    for command in as_array(json, "weight")? {
+        let (name, args) = read_command(command)?;
+        let p = |i: usize| Vector3::new(args[3 * i], args[3 * i + 1], args[3 * i + 2]);
+        commands.push(match (name, args.len()) {
+            ("L", 3) => WeightCmd::LineTo(p(0)),
+            ("Q", 6) => WeightCmd::QuadTo(p(0), p(1)),
+            ("C", 9) => WeightCmd::CubicTo(p(0), p(1), p(2)),
+            _ => return Err(invalid(format!("invalid weight command {}", command))),
+        });
+    }
+    Ok(commands.into())
+}
+
+fn read_drawable(owner: NodeRef, json: &Json) -> Result<Drawable, SceneError> {
+    let object = as_object(json, "drawable")?;
+    let index = |json: &Json| {
+        json.as_u64()
+            .ok_or_else(|| invalid(format!("invalid drawable index {}", json)))
+    };
+    let id = match object.get("id") {
+        Some(Json::Array(pair)) if pair.len() == 2 => (read_node_ref(&pair[0])?, index(&pair[1])?),
+        Some(id) => (owner, index(id)?),
+        None => return Err(invalid("drawable has no ID")),
+    };
+
+    let path = match object.get("path") {
+        Some(path) => read_path(path)?,
+        None => return Err(invalid("drawable has no path")),
+    };
+    let fill = match object.get("fill") {
+        Some(fill) => Some(read_color(fill)?),
+        None => None,
+    };
+    let stroke = match object.get("stroke") {
+        Some(stroke) => {
+            let weight = match stroke.get("weight") {
+                Some(weight) => read_weight(weight)?,
+                None => StrokeWeight::constant(),
+            };
+            let width = stroke["width"]
+                .as_f64()
+                .ok_or_else(|| invalid("stroke has no width"))?;
+            let color = read_color(&stroke["color"])?;
+            Some((weight, width as f32, color))
+        }
+        None => None,
+    };
+    let transform = match object.get("transform") {
+        Some(transform) => {
+            let v: Vec<_> = read_floats(transform, 16, "transform")?
+                .into_iter()
+                .map(|x| x as f32)
+                .collect();
+            Some(matrix(&v))
+        }
+        None => None,
+    };
+
+    Ok(Drawable {
+        id,
+        shape: Shape {
+            path,
+            stroke,
+            fill,
+            transform,
+        },
+    })
+}
+
+fn read_interpolation(json: &Json) -> Result<Interpolation, SceneError> {
+    match json.as_str() {
+        Some("linear") => return Ok(Interpolation::Linear),
+        Some("hold") => return Ok(Interpolation::Hold),
+        _ => (),
+    }
+    if let Some(steps) = json.get("steps").and_then(Json::as_u64) {
+        return Ok(Interpolation::Step(steps as u32));
+    }
+    if let Some(curve) = json.get("bezier") {
+        let v = read_floats(curve, 4, "bezier curve")?;
+        return Ok(Interpolation::Bezier(v[0], v[1], v[2], v[3]));
+    }
+    Err(invalid(format!("invalid interpolation {}", json)))
+}
+
+#[test]
+fn round_trip() {
+    use crate::data::Keyframe;
+
+    let mut graph = Graph::new();
+    let mut a = Node::empty("a".into());
+    a.set(0, 1.5);
+    a.set(1, "text");
+    a.set(2, Vector2::new(1., 2.));
+    a.set(3, Matrix4::from_scale(2.));
+    a.set(4, Color::WHITE);
+    let path: Path2D = vec![
+        Path2DCmd::JumpTo(Vector2::new(0., 0.)),
+        Path2DCmd::CubicTo(
+            Vector2::new(1., 0.),
+            Vector2::new(1., 1.),
+            Vector2::new(0., 1.),
+        ),
+        Path2DCmd::CloseShape,
+    ]
+    .into();
+    a.set(5, path.clone());
+    let mut track = Track::new();
+    track.insert(0., 0., Interpolation::Bezier(0.4, 0., 0.2, 1.));
+    track.insert(1., 10., Interpolation::Hold);
+    a.set_track(0, track.clone());
+    let a = graph.add_node(a);
+
+    let mut b = Node::empty("b".into());
+    b.enabled = false;
+    let b = graph.add_node(b);
+    graph.node_mut(&b).unwrap().set(
+        0,
+        Drawable {
+            id: (b, 3),
+            shape: Shape {
+                path,
+                stroke: Some((StrokeWeight::constant(), 2., Color::BLACK)),
+                fill: None,
+                transform: Some(Matrix4::from_scale(0.5)),
+            },
+        },
+    );
+    graph.link(a, 7, b, 1);
+    graph.set_output(b);
+
+    let schemas = SchemaRegistry::new();
+    let loaded = Graph::from_scene(&graph.to_scene(&schemas).unwrap(), &schemas).unwrap();
+    assert_eq!(loaded.output(), b);
+    assert_eq!(
+        loaded.iter_links().collect::<Vec<_>>(),
+        vec![((a, 7), (b, 1))]
+    );
+    for (node_ref, node) in graph.iter_nodes() {
+        let loaded = loaded.node(node_ref).unwrap();
+        assert_eq!(loaded.node_type, node.node_type);
+        assert_eq!(loaded.enabled, node.enabled);
+        assert_eq!(loaded.props, node.props);
+    }
+    let keyframes: &[Keyframe] = loaded.node(&a).unwrap().track(0).unwrap().keyframes();
+    assert_eq!(keyframes, track.keyframes());
+
+    let mut graph = Graph::new();
+    graph.add_node(Node::empty("c".into()));
+    let c = graph.add_node(Node::empty("c".into()));
+    graph.node_mut(&c).unwrap().set_any(0, ());
+    match graph.to_scene(&schemas) {
+        Err(SceneError::Unsupported(node, 0, ValueType::Any)) => assert_eq!(node, c),
+        res => panic!("unexpected result {:?}", res),
+    }
+
+    assert!(Graph::from_scene("{\"nodes\": [{\"id\": 0, \"type\": \"a\"}]}", &schemas).is_err());
+    assert!(Graph::from_scene("{\"nodes\": [{\"id\": 1}]}", &schemas).is_err());
+}
+
+#[test]
+fn builtin_port_names() {
+    use crate::node::defs::{builtin_schemas, CameraProps, GaussianProps};
+
+    let source = r#"{
+        "output": 1,
+        "nodes": [
+            { "id": 1, "type": "narwhal.camera", "props": { "size": [400, 300] } },
+            { "id": 2, "type": "narwhal.gaussian-blur", "props": { "radius": 4 } }
+        ],
+        "links": [{ "from": [2, "out"], "to": [1, "in"] }]
+    }"#;
+    let schemas = builtin_schemas();
+    let graph = Graph::from_scene(source, &schemas).unwrap();
+    let camera = NodeRef::from_id(1);
+    let blur = NodeRef::from_id(2);
+    assert_eq!(
+        graph[blur].get(GaussianProps::Radius.into()),
+        Some(&Value::Float(4.))
+    );
+    assert_eq!(
+        graph.iter_links().collect::<Vec<_>>(),
+        vec![(
+            (blur, GaussianProps::Out.into()),
+            (camera, CameraProps::In.into())
+        )]
+    );
+
+    let saved = graph.to_scene(&schemas).unwrap();
+    assert!(saved.contains("\"radius\""));
+    assert!(Graph::from_scene(source, &SchemaRegistry::new()).is_err());
+}
+
+#[test]
+fn numeric_enums() {
+    use crate::data::TextAlign;
+    use crate::node::defs::{
+        builtin_schemas, MaskProps, TextProps, ToneMapProps, MASK_NAME, TEXT_NAME, TONE_MAP_NAME,
+    };
+    use crate::render::fx::{MaskMode, ToneMapOperator};
+
+    let mut graph = Graph::new();
+    let mut tone_map = Node::empty(TONE_MAP_NAME.into());
+    tone_map.set(
+        ToneMapProps::Operator.into(),
+        ToneMapOperator::Aces as i32 as f64,
+    );
+    let tone_map = graph.add_node(tone_map);
+    let mut mask = Node::empty(MASK_NAME.into());
+    mask.set(MaskProps::Mode.into(), MaskMode::LumaCutter as i32 as f64);
+    let mask = graph.add_node(mask);
+    let mut text = Node::empty(TEXT_NAME.into());
+    text.set(TextProps::Align.into(), TextAlign::Right as i32 as f64);
+    let text = graph.add_node(text);
+
+    let schemas = builtin_schemas();
+    let saved = graph.to_scene(&schemas).unwrap();
+    assert!(saved.contains("\"operator\""));
+    let loaded = Graph::from_scene(&saved, &schemas).unwrap();
+    match loaded[tone_map].get(ToneMapProps::Operator.into()) {
+        Some(Value::Float(index)) => {
+            assert_eq!(
+                ToneMapOperator::from_index(*index as i32),
+                Some(ToneMapOperator::Aces)
+            );
+        }
+        value => panic!("unexpected value {:?}", value),
+    }
+    match loaded[mask].get(MaskProps::Mode.into()) {
+        Some(Value::Float(index)) => {
+            assert_eq!(
+                MaskMode::from_index(*index as i32),
+                Some(MaskMode::LumaCutter)
+            );
+        }
+        value => panic!("unexpected value {:?}", value),
+    }
+    match loaded[text].get(TextProps::Align.into()) {
+        Some(Value::Float(index)) => {
+            assert_eq!(TextAlign::from_index(*index as i32), Some(TextAlign::Right));
+        }
+        value => panic!("unexpected value {:?}", value),
+    }
+}
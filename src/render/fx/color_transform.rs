@@ -1,4 +1,5 @@
 use crate::render::Texture;
+use cgmath::{Matrix3, Matrix4, SquareMatrix};
 use failure::Error;
 use half::f16;
 use lcms_prime::pixel_format::RGBA;
@@ -22,7 +23,31 @@ mod shader {
     vulkano_shaders::shader!(ty: "compute", path: "src/shaders/color_transform.comp");
 }
 
-use self::shader::ty::Data;
+use self::shader::ty::{Data, Region};
+
+/// How transformed values are encoded for output.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputEncoding {
+    /// Values are mapped through the LUT (clamped to the LUT bounds).
+    Lut,
+
+    /// Linear values are passed through as-is, including values above 1.
+    Linear,
+
+    /// Values are encoded with the SMPTE ST 2084 (PQ) curve, with 1.0 mapping to the given paper
+    /// white luminance in nits.
+    Pq { paper_white: f32 },
+}
+
+impl OutputEncoding {
+    fn shader_values(self) -> (i32, f32) {
+        match self {
+            OutputEncoding::Lut => (0, 0.),
+            OutputEncoding::Linear => (1, 0.),
+            OutputEncoding::Pq { paper_white } => (2, paper_white),
+        }
+    }
+}
 
 /// A color transform.
 pub struct ColorTransform {
@@ -34,8 +59,11 @@ pub struct ColorTransform {
     input_sampler: Arc<Sampler>,
     lut_sampler: Arc<Sampler>,
     transform: Option<Transform<RGBA<f32>, RGBA<f32>>>,
+    matrix: Matrix3<f32>,
+    encoding: OutputEncoding,
     lut_resolution: u16,
     lut_bounds: (f32, f32),
+    lut_is_set: bool,
     lut_needs_update: bool,
 }
 
@@ -71,8 +99,11 @@ impl ColorTransform {
                 ..BufferUsage::none()
             },
             Data {
+                matrix: Matrix4::identity().into(),
                 lower_bound: lut_bounds.0,
                 lut_range: lut_bounds.1 - lut_bounds.0,
+                encoding: 0,
+                paper_white: 0.,
             },
         )?;
 
@@ -146,8 +177,11 @@ impl ColorTransform {
             input_sampler,
             lut_sampler,
             transform: None,
+            matrix: Matrix3::identity(),
+            encoding: OutputEncoding::Lut,
             lut_resolution,
             lut_bounds,
+            lut_is_set: false,
             lut_needs_update: true,
         })
     }
@@ -163,6 +197,51 @@ impl ColorTransform {
         self.encode_pipeline()
     }
 
+    /// Sets the LUT from a per-channel transfer function instead of a color transform.
+    pub fn set_curve<F: Fn(f32) -> f32>(&mut self, curve: F) -> Result<(), Error> {
+        self.transform = None;
+
+        let mut lut_data = self.lut_buf.write()?;
+        for i in 0..lut_data.len() / 4 {
+            let value = curve((i as f32 / self.lut_resolution as f32) + self.lut_bounds.0);
+            lut_data[i * 4] = f16::from_f32(value);
+            lut_data[i * 4 + 1] = f16::from_f32(value);
+            lut_data[i * 4 + 2] = f16::from_f32(value);
+            lut_data[i * 4 + 3] = f16::from_f32(1.);
+        }
+
+        self.lut_is_set = true;
+        self.lut_needs_update = true;
+
+        Ok(())
+    }
+
+    /// Sets the matrix that is applied to linear RGB values before encoding.
+    pub fn set_matrix(&mut self, matrix: Matrix3<f32>) -> Result<(), Error> {
+        self.matrix = matrix;
+        self.update_data()
+    }
+
+    /// Sets the output encoding.
+    pub fn set_encoding(&mut self, encoding: OutputEncoding) -> Result<(), Error> {
+        self.encoding = encoding;
+        self.update_data()
+    }
+
+    /// Returns the output encoding.
+    pub fn encoding(&self) -> OutputEncoding {
+        self.encoding
+    }
+
+    fn update_data(&mut self) -> Result<(), Error> {
+        let (encoding, paper_white) = self.encoding.shader_values();
+        let mut data = self.data_buf.write()?;
+        data.matrix = Matrix4::from(self.matrix).into();
+        data.encoding = encoding;
+        data.paper_white = paper_white;
+        Ok(())
+    }
+
     fn encode_pipeline(&mut self) -> Result<(), Error> {
         if self.transform.is_none() {
             return Ok(());
@@ -191,6 +270,7 @@ impl ColorTransform {
             lut_data[i] = f16::from_f32(lut_pixels[i]);
         }
 
+        self.lut_is_set = true;
         self.lut_needs_update = true;
 
         Ok(())
@@ -198,17 +278,22 @@ impl ColorTransform {
 
     /// Dispatches the color transform compute shader.
     ///
+    /// The input region with its bottom right corner at the texture coordinates `in_scale` is
+    /// stretched to the entire output.
+    ///
     /// # Errors
-    /// - when `set_transform` hasn’t been called to set a transform
+    /// - when the output encoding uses the LUT and neither `set_transform` nor `set_curve` have
+    ///   been called
     /// - when the output texture is not 2D
     /// - when Vulkan decides to raise an error
     pub fn dispatch(
         &mut self,
         mut cmd_buffer: AutoCommandBufferBuilder,
         input: &Texture,
+        in_scale: [f32; 2],
         output: &Arc<StorageImage<Format>>,
     ) -> Result<AutoCommandBufferBuilder, Error> {
-        if self.transform.is_none() {
+        if self.encoding == OutputEncoding::Lut && !self.lut_is_set {
             return Err(DispatchError::NoTransform.into());
         }
 
@@ -242,7 +327,7 @@ impl ColorTransform {
             ],
             Arc::clone(&self.pipeline),
             set,
-            (),
+            Region { in_scale },
         )?;
 
         Ok(cmd_buffer)
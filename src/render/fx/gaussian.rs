@@ -1,7 +1,6 @@
 //! Gaussian blur compute shader.
 
-use crate::eval::EvalError;
-use crate::render::Texture;
+use crate::render::{Texture, TextureRef};
 use failure::Error;
 use std::sync::Arc;
 use vulkano::command_buffer::pool::standard::StandardCommandPoolBuilder;
@@ -9,7 +8,7 @@ use vulkano::command_buffer::AutoCommandBufferBuilder;
 use vulkano::descriptor::descriptor_set::FixedSizeDescriptorSetsPool;
 use vulkano::device::Device;
 use vulkano::format::Format;
-use vulkano::image::{Dimensions, StorageImage};
+use vulkano::image::StorageImage;
 use vulkano::pipeline::{ComputePipeline, ComputePipelineAbstract};
 use vulkano::sampler::{Filter, MipmapMode, Sampler, SamplerAddressMode};
 
@@ -66,13 +65,17 @@ impl GaussianBlur {
         mut cmd_buffer: AutoCommandBufferBuilder<StandardCommandPoolBuilder>,
         input: &Texture,
         output: &Arc<StorageImage<Format>>,
+        size: [u32; 2],
         filter_size: f32,
         vertical: bool,
     ) -> Result<AutoCommandBufferBuilder<StandardCommandPoolBuilder>, Error> {
-        let (width, height) = match output.dimensions() {
-            Dimensions::Dim2d { width, height } => (width, height),
-            _ => return Err(EvalError::Input("Unsupported texture dimensions".into()).into()),
-        };
+        let [width, height] = size;
+        let [in_width, in_height] = input.dimensions();
+        let texel = [1. / in_width as f32, 1. / in_height as f32];
+        let max_coord = [
+            (width as f32 - 0.5) * texel[0],
+            (height as f32 - 0.5) * texel[1],
+        ];
 
         let set = self
             .ds_pool
@@ -90,6 +93,8 @@ impl GaussianBlur {
             Arc::clone(&self.pipeline),
             set,
             Data {
+                texel,
+                max_coord,
                 size: filter_size,
                 vertical: if vertical { 1 } else { 0 },
             },
@@ -101,7 +106,8 @@ impl GaussianBlur {
     /// Dispatches the gaussian blur shader in the command buffer.
     ///
     /// Because this is a multi-pass gaussian blur (i.e. horizontal and vertical) a third attachment
-    /// is required for temporary storage.
+    /// is required for temporary storage. Only a region the size of the input region is read from
+    /// and written to in the other textures.
     ///
     /// To set the blur radius, use `radius_px`. Note that for the blur to be of decent quality,
     /// `passes` should be increased as well. The shader is approximately a 9-tap gaussian blur,
@@ -111,7 +117,7 @@ impl GaussianBlur {
     pub fn dispatch(
         &mut self,
         mut cmd_buffer: AutoCommandBufferBuilder<StandardCommandPoolBuilder>,
-        input: &Texture,
+        input: &TextureRef,
         intermediate: &Arc<StorageImage<Format>>,
         output: &Arc<StorageImage<Format>>,
         radius_px: f32,
@@ -120,21 +126,47 @@ impl GaussianBlur {
         // times 2 because this is the radius (not the diameter)
         // 9 because the shader is a 9-tap filter
         let filter_size = radius_px * 2. / (9. * passes as f32);
+        let size = input.px_size();
 
         let intermediate_tex = Texture::Storage(Arc::clone(&intermediate));
         let output_tex = Texture::Storage(Arc::clone(&output));
 
         // TODO: keep descriptor sets around instead of recreating each pass
         if passes > 0 {
-            cmd_buffer = self.dispatch_pass(cmd_buffer, input, intermediate, filter_size, false)?;
-            cmd_buffer =
-                self.dispatch_pass(cmd_buffer, &intermediate_tex, output, filter_size, true)?;
+            cmd_buffer = self.dispatch_pass(
+                cmd_buffer,
+                input.color(),
+                intermediate,
+                size,
+                filter_size,
+                false,
+            )?;
+            cmd_buffer = self.dispatch_pass(
+                cmd_buffer,
+                &intermediate_tex,
+                output,
+                size,
+                filter_size,
+                true,
+            )?;
         }
         for _ in 1..passes {
-            cmd_buffer =
-                self.dispatch_pass(cmd_buffer, &output_tex, intermediate, filter_size, false)?;
-            cmd_buffer =
-                self.dispatch_pass(cmd_buffer, &intermediate_tex, output, filter_size, true)?;
+            cmd_buffer = self.dispatch_pass(
+                cmd_buffer,
+                &output_tex,
+                intermediate,
+                size,
+                filter_size,
+                false,
+            )?;
+            cmd_buffer = self.dispatch_pass(
+                cmd_buffer,
+                &intermediate_tex,
+                output,
+                size,
+                filter_size,
+                true,
+            )?;
         }
         Ok(cmd_buffer)
     }
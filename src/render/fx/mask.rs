@@ -1,7 +1,6 @@
 //! Mask compute shader.
 
-use crate::eval::EvalError;
-use crate::render::Texture;
+use crate::render::TextureRef;
 use failure::Error;
 use std::sync::Arc;
 use vulkano::command_buffer::pool::standard::StandardCommandPoolBuilder;
@@ -9,7 +8,7 @@ use vulkano::command_buffer::AutoCommandBufferBuilder;
 use vulkano::descriptor::descriptor_set::FixedSizeDescriptorSetsPool;
 use vulkano::device::Device;
 use vulkano::format::Format;
-use vulkano::image::{Dimensions, StorageImage};
+use vulkano::image::StorageImage;
 use vulkano::pipeline::{ComputePipeline, ComputePipelineAbstract};
 use vulkano::sampler::{BorderColor, Filter, MipmapMode, Sampler, SamplerAddressMode};
 
@@ -24,23 +23,37 @@ use self::shader::ty::Data;
 
 /// Mask modes.
 ///
-/// Luma is obtained by taking the average of the three color channels multiplied by alpha.
+/// Luma is obtained by taking the average of the three premultiplied color channels, so it is
+/// already weighted by alpha.
 #[repr(i32)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum MaskMode {
-    /// Sets the texture alpha to the alpha of the matte.
+    /// Multiplies the texture with the alpha of the matte.
     AlphaMatte = 0,
 
-    /// Sets the texture alpha to the luma of the matte.
+    /// Multiplies the texture with the luma of the matte.
     LumaMatte = 1,
 
-    /// Sets the texture alpha to the inverse of the alpha of the matte.
+    /// Multiplies the texture with the inverse of the alpha of the matte.
     AlphaCutter = 2,
 
-    /// Sets the texture alpha to the inverse of the luma of the matte.
+    /// Multiplies the texture with the inverse of the luma of the matte.
     LumaCutter = 3,
 }
 
+impl MaskMode {
+    /// Returns the mask mode with the given numeric value.
+    pub fn from_index(index: i32) -> Option<MaskMode> {
+        match index {
+            0 => Some(MaskMode::AlphaMatte),
+            1 => Some(MaskMode::LumaMatte),
+            2 => Some(MaskMode::AlphaCutter),
+            3 => Some(MaskMode::LumaCutter),
+            _ => None,
+        }
+    }
+}
+
 /// Mask/matte shader.
 pub struct Mask {
     pipeline: Arc<dyn ComputePipelineAbstract + Send + Sync>,
@@ -81,24 +94,24 @@ impl Mask {
     }
 
     /// Dispatches the mask shader in the command buffer.
+    ///
+    /// The mask is stretched to the input size, and the output is written to a region the size of
+    /// the input region.
     pub fn dispatch(
         &mut self,
         mut cmd_buffer: AutoCommandBufferBuilder<StandardCommandPoolBuilder>,
-        input: &Texture,
-        mask: &Texture,
+        input: &TextureRef,
+        mask: &TextureRef,
         output: &Arc<StorageImage<Format>>,
         mode: MaskMode,
     ) -> Result<AutoCommandBufferBuilder<StandardCommandPoolBuilder>, Error> {
-        let (width, height) = match output.dimensions() {
-            Dimensions::Dim2d { width, height } => (width, height),
-            _ => return Err(EvalError::Input("Unsupported texture dimensions".into()).into()),
-        };
+        let [width, height] = input.px_size();
 
         let set = self
             .ds_pool
             .next()
-            .add_sampled_image(input.clone(), Arc::clone(&self.sampler))?
-            .add_sampled_image(mask.clone(), Arc::clone(&self.sampler))?
+            .add_sampled_image(input.color().clone(), Arc::clone(&self.sampler))?
+            .add_sampled_image(mask.color().clone(), Arc::clone(&self.sampler))?
             .add_image(Arc::clone(&output))?
             .build()?;
 
@@ -110,7 +123,12 @@ impl Mask {
             ],
             Arc::clone(&self.pipeline),
             set,
-            Data { mode: mode as i32 },
+            Data {
+                out_size: [width as f32, height as f32],
+                in_scale: input.uv_scale().into(),
+                mask_scale: mask.uv_scale().into(),
+                mode: mode as i32,
+            },
         )?;
 
         Ok(cmd_buffer)
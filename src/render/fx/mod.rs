@@ -3,7 +3,9 @@
 mod color_transform;
 mod gaussian;
 mod mask;
+mod tone_map;
 
 pub use self::color_transform::*;
 pub use self::gaussian::*;
 pub use self::mask::*;
+pub use self::tone_map::*;
@@ -0,0 +1,146 @@
+//! Tone mapping compute shader.
+
+use crate::render::TextureRef;
+use failure::Error;
+use std::sync::Arc;
+use vulkano::command_buffer::pool::standard::StandardCommandPoolBuilder;
+use vulkano::command_buffer::AutoCommandBufferBuilder;
+use vulkano::descriptor::descriptor_set::FixedSizeDescriptorSetsPool;
+use vulkano::device::Device;
+use vulkano::format::Format;
+use vulkano::image::StorageImage;
+use vulkano::pipeline::{ComputePipeline, ComputePipelineAbstract};
+use vulkano::sampler::{BorderColor, Filter, MipmapMode, Sampler, SamplerAddressMode};
+
+const LOCAL_SIZE_X: f32 = 16.;
+const LOCAL_SIZE_Y: f32 = 16.;
+
+mod shader {
+    vulkano_shaders::shader!(ty: "compute", path: "src/shaders/tone_map.comp");
+}
+
+use self::shader::ty::Data;
+
+/// Tone mapping operators.
+///
+/// All operators map linear ACEScg values to linear ACEScg values, so the output still goes
+/// through the regular display color transform.
+#[repr(i32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ToneMapOperator {
+    /// Only applies exposure; values above 1 are left as-is.
+    None = 0,
+
+    /// Reinhard: `x / (1 + x)`.
+    Reinhard = 1,
+
+    /// John Hable’s filmic curve (as used in Uncharted 2).
+    Hable = 2,
+
+    /// Stephen Hill’s fitted approximation of the ACES RRT and sRGB ODT.
+    Aces = 3,
+
+    /// A minimal AgX implementation with an approximated default contrast curve.
+    AgX = 4,
+}
+
+impl ToneMapOperator {
+    /// Returns the tone mapping operator with the given numeric value.
+    pub fn from_index(index: i32) -> Option<ToneMapOperator> {
+        match index {
+            0 => Some(ToneMapOperator::None),
+            1 => Some(ToneMapOperator::Reinhard),
+            2 => Some(ToneMapOperator::Hable),
+            3 => Some(ToneMapOperator::Aces),
+            4 => Some(ToneMapOperator::AgX),
+            _ => None,
+        }
+    }
+}
+
+impl Default for ToneMapOperator {
+    fn default() -> ToneMapOperator {
+        ToneMapOperator::None
+    }
+}
+
+/// Tone mapping shader.
+pub struct ToneMap {
+    pipeline: Arc<dyn ComputePipelineAbstract + Send + Sync>,
+    sampler: Arc<Sampler>,
+    ds_pool: FixedSizeDescriptorSetsPool<Arc<dyn ComputePipelineAbstract + Send + Sync>>,
+}
+
+impl ToneMap {
+    /// Compiles shaders and creates a pipeline.
+    pub fn new(device: Arc<Device>) -> Result<ToneMap, Error> {
+        let shader = shader::Shader::load(Arc::clone(&device))?;
+
+        let pipeline: Arc<dyn ComputePipelineAbstract + Send + Sync> = Arc::new(
+            ComputePipeline::new(Arc::clone(&device), &shader.main_entry_point(), &())?,
+        );
+
+        let ds_pool = FixedSizeDescriptorSetsPool::new(Arc::clone(&pipeline), 0);
+
+        let sampler = Sampler::new(
+            Arc::clone(&device),
+            Filter::Nearest,
+            Filter::Nearest,
+            MipmapMode::Nearest,
+            SamplerAddressMode::ClampToBorder(BorderColor::FloatTransparentBlack),
+            SamplerAddressMode::ClampToBorder(BorderColor::FloatTransparentBlack),
+            SamplerAddressMode::ClampToBorder(BorderColor::FloatTransparentBlack),
+            0.,
+            1.,
+            0.,
+            0.,
+        )?;
+
+        Ok(ToneMap {
+            pipeline,
+            sampler,
+            ds_pool,
+        })
+    }
+
+    /// Dispatches the tone mapping shader in the command buffer.
+    ///
+    /// `exposure` is given in stops and is applied before the operator. The input is stretched to
+    /// a region of `size` pixels at the top left of the output.
+    pub fn dispatch(
+        &mut self,
+        mut cmd_buffer: AutoCommandBufferBuilder<StandardCommandPoolBuilder>,
+        input: &TextureRef,
+        output: &Arc<StorageImage<Format>>,
+        size: [u32; 2],
+        operator: ToneMapOperator,
+        exposure: f32,
+    ) -> Result<AutoCommandBufferBuilder<StandardCommandPoolBuilder>, Error> {
+        let [width, height] = size;
+
+        let set = self
+            .ds_pool
+            .next()
+            .add_sampled_image(input.color().clone(), Arc::clone(&self.sampler))?
+            .add_image(Arc::clone(&output))?
+            .build()?;
+
+        cmd_buffer = cmd_buffer.dispatch(
+            [
+                (width as f32 / LOCAL_SIZE_X).ceil() as u32,
+                (height as f32 / LOCAL_SIZE_Y).ceil() as u32,
+                1,
+            ],
+            Arc::clone(&self.pipeline),
+            set,
+            Data {
+                out_size: [width as f32, height as f32],
+                in_scale: input.uv_scale().into(),
+                mode: operator as i32,
+                exposure,
+            },
+        )?;
+
+        Ok(cmd_buffer)
+    }
+}
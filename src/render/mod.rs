@@ -1,16 +1,23 @@
 //! Rendering.
 
 pub mod fx;
+mod offscreen;
 mod presenter;
+pub mod reference;
 mod renderer;
+mod report;
+mod sequence;
 mod shape;
 pub mod stroke_tess;
 mod swapchain_renderer;
 mod tex_comp;
 mod texture;
 
+pub use self::offscreen::*;
 pub use self::presenter::*;
 pub use self::renderer::*;
+pub use self::report::*;
+pub use self::sequence::*;
 pub use self::shape::*;
 pub use self::tex_comp::*;
 pub use self::texture::*;
@@ -0,0 +1,97 @@
+use crate::data::Bitmap;
+use crate::render::{DeviceRetrievalError, RenderError, Renderer, Texture};
+use failure::Error;
+use half::f16;
+use std::sync::Arc;
+use vulkano::buffer::{BufferUsage, CpuAccessibleBuffer};
+use vulkano::command_buffer::CommandBuffer;
+use vulkano::device::{Device, DeviceExtensions, Features, Queue};
+use vulkano::instance::{Instance, InstanceExtensions, PhysicalDevice};
+use vulkano::sync::GpuFuture;
+
+/// Creates a Vulkan instance without any window system extensions.
+pub fn offscreen_instance() -> Result<Arc<Instance>, Error> {
+    Ok(Instance::new(None, &InstanceExtensions::none(), None)?)
+}
+
+/// Chooses and creates a device suitable for offscreen rendering.
+pub fn offscreen_device(
+    instance: &Arc<Instance>,
+) -> Result<(Arc<Device>, Arc<Queue>), DeviceRetrievalError> {
+    for dev in PhysicalDevice::enumerate(instance) {
+        if let Some(queue_family) = dev
+            .queue_families()
+            .find(|q| q.supports_graphics() && q.supports_compute())
+        {
+            debug!(target: "narwhal", "Using device {}", dev.name());
+
+            let (device, mut queues) = Device::new(
+                dev,
+                &Features::none(),
+                &DeviceExtensions::none(),
+                [(queue_family, 0.5)].iter().cloned(),
+            )?;
+
+            return Ok((device, queues.next().expect("No device queue")));
+        }
+    }
+
+    Err(DeviceRetrievalError::NoSuitableDevice)
+}
+
+impl Renderer {
+    /// Renders the scene, waits for the GPU to finish, and reads back the output texture.
+    ///
+    /// The bitmap will be in linear ACEScg with premultiplied alpha.
+    pub fn render_to_bitmap(&mut self) -> Result<Bitmap, RenderError> {
+        let cmd_buffer = self.new_cmd_buffer().map_err(Error::from)?;
+        let (cmd_buffer, texture) = self.render(cmd_buffer)?;
+
+        let [width, height] = texture.px_size();
+        let buffer = CpuAccessibleBuffer::from_iter(
+            Arc::clone(self.device()),
+            BufferUsage::transfer_destination(),
+            (0..width * height * 4).map(|_| f16::from_f32(0.)),
+        )
+        .map_err(Error::from)?;
+
+        let cmd_buffer = match texture.color() {
+            Texture::Attachment(image) => cmd_buffer.copy_image_to_buffer_dimensions(
+                Arc::clone(image),
+                Arc::clone(&buffer),
+                [0, 0, 0],
+                [width, height, 1],
+                0,
+                1,
+                0,
+            ),
+            Texture::Storage(image) => cmd_buffer.copy_image_to_buffer_dimensions(
+                Arc::clone(image),
+                Arc::clone(&buffer),
+                [0, 0, 0],
+                [width, height, 1],
+                0,
+                1,
+                0,
+            ),
+        }
+        .map_err(Error::from)?;
+
+        cmd_buffer
+            .build()
+            .map_err(Error::from)?
+            .execute(Arc::clone(self.queue()))
+            .map_err(Error::from)?
+            .then_signal_fence_and_flush()
+            .map_err(Error::from)?
+            .wait(None)
+            .map_err(Error::from)?;
+
+        let pixels = buffer.read().map_err(Error::from)?;
+        Ok(Bitmap {
+            width,
+            height,
+            pixels: pixels.iter().map(|x| x.to_f32()).collect(),
+        })
+    }
+}
@@ -1,15 +1,18 @@
 use crate::data::{ACES_CG, SRGB};
 use crate::platform::NarwhalSurface;
-use crate::render::fx::ColorTransform;
+use crate::render::fx::{ColorTransform, OutputEncoding, ToneMap, ToneMapOperator};
 use crate::render::swapchain_renderer::SwapchainRenderer;
-use crate::render::{Texture, COLOR_FORMAT};
+use crate::render::{Texture, TextureRef, COLOR_FORMAT};
+use cgmath::{Matrix3, SquareMatrix};
 use failure::Error;
 use lcms_prime::{Intent, Profile, Transform};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use vulkano::command_buffer::{
     AutoCommandBuffer, AutoCommandBufferBuilder, CommandBufferExecFuture,
 };
 use vulkano::device::{Device, DeviceCreationError, DeviceExtensions, Features, Queue};
+use vulkano::format::Format;
 use vulkano::image::{Dimensions, ImageUsage, StorageImage, SwapchainImage};
 use vulkano::instance::{Instance, PhysicalDevice};
 use vulkano::swapchain::{
@@ -32,9 +35,137 @@ impl From<Error> for PresentError {
     }
 }
 
+/// Output color spaces a presenter may negotiate with the surface.
+///
+/// Only color spaces for which [`OutputColorSpace::is_presentable`] returns true are currently
+/// negotiated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OutputColorSpace {
+    /// Non-linear sRGB. Values above 1 are clamped.
+    Srgb,
+
+    /// Linear extended sRGB. Values above 1 are passed through.
+    ExtendedSrgbLinear,
+
+    /// Rec. 2020 primaries with the SMPTE ST 2084 (PQ) transfer function.
+    Hdr10,
+
+    /// Display P3 primaries with the sRGB transfer function. Values above 1 are clamped.
+    DisplayP3,
+}
+
+impl OutputColorSpace {
+    /// The default order of preference when negotiating a color space.
+    pub const DEFAULT_PREFERENCE: &'static [OutputColorSpace] = &[
+        OutputColorSpace::ExtendedSrgbLinear,
+        OutputColorSpace::Hdr10,
+        OutputColorSpace::DisplayP3,
+        OutputColorSpace::Srgb,
+    ];
+
+    fn vk_color_space(self) -> ColorSpace {
+        match self {
+            OutputColorSpace::Srgb => ColorSpace::SrgbNonLinear,
+            OutputColorSpace::ExtendedSrgbLinear => ColorSpace::ExtendedSrgbLinear,
+            OutputColorSpace::Hdr10 => ColorSpace::Hdr10St2084,
+            OutputColorSpace::DisplayP3 => ColorSpace::DisplayP3NonLinear,
+        }
+    }
+
+    /// Returns true if a swapchain can be created in this color space.
+    ///
+    /// vulkano 0.11 always creates swapchains in `SRGB_NONLINEAR` and has no way of choosing
+    /// another image color space, so presenting linear or PQ-encoded values in anything else
+    /// would display them as if they were sRGB.
+    pub fn is_presentable(self) -> bool {
+        self.vk_color_space() == ColorSpace::SrgbNonLinear
+    }
+
+    /// Returns true if values above 1 can be displayed.
+    pub fn is_extended_range(self) -> bool {
+        match self {
+            OutputColorSpace::ExtendedSrgbLinear | OutputColorSpace::Hdr10 => true,
+            OutputColorSpace::Srgb | OutputColorSpace::DisplayP3 => false,
+        }
+    }
+
+    /// Returns true if the given swapchain format can carry this color space without losing
+    /// too much precision.
+    fn accepts_format(self, format: Format) -> bool {
+        match self {
+            OutputColorSpace::Srgb | OutputColorSpace::DisplayP3 => true,
+            OutputColorSpace::ExtendedSrgbLinear => format == COLOR_FORMAT,
+            OutputColorSpace::Hdr10 => {
+                format == COLOR_FORMAT || format == Format::A2B10G10R10UnormPack32
+            }
+        }
+    }
+
+    /// Returns the matrix that converts linear ACEScg to linear RGB with the primaries of this
+    /// color space (with a Bradford D60 to D65 adaptation).
+    fn matrix_from_aces_cg(self) -> Matrix3<f32> {
+        // cgmath matrices are column-major, so these are transposed
+        match self {
+            OutputColorSpace::Srgb | OutputColorSpace::ExtendedSrgbLinear => Matrix3::new(
+                1.70505, -0.13026, -0.02400, //
+                -0.62179, 1.14080, -0.12897, //
+                -0.08326, -0.01055, 1.15297,
+            ),
+            OutputColorSpace::Hdr10 => Matrix3::new(
+                1.02582, -0.00223, -0.00501, //
+                -0.02005, 1.00458, -0.02529, //
+                -0.00577, -0.00235, 1.03030,
+            ),
+            OutputColorSpace::DisplayP3 => Matrix3::new(
+                1.37921, -0.06933, -0.00216, //
+                -0.30886, 1.08229, -0.04546, //
+                -0.07035, -0.01296, 1.04762,
+            ),
+        }
+    }
+}
+
+/// The sRGB transfer function (linear to non-linear).
+fn srgb_transfer(value: f32) -> f32 {
+    let value = value.max(0.).min(1.);
+    if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1. / 2.4) - 0.055
+    }
+}
+
+/// The default HDR10 luminance of 1.0 in nits (ITU-R BT.2408 reference white).
+const DEFAULT_PAPER_WHITE: f32 = 203.;
+
+/// Timing information about a presented frame.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct FrameTimings {
+    /// Time spent waiting for a swapchain image.
+    pub acquire_latency: Duration,
+
+    /// Time spent recording the presenter’s commands and submitting them.
+    pub encode_time: Duration,
+
+    /// CPU wall-clock time between submitting the frame and the call to
+    /// [`Presenter::frame_completed`], if it was called before the next frame was presented.
+    ///
+    /// This is not GPU time: it includes queueing, presentation and however long it took the
+    /// caller to notice that the frame finished, so it is only an upper bound.
+    pub submit_to_completed: Option<Duration>,
+}
+
+/// Intermediate images, which are kept until the swapchain extent changes.
+struct Intermediates {
+    size: [u32; 2],
+    tone_mapped: Option<Arc<StorageImage<Format>>>,
+    transformed: Option<Arc<StorageImage<Format>>>,
+}
+
 /// Presents a texture on a narwhal surface.
 ///
-/// Assumes sRGB output by default.
+/// Negotiates an output color space with the surface (see [`OutputColorSpace::is_presentable`])
+/// and falls back to sRGB otherwise.
 pub struct Presenter {
     device: Arc<Device>,
     queue: Arc<Queue>,
@@ -42,9 +173,21 @@ pub struct Presenter {
     surface: Arc<Surface<NarwhalSurface>>,
     swapchain: Arc<Swapchain<NarwhalSurface>>,
     chain_images: Vec<Arc<SwapchainImage<NarwhalSurface>>>,
+    color_space: OutputColorSpace,
+    paper_white: f32,
+    tone_map: ToneMap,
+    tone_map_operator: ToneMapOperator,
+    exposure: f32,
     color_transform: ColorTransform,
     color_transform_enabled: bool,
+    profile: Profile,
     tex_renderer: SwapchainRenderer,
+    intermediates: Option<Intermediates>,
+    submitted_at: Option<Instant>,
+    /// Timings of the frame in flight.
+    timings: FrameTimings,
+    /// Timings of the last completed frame.
+    last_frame: FrameTimings,
 }
 
 #[derive(Debug, Fail)]
@@ -53,31 +196,74 @@ enum ColorTransformEncodeError {
     TransformFailed(String),
 }
 
+#[derive(Debug, Fail)]
+enum SwapchainSetupError {
+    #[fail(display = "surface supports no sRGB formats")]
+    NoSrgbFormat,
+}
+
 impl Presenter {
     /// Creates a new presenter.
     ///
-    /// The output color space will be set to sRGB by default.
+    /// The output color space will be negotiated in the order of
+    /// [`OutputColorSpace::DEFAULT_PREFERENCE`].
     pub fn new(
         phys_dev: &PhysicalDevice,
         surface: Arc<Surface<NarwhalSurface>>,
         device: Arc<Device>,
         queue: Arc<Queue>,
+    ) -> Result<Presenter, Error> {
+        Self::with_color_spaces(
+            phys_dev,
+            surface,
+            device,
+            queue,
+            OutputColorSpace::DEFAULT_PREFERENCE,
+        )
+    }
+
+    /// Creates a new presenter, using the first color space in `preference` that the surface
+    /// supports and that can be presented.
+    ///
+    /// Falls back to sRGB if none of them are supported.
+    pub fn with_color_spaces(
+        phys_dev: &PhysicalDevice,
+        surface: Arc<Surface<NarwhalSurface>>,
+        device: Arc<Device>,
+        queue: Arc<Queue>,
+        preference: &[OutputColorSpace],
     ) -> Result<Presenter, Error> {
         let caps = surface.capabilities(*phys_dev)?;
         let alpha = caps.supported_composite_alpha.iter().next().unwrap();
 
         let extent = Self::get_extent(&device, phys_dev.index(), &surface);
 
-        let output_format = if let Some((_, cs)) = caps
-            .supported_formats
+        let fallback = [OutputColorSpace::Srgb];
+        let mut negotiated = preference
             .iter()
-            .find(|(x, _)| *x == COLOR_FORMAT)
-        {
-            (COLOR_FORMAT, *cs)
-        } else {
-            // fallback
-            (caps.supported_formats[0].0, ColorSpace::SrgbNonLinear)
-        };
+            .chain(&fallback)
+            .filter(|color_space| color_space.is_presentable())
+            .filter_map(|color_space| {
+                let formats = caps
+                    .supported_formats
+                    .iter()
+                    .filter(|(format, cs)| {
+                        *cs == color_space.vk_color_space() && color_space.accepts_format(*format)
+                    })
+                    .map(|(format, _)| *format)
+                    .collect::<Vec<_>>();
+
+                // prefer the internal color format if possible
+                if formats.contains(&COLOR_FORMAT) {
+                    Some((COLOR_FORMAT, *color_space))
+                } else {
+                    formats.first().map(|format| (*format, *color_space))
+                }
+            });
+
+        let output_format = negotiated.next().ok_or(SwapchainSetupError::NoSrgbFormat)?;
+
+        debug!(target: "narwhal", "Presenting in {:?} with {:?}", output_format.1, output_format.0);
 
         let (swapchain, chain_images) = Swapchain::new(
             Arc::clone(&device),
@@ -95,6 +281,7 @@ impl Presenter {
             None,
         )?;
 
+        let tone_map = ToneMap::new(Arc::clone(&device))?;
         let color_transform = ColorTransform::new(Arc::clone(&device), &queue, 1024, (0., 1.))?;
         let tex_renderer = SwapchainRenderer::new(Arc::clone(&device), output_format.0)?;
 
@@ -105,21 +292,158 @@ impl Presenter {
             surface,
             swapchain,
             chain_images,
+            color_space: output_format.1,
+            paper_white: DEFAULT_PAPER_WHITE,
+            tone_map,
+            tone_map_operator: ToneMapOperator::None,
+            exposure: 0.,
             color_transform,
             color_transform_enabled: true,
+            profile: SRGB.clone(),
             tex_renderer,
+            intermediates: None,
+            submitted_at: None,
+            timings: FrameTimings::default(),
+            last_frame: FrameTimings::default(),
         };
-        presenter.set_profile(SRGB.clone())?;
+        presenter.update_color_space()?;
         Ok(presenter)
     }
 
+    /// Returns the negotiated output color space.
+    pub fn color_space(&self) -> OutputColorSpace {
+        self.color_space
+    }
+
+    /// Returns the luminance of 1.0 in nits when presenting HDR10.
+    pub fn paper_white(&self) -> f32 {
+        self.paper_white
+    }
+
+    /// Sets the luminance of 1.0 in nits when presenting HDR10.
+    pub fn set_paper_white(&mut self, nits: f32) -> Result<(), Error> {
+        self.paper_white = nits;
+        self.update_color_space()
+    }
+
+    /// Returns the display tone mapping operator and exposure (in stops).
+    pub fn tone_map(&self) -> (ToneMapOperator, f32) {
+        (self.tone_map_operator, self.exposure)
+    }
+
+    /// Sets the display tone mapping operator and exposure (in stops).
+    ///
+    /// Tone mapping is applied to ACEScg values before the output color transform. Note that
+    /// with an extended-range color space, [`ToneMapOperator::None`] will pass values above 1
+    /// through to the display.
+    pub fn set_tone_map(&mut self, operator: ToneMapOperator, exposure: f32) {
+        self.tone_map_operator = operator;
+        self.exposure = exposure;
+    }
+
+    fn tone_map_enabled(&self) -> bool {
+        self.tone_map_operator != ToneMapOperator::None || self.exposure != 0.
+    }
+
+    /// Returns the timings of the last completed frame.
+    pub fn last_frame_timings(&self) -> FrameTimings {
+        self.last_frame
+    }
+
+    /// Notifies the presenter that the last presented frame has finished executing (e.g. right
+    /// after waiting for its fence), so that [`FrameTimings::submit_to_completed`] can be
+    /// recorded.
+    ///
+    /// If this is not called, the frame’s timings are recorded without it when the next one is
+    /// presented.
+    pub fn frame_completed(&mut self) {
+        if let Some(submitted_at) = self.submitted_at.take() {
+            self.timings.submit_to_completed = Some(submitted_at.elapsed());
+            self.last_frame = self.timings;
+        }
+    }
+
+    /// Returns the cached intermediate images, recreating them if the size changed.
+    fn intermediates(&mut self, size: [u32; 2]) -> &mut Intermediates {
+        if self
+            .intermediates
+            .as_ref()
+            .map_or(true, |intermediates| intermediates.size != size)
+        {
+            self.intermediates = Some(Intermediates {
+                size,
+                tone_mapped: None,
+                transformed: None,
+            });
+        }
+        self.intermediates.as_mut().unwrap()
+    }
+
+    fn new_intermediate(
+        device: &Arc<Device>,
+        queue: &Arc<Queue>,
+        size: [u32; 2],
+    ) -> Result<Arc<StorageImage<Format>>, Error> {
+        Ok(StorageImage::with_usage(
+            Arc::clone(device),
+            Dimensions::Dim2d {
+                width: size[0],
+                height: size[1],
+            },
+            COLOR_FORMAT,
+            ImageUsage {
+                sampled: true,
+                storage: true,
+                ..ImageUsage::none()
+            },
+            Some(queue.family()),
+        )?)
+    }
+
+    /// Sets up the color transform for the current color space.
+    fn update_color_space(&mut self) -> Result<(), Error> {
+        self.color_transform_enabled = true;
+        self.color_transform
+            .set_matrix(self.color_space.matrix_from_aces_cg())?;
+
+        match self.color_space {
+            OutputColorSpace::Srgb => self.apply_profile(),
+            OutputColorSpace::ExtendedSrgbLinear => {
+                self.color_transform.set_encoding(OutputEncoding::Linear)
+            }
+            OutputColorSpace::Hdr10 => self.color_transform.set_encoding(OutputEncoding::Pq {
+                paper_white: self.paper_white,
+            }),
+            OutputColorSpace::DisplayP3 => {
+                self.color_transform.set_encoding(OutputEncoding::Lut)?;
+                self.color_transform.set_curve(srgb_transfer)
+            }
+        }
+    }
+
     /// Sets the output color profile.
+    ///
+    /// This only has an effect when presenting in the sRGB color space; the other color spaces
+    /// have a fixed encoding.
     pub fn set_profile(&mut self, profile: Profile) -> Result<(), Error> {
-        let transform = match Transform::new(&ACES_CG, &profile, Intent::Perceptual) {
+        self.profile = profile;
+        if self.color_space != OutputColorSpace::Srgb {
+            return Ok(());
+        }
+        self.apply_profile()
+    }
+
+    /// Sets up the color transform for the current output color profile.
+    fn apply_profile(&mut self) -> Result<(), Error> {
+        let transform = match Transform::new(&ACES_CG, &self.profile, Intent::Perceptual) {
             Ok(t) => t,
             Err(err) => return Err(ColorTransformEncodeError::TransformFailed(err).into()),
         };
 
+        // the profile transform handles primaries by itself
+        self.color_transform.set_matrix(Matrix3::identity())?;
+        self.color_transform.set_encoding(OutputEncoding::Lut)?;
+
         // check if the profile is ACEScg
         // FIXME: this is a terrible heuristic
         // sample a few colors and see if the transform is rougly an identity transform
@@ -191,15 +515,21 @@ impl Presenter {
             .map_err(|e| Error::from(e))?;
         self.swapchain = new_chain;
         self.chain_images = new_images;
+        self.tex_renderer.clear_framebuffers();
 
         Ok(())
     }
 
     /// Presents a texture on screen using the given command buffer.
+    ///
+    /// The texture is stretched to fill the window.
+    ///
+    /// Call [`Presenter::frame_completed`] once the returned future has finished to record how
+    /// long the frame took to complete.
     pub fn present(
         &mut self,
         mut cmd_buffer: AutoCommandBufferBuilder,
-        tex: &Texture,
+        tex: &TextureRef,
     ) -> Result<
         PresentFuture<
             CommandBufferExecFuture<SwapchainAcquireFuture<NarwhalSurface>, AutoCommandBuffer>,
@@ -214,6 +544,12 @@ impl Presenter {
             }
         }
 
+        if self.submitted_at.take().is_some() {
+            // the time until now would mostly be the frame interval
+            self.last_frame = self.timings;
+        }
+        let acquire_start = Instant::now();
+
         let (index, acq) = match swapchain::acquire_next_image(Arc::clone(&self.swapchain), None) {
             Ok(v) => v,
             Err(AcquireError::OutOfDate) => {
@@ -225,46 +561,73 @@ impl Presenter {
             Err(e) => return Err(Error::from(e).into()),
         };
 
-        let surf_image = &self.chain_images[index];
-        let size = surf_image.dimensions();
+        let encode_start = Instant::now();
+        self.timings = FrameTimings {
+            acquire_latency: encode_start - acquire_start,
+            ..FrameTimings::default()
+        };
+
+        let size = self.chain_images[index].dimensions();
+
+        let tone_mapped;
+        let (tex, in_scale) = if self.tone_map_enabled() {
+            let (device, queue) = (Arc::clone(&self.device), Arc::clone(&self.queue));
+            let intermediates = self.intermediates(size);
+            if intermediates.tone_mapped.is_none() {
+                intermediates.tone_mapped = Some(Self::new_intermediate(&device, &queue, size)?);
+            }
+            let intermediate = Arc::clone(intermediates.tone_mapped.as_ref().unwrap());
+
+            cmd_buffer = self.tone_map.dispatch(
+                cmd_buffer,
+                tex,
+                &intermediate,
+                size,
+                self.tone_map_operator,
+                self.exposure,
+            )?;
+
+            tone_mapped = Texture::Storage(intermediate);
+            (&tone_mapped, [1., 1.])
+        } else {
+            (tex.color(), tex.uv_scale().into())
+        };
 
         if self.color_transform_enabled {
-            // TODO: don't recreate this every frame
-            let intermediate = StorageImage::with_usage(
-                Arc::clone(&self.device),
-                Dimensions::Dim2d {
-                    width: size[0],
-                    height: size[1],
-                },
-                COLOR_FORMAT,
-                ImageUsage {
-                    sampled: true,
-                    storage: true,
-                    ..ImageUsage::none()
-                },
-                Some(self.queue.family()),
-            )
-            .map_err(|e| Error::from(e))?;
+            let (device, queue) = (Arc::clone(&self.device), Arc::clone(&self.queue));
+            let intermediates = self.intermediates(size);
+            if intermediates.transformed.is_none() {
+                intermediates.transformed = Some(Self::new_intermediate(&device, &queue, size)?);
+            }
+            let intermediate = Arc::clone(intermediates.transformed.as_ref().unwrap());
 
             cmd_buffer = self
                 .color_transform
-                .dispatch(cmd_buffer, tex, &intermediate)?;
+                .dispatch(cmd_buffer, tex, in_scale, &intermediate)?;
 
             cmd_buffer = self.tex_renderer.render(
                 cmd_buffer,
                 &Texture::Storage(intermediate),
-                surf_image,
+                [1., 1.],
+                &self.chain_images[index],
             )?;
         } else {
-            cmd_buffer = self.tex_renderer.render(cmd_buffer, tex, surf_image)?;
+            cmd_buffer =
+                self.tex_renderer
+                    .render(cmd_buffer, tex, in_scale, &self.chain_images[index])?;
         }
 
         let cmd_buffer = cmd_buffer.build().map_err(|e| Error::from(e))?;
 
-        Ok(acq
+        let future = acq
             .then_execute(Arc::clone(&self.queue), cmd_buffer)
             .map_err(|e| Error::from(e))?
-            .then_swapchain_present(Arc::clone(&self.queue), Arc::clone(&self.swapchain), index))
+            .then_swapchain_present(Arc::clone(&self.queue), Arc::clone(&self.swapchain), index);
+
+        self.timings.encode_time = encode_start.elapsed();
+        self.submitted_at = Some(Instant::now());
+
+        Ok(future)
     }
 }
 
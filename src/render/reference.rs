@@ -0,0 +1,1041 @@
+//! Software reference renderer.
+//!
+//! Evaluates a graph entirely on the CPU with the same node semantics as the [Renderer], so that
+//! its output can serve as a reference for the GPU path in tests (see [Bitmap::max_difference]).
+//! It is slow and does not cache anything between renders.
+//!
+//! Only the composite, gaussian blur, and mask graphics nodes are supported; all data node types
+//! are supported.
+
+use crate::data::{Bitmap, Camera, Shape, Value};
+use crate::eval::*;
+use crate::node::defs::{
+    self, blur_passes, composite_schema, gaussian_schema, mask_mode, mask_schema, CameraProps,
+    CompositeProps, GaussianProps, MaskProps, COMPOSITE_NAME, GAUSSIAN_BLUR_NAME, MASK_NAME,
+};
+use crate::node::{Graph, NodeRef};
+use crate::render::fx::MaskMode;
+use crate::render::{Context, InternalRendererError, RenderError};
+use cgmath::{Matrix4, SquareMatrix, Vector2, Vector4};
+use fnv::FnvHashMap;
+use std::any::Any;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+const CAMERA_SCENE_INPUT_PROP: usize = CameraProps::In as usize;
+const CAMERA_DATA_OUTPUT_PROP: usize = CameraProps::Camera as usize;
+
+// see src/shaders/gaussian_blur.comp
+const GAUSSIAN_OFFSETS: [f32; 3] = [0.0, 1.3846153845, 3.2307692297];
+const GAUSSIAN_WEIGHTS: [f32; 3] = [0.2270270270, 0.3162162162, 0.0702702703];
+
+/// A CPU image, the software equivalent of a [TextureRef](crate::render::TextureRef).
+///
+/// Images are passed between nodes as [Value::Any].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Image {
+    /// Width in pixels.
+    pub width: u32,
+
+    /// Height in pixels.
+    pub height: u32,
+
+    /// Pixels per scene unit.
+    pub resolution: f32,
+
+    /// Transform used when compositing.
+    pub transform: Matrix4<f32>,
+
+    /// RGBA pixels with premultiplied alpha, row by row, starting at the top left.
+    pub pixels: Vec<[f32; 4]>,
+}
+
+impl Image {
+    /// Creates a new transparent image with the given size in scene units.
+    pub fn new(width: f32, height: f32, resolution: f32) -> Image {
+        Image::with_px_size(
+            (width * resolution) as u32,
+            (height * resolution) as u32,
+            resolution,
+        )
+    }
+
+    fn with_px_size(width: u32, height: u32, resolution: f32) -> Image {
+        Image {
+            width,
+            height,
+            resolution,
+            transform: Matrix4::identity(),
+            pixels: vec![[0.; 4]; width as usize * height as usize],
+        }
+    }
+
+    /// Returns the size in scene units.
+    pub fn size(&self) -> Vector2<f32> {
+        (
+            self.width as f32 / self.resolution,
+            self.height as f32 / self.resolution,
+        )
+            .into()
+    }
+
+    /// Returns a pixel, or transparent black outside the image.
+    fn pixel(&self, x: i64, y: i64) -> [f32; 4] {
+        if x < 0 || y < 0 || x >= self.width as i64 || y >= self.height as i64 {
+            return [0.; 4];
+        }
+        self.pixels[y as usize * self.width as usize + x as usize]
+    }
+
+    /// Samples with nearest filtering, clamping to a transparent border.
+    fn sample_nearest(&self, u: f32, v: f32) -> [f32; 4] {
+        let x = (u * self.width as f32).floor() as i64;
+        let y = (v * self.height as f32).floor() as i64;
+        self.pixel(x, y)
+    }
+
+    /// Samples with linear filtering, clamping to the edge.
+    fn sample_linear(&self, u: f32, v: f32) -> [f32; 4] {
+        if self.width == 0 || self.height == 0 {
+            return [0.; 4];
+        }
+        let x = u * self.width as f32 - 0.5;
+        let y = v * self.height as f32 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let clamp_x = |x: f32| (x as i64).max(0).min(self.width as i64 - 1);
+        let clamp_y = |y: f32| (y as i64).max(0).min(self.height as i64 - 1);
+        let a = self.pixel(clamp_x(x0), clamp_y(y0));
+        let b = self.pixel(clamp_x(x0 + 1.), clamp_y(y0));
+        let c = self.pixel(clamp_x(x0), clamp_y(y0 + 1.));
+        let d = self.pixel(clamp_x(x0 + 1.), clamp_y(y0 + 1.));
+        let mut out = [0.; 4];
+        for i in 0..4 {
+            let top = a[i] + (b[i] - a[i]) * fx;
+            let bottom = c[i] + (d[i] - c[i]) * fx;
+            out[i] = top + (bottom - top) * fy;
+        }
+        out
+    }
+
+    /// Blends a color over a pixel like the GPU pipelines do (source alpha for color, and one for
+    /// alpha).
+    fn blend(&mut self, x: u32, y: u32, src: [f32; 4]) {
+        let dst = &mut self.pixels[y as usize * self.width as usize + x as usize];
+        let a = src[3];
+        for i in 0..3 {
+            dst[i] = src[i] * a + dst[i] * (1. - a);
+        }
+        dst[3] = a + dst[3] * (1. - a);
+    }
+
+    /// Converts the image to a bitmap.
+    pub fn to_bitmap(&self) -> Bitmap {
+        Bitmap {
+            width: self.width,
+            height: self.height,
+            pixels: self
+                .pixels
+                .iter()
+                .flat_map(|pixel| pixel.iter().cloned())
+                .collect(),
+        }
+    }
+}
+
+fn image_value(image: Image) -> Value {
+    let image: Arc<dyn Any + Send + Sync> = Arc::new(image);
+    image.into()
+}
+
+/// A vertex in clip space with texture coordinates.
+#[derive(Debug, Clone, Copy)]
+struct ClipVertex {
+    pos: Vector4<f32>,
+    uv: Vector2<f32>,
+}
+
+impl ClipVertex {
+    fn lerp(self, other: ClipVertex, t: f32) -> ClipVertex {
+        ClipVertex {
+            pos: self.pos + (other.pos - self.pos) * t,
+            uv: self.uv + (other.uv - self.uv) * t,
+        }
+    }
+}
+
+/// Clips a polygon against the Vulkan depth range (0 ≤ z ≤ w).
+fn clip_depth(polygon: Vec<ClipVertex>) -> Vec<ClipVertex> {
+    let planes: [fn(&Vector4<f32>) -> f32; 2] = [|p| p.z, |p| p.w - p.z];
+    let mut polygon = polygon;
+    for plane in &planes {
+        let mut clipped = Vec::with_capacity(polygon.len() + 1);
+        for i in 0..polygon.len() {
+            let a = polygon[i];
+            let b = polygon[(i + 1) % polygon.len()];
+            let (da, db) = (plane(&a.pos), plane(&b.pos));
+            if da >= 0. {
+                clipped.push(a);
+            }
+            if (da >= 0.) != (db >= 0.) {
+                clipped.push(a.lerp(b, da / (da - db)));
+            }
+        }
+        polygon = clipped;
+    }
+    polygon
+}
+
+/// Rasterizes a triangle into the target, calling `shade` with the perspective-correct texture
+/// coordinates of every covered pixel center. Fragments for which `shade` returns None are
+/// discarded.
+fn draw_triangle<F>(target: &mut Image, triangle: [ClipVertex; 3], shade: &F)
+where
+    F: Fn(Vector2<f32>) -> Option<[f32; 4]>,
+{
+    let polygon = clip_depth(triangle.to_vec());
+    if polygon.len() < 3 || polygon.iter().any(|vert| vert.pos.w <= 0.) {
+        return;
+    }
+
+    let (width, height) = (target.width as f32, target.height as f32);
+    // (screen position, 1 / w, uv / w)
+    let screen: Vec<_> = polygon
+        .iter()
+        .map(|vert| {
+            let w = vert.pos.w;
+            let pos = Vector2::new(
+                (vert.pos.x / w + 1.) / 2. * width,
+                (vert.pos.y / w + 1.) / 2. * height,
+            );
+            (pos, 1. / w, vert.uv / w)
+        })
+        .collect();
+
+    let edge = |a: Vector2<f32>, b: Vector2<f32>, p: Vector2<f32>| {
+        (b.x - a.x) * (p.y - a.y) - (b.y - a.y) * (p.x - a.x)
+    };
+    // pixel centers exactly on an edge belong to only one of the triangles sharing it
+    let owns_edge = |a: Vector2<f32>, b: Vector2<f32>| {
+        let d = b - a;
+        d.y > 0. || (d.y == 0. && d.x < 0.)
+    };
+
+    for i in 1..screen.len() - 1 {
+        let mut tri = [screen[0], screen[i], screen[i + 1]];
+        let mut area = edge(tri[0].0, tri[1].0, tri[2].0);
+        if area == 0. || !area.is_finite() {
+            continue;
+        }
+        if area < 0. {
+            tri.swap(1, 2);
+            area = -area;
+        }
+
+        let min_x = tri.iter().map(|v| v.0.x).fold(f32::INFINITY, f32::min);
+        let max_x = tri.iter().map(|v| v.0.x).fold(f32::NEG_INFINITY, f32::max);
+        let min_y = tri.iter().map(|v| v.0.y).fold(f32::INFINITY, f32::min);
+        let max_y = tri.iter().map(|v| v.0.y).fold(f32::NEG_INFINITY, f32::max);
+        let x_range = (min_x.floor().max(0.) as u32, max_x.ceil().min(width) as u32);
+        let y_range = (
+            min_y.floor().max(0.) as u32,
+            max_y.ceil().min(height) as u32,
+        );
+
+        for y in y_range.0..y_range.1 {
+            for x in x_range.0..x_range.1 {
+                let p = Vector2::new(x as f32 + 0.5, y as f32 + 0.5);
+                let mut bary = [0.; 3];
+                let mut inside = true;
+                for j in 0..3 {
+                    let (a, b) = (tri[(j + 1) % 3].0, tri[(j + 2) % 3].0);
+                    let e = edge(a, b, p);
+                    if e < 0. || (e == 0. && !owns_edge(a, b)) {
+                        inside = false;
+                        break;
+                    }
+                    bary[j] = e / area;
+                }
+                if !inside {
+                    continue;
+                }
+
+                let inv_w: f32 = (0..3).map(|j| bary[j] * tri[j].1).sum();
+                let uv = (0..3)
+                    .map(|j| tri[j].2 * bary[j])
+                    .fold(Vector2::new(0., 0.), |acc, uv| acc + uv)
+                    / inv_w;
+                if let Some(color) = shade(uv) {
+                    target.blend(x, y, color);
+                }
+            }
+        }
+    }
+}
+
+/// Draws an indexed triangle mesh with a solid color.
+fn draw_mesh(
+    target: &mut Image,
+    matrix: Matrix4<f32>,
+    verts: &[Vector2<f32>],
+    indices: &[u16],
+    color: [f32; 4],
+) {
+    let clip: Vec<_> = verts
+        .iter()
+        .map(|vert| ClipVertex {
+            pos: matrix * Vector4::new(vert.x, vert.y, 0., 1.),
+            uv: Vector2::new(0., 0.),
+        })
+        .collect();
+    for triangle in indices.chunks(3) {
+        if triangle.len() < 3 {
+            break;
+        }
+        let triangle = [
+            clip[triangle[0] as usize],
+            clip[triangle[1] as usize],
+            clip[triangle[2] as usize],
+        ];
+        draw_triangle(target, triangle, &|_| Some(color));
+    }
+}
+
+/// Draws a shape like the [ShapeRasterizer](crate::render::ShapeRasterizer): the fill, then the
+/// stroke.
+fn draw_shape(target: &mut Image, shape: &Shape, camera: Matrix4<f32>) -> EvalResult<()> {
+    let matrix = camera * shape.transform.unwrap_or(Matrix4::identity());
+    if let (Some((verts, indices)), Some(color)) = (shape.fill_mesh()?, shape.fill) {
+        draw_mesh(target, matrix, &verts, &indices, color.into());
+    }
+    if let (Some((verts, indices)), Some((_, _, color))) = (shape.stroke_mesh(), &shape.stroke) {
+        draw_mesh(target, matrix, &verts, &indices, (*color).into());
+    }
+    Ok(())
+}
+
+/// Draws an image like the [TexCompositor](crate::render::TexCompositor).
+fn draw_image(target: &mut Image, image: &Image, camera: Matrix4<f32>) {
+    let matrix = camera * image.transform;
+    let size = image.size();
+    let vert = |x: f32, y: f32| ClipVertex {
+        pos: matrix * Vector4::new(x * size.x, y * size.y, 0., 1.),
+        uv: Vector2::new(x, y),
+    };
+    let quad = [vert(0., 0.), vert(1., 0.), vert(0., 1.), vert(1., 1.)];
+    let shade = |uv: Vector2<f32>| {
+        let color = image.sample_nearest(uv.x, uv.y);
+        if color[3] <= 0. {
+            None
+        } else {
+            Some(color)
+        }
+    };
+    draw_triangle(target, [quad[0], quad[1], quad[2]], &shade);
+    draw_triangle(target, [quad[2], quad[1], quad[3]], &shade);
+}
+
+fn eval_composite(input: &Input, context: Context, output: &mut Output) -> EvalResult<()> {
+    let camera = context.camera;
+    let mut target = Image::new(camera.width, camera.height, context.resolution);
+    let matrix = camera.matrix();
+
+    if let Ok(in_values) = input.get(CompositeProps::In) {
+        for value in in_values {
+            match &**value {
+                Value::Drawables(drawables) => {
+                    for drawable in drawables {
+                        draw_shape(&mut target, &drawable.shape, matrix)?;
+                    }
+                }
+                Value::Any(any) => match any.downcast_ref::<Image>() {
+                    Some(image) => draw_image(&mut target, image, matrix),
+                    None => return Err(EvalError::InputType(CompositeProps::In.into())),
+                },
+                _ => return Err(EvalError::InputType(CompositeProps::In.into())),
+            }
+        }
+    }
+
+    output.set(CompositeProps::Out, image_value(target));
+    Ok(())
+}
+
+fn eval_gaussian(input: &Input, context: Context, output: &mut Output) -> EvalResult<()> {
+    let radius = *input.one::<_, f64>(GaussianProps::Radius)? as f32 * context.resolution;
+    let image = input.one_any::<_, Image>(GaussianProps::In)?;
+
+    let passes = match blur_passes(radius) {
+        Some(passes) => passes,
+        None => {
+            output.set(
+                GaussianProps::Out,
+                (*input.get(GaussianProps::In)?[0]).clone(),
+            );
+            return Ok(());
+        }
+    };
+    let filter_size = radius * 2. / (9. * f32::from(passes));
+
+    let blur_pass = |src: &Image, vertical: bool| {
+        let mut out = Image::with_px_size(src.width, src.height, src.resolution);
+        let scale = Vector2::new(1. / src.width as f32, 1. / src.height as f32);
+        let dir = if vertical {
+            Vector2::new(0., scale.y)
+        } else {
+            Vector2::new(scale.x, 0.)
+        };
+        for y in 0..src.height {
+            for x in 0..src.width {
+                let pos = Vector2::new((x as f32 + 0.5) * scale.x, (y as f32 + 0.5) * scale.y);
+                let mut color = [0.; 4];
+                let mut add = |sample: [f32; 4], weight: f32| {
+                    for c in 0..4 {
+                        color[c] += sample[c] * weight;
+                    }
+                };
+                add(src.sample_linear(pos.x, pos.y), GAUSSIAN_WEIGHTS[0]);
+                for i in 1..GAUSSIAN_OFFSETS.len() {
+                    let offset = dir * GAUSSIAN_OFFSETS[i] * filter_size;
+                    let weight = GAUSSIAN_WEIGHTS[i];
+                    add(
+                        src.sample_linear(pos.x + offset.x, pos.y + offset.y),
+                        weight,
+                    );
+                    add(
+                        src.sample_linear(pos.x - offset.x, pos.y - offset.y),
+                        weight,
+                    );
+                }
+                out.pixels[(y * src.width + x) as usize] = color;
+            }
+        }
+        out
+    };
+
+    let mut result = blur_pass(&blur_pass(image, false), true);
+    for _ in 1..passes {
+        result = blur_pass(&blur_pass(&result, false), true);
+    }
+
+    output.set(GaussianProps::Out, image_value(result));
+    Ok(())
+}
+
+fn eval_mask(input: &Input, output: &mut Output) -> EvalResult<()> {
+    if input.get(MaskProps::Mask).is_err() {
+        // no mask input
+        output.set(MaskProps::Out, (*input.get(MaskProps::In)?[0]).clone());
+        return Ok(());
+    }
+
+    let image = input.one_any::<_, Image>(MaskProps::In)?;
+    let mask = input.one_any::<_, Image>(MaskProps::Mask)?;
+    let mode = mask_mode(input)?;
+
+    let mut out = Image::with_px_size(image.width, image.height, image.resolution);
+    for y in 0..out.height {
+        for x in 0..out.width {
+            let u = (x as f32 + 0.5) / out.width as f32;
+            let v = (y as f32 + 0.5) / out.height as f32;
+            let pixel = image.sample_nearest(u, v);
+            let m = mask.sample_nearest(u, v);
+            // premultiplied, so this is already weighted by alpha
+            let luma = (m[0] + m[1] + m[2]) / 3.;
+            let factor = match mode {
+                MaskMode::AlphaMatte => m[3],
+                MaskMode::LumaMatte => luma,
+                MaskMode::AlphaCutter => 1. - m[3],
+                MaskMode::LumaCutter => 1. - luma,
+            };
+            let out_pixel = &mut out.pixels[(y * out.width + x) as usize];
+            for (channel, value) in out_pixel.iter_mut().zip(&pixel) {
+                *channel = value * factor;
+            }
+        }
+    }
+
+    output.set(MaskProps::Out, image_value(out));
+    Ok(())
+}
+
+/// A software renderer that evaluates graphs on the CPU.
+///
+/// Unlike the [Renderer], which outputs textures, this outputs [Image]s.
+pub struct ReferenceRenderer {
+    data_types: HashMap<String, Box<dyn SharedDataType>>,
+    schemas: SchemaRegistry,
+    resolution: f32,
+    time: f64,
+    frame: u64,
+}
+
+impl ReferenceRenderer {
+    /// Creates a new reference renderer with all built-in data node types and the supported
+    /// graphics node types.
+    pub fn new() -> ReferenceRenderer {
+        let mut renderer = ReferenceRenderer {
+            data_types: HashMap::new(),
+            schemas: SchemaRegistry::new(),
+            resolution: 1.,
+            time: 0.,
+            frame: 0,
+        };
+
+        for type_def in defs::builtin() {
+            if let NodeTypeDef::Data(new) = type_def {
+                renderer.add_data_type(new());
+            }
+        }
+        for (name, schema) in vec![
+            (COMPOSITE_NAME, composite_schema()),
+            (GAUSSIAN_BLUR_NAME, gaussian_schema()),
+            (MASK_NAME, mask_schema()),
+        ] {
+            renderer.schemas.insert(name, schema);
+        }
+
+        renderer
+    }
+
+    /// Adds a data node type.
+    pub fn add_data_type(&mut self, data_type: Box<dyn SharedDataType>) {
+        self.schemas.insert(&data_type.name(), data_type.schema());
+        self.data_types.insert(data_type.name(), data_type);
+    }
+
+    /// Returns the schemas of all supported node types.
+    pub fn schemas(&self) -> &SchemaRegistry {
+        &self.schemas
+    }
+
+    /// Sets the rendering resolution.
+    pub fn set_resolution(&mut self, value: f32) {
+        self.resolution = value;
+    }
+
+    /// Sets the current time in seconds.
+    pub fn set_time(&mut self, time: f64) {
+        self.time = time;
+    }
+
+    /// Sets the current frame number.
+    pub fn set_frame(&mut self, frame: u64) {
+        self.frame = frame;
+    }
+
+    /// Renders the graph at the current time.
+    pub fn render(&mut self, graph: &Graph) -> Result<Image, RenderError> {
+        let mut graph = if graph.has_groups() {
+            graph.flatten()
+        } else {
+            graph.clone()
+        };
+        graph.animate(self.time);
+        graph.update_order()?;
+
+        let resolution = self.resolution;
+        let mut eval = Evaluation {
+            renderer: self,
+            graph: &graph,
+            cache: FnvHashMap::default(),
+            context: None,
+        };
+
+        let camera_ref = graph.output();
+        eval.eval_camera(camera_ref, true)?;
+
+        let camera = eval
+            .cache
+            .get(&camera_ref)
+            .and_then(|values| values.get(&CAMERA_DATA_OUTPUT_PROP))
+            .and_then(|value| match &**value {
+                Value::Any(any) => any.downcast_ref::<Camera>(),
+                _ => None,
+            })
+            .map(|camera| *camera)
+            .ok_or(RenderError::NoCameraData)?;
+        eval.context = Some(Context { camera, resolution });
+
+        for node_ref in graph.order().unwrap() {
+            if !eval.cache.contains_key(node_ref) {
+                eval.eval_one(*node_ref, None)?;
+            }
+        }
+
+        let inputs = eval
+            .node_inputs(camera_ref, None)
+            .map_err(|e| RenderError::Eval(camera_ref, e))?;
+        match inputs
+            .get(&CAMERA_SCENE_INPUT_PROP)
+            .and_then(|values| values.get(0))
+            .map(|value| &**value)
+        {
+            Some(Value::Any(any)) => match any.downcast_ref::<Image>() {
+                Some(image) => Ok(image.clone()),
+                None => Err(RenderError::NoScene),
+            },
+            _ => Err(RenderError::NoScene),
+        }
+    }
+
+    /// Renders the graph at the current time into a bitmap.
+    pub fn render_to_bitmap(&mut self, graph: &Graph) -> Result<Bitmap, RenderError> {
+        Ok(self.render(graph)?.to_bitmap())
+    }
+}
+
+/// State of a single render.
+struct Evaluation<'a> {
+    renderer: &'a mut ReferenceRenderer,
+    graph: &'a Graph,
+    cache: FnvHashMap<NodeRef, FnvHashMap<usize, Arc<Value>>>,
+    /// Graphics node context; only available once the camera has been evaluated.
+    context: Option<Context>,
+}
+
+impl<'a> Evaluation<'a> {
+    /// Evaluates the camera node and its data inputs, recursively.
+    fn eval_camera(&mut self, node_ref: NodeRef, is_camera: bool) -> Result<(), RenderError> {
+        let mut input_nodes = Vec::new();
+        for (input, _, in_prop) in self.graph.node_inputs(node_ref) {
+            if is_camera && in_prop == CAMERA_SCENE_INPUT_PROP {
+                continue;
+            }
+            if !input_nodes.contains(&input) {
+                input_nodes.push(input);
+            }
+        }
+
+        for node in input_nodes {
+            if !self.cache.contains_key(&node) {
+                self.eval_camera(node, false)?;
+            }
+        }
+
+        self.eval_one(
+            node_ref,
+            if is_camera {
+                Some(CAMERA_SCENE_INPUT_PROP)
+            } else {
+                None
+            },
+        )
+    }
+
+    /// Evaluates a single node and caches its outputs, like [Renderer] does.
+    fn eval_one(
+        &mut self,
+        node_ref: NodeRef,
+        ignore_prop: Option<usize>,
+    ) -> Result<(), RenderError> {
+        let graph = self.graph;
+        let node = graph
+            .node(&node_ref)
+            .ok_or(InternalRendererError::MissingNode(node_ref))?;
+        let input = Input {
+            values: self
+                .node_inputs(node_ref, ignore_prop)
+                .map_err(|e| RenderError::Eval(node_ref, e))?,
+            node: node_ref,
+            context: EvalContext {
+                time: self.renderer.time,
+                frame: self.renderer.frame,
+                delta: 0.,
+                resolution: self.renderer.resolution,
+            },
+        };
+        let mut output = Output {
+            values: FnvHashMap::default(),
+        };
+
+        // the camera itself can not be bypassed
+        if !node.enabled && ignore_prop.is_none() {
+            if let Some(schema) = self.renderer.schemas.get(&node.node_type) {
+                for (input_prop, output_prop) in &schema.bypass {
+                    if let Some(value) = input.values.get(input_prop).and_then(|v| v.get(0)) {
+                        output.values.insert(*output_prop, Arc::clone(value));
+                    }
+                }
+            }
+        } else if let Some(data_type) = self.renderer.data_types.get_mut(&node.node_type) {
+            data_type
+                .create()
+                .eval(input, &mut output)
+                .map_err(|e| RenderError::Eval(node_ref, e))?;
+        } else {
+            let context = match self.context {
+                Some(context) => context,
+                None if self.renderer.schemas.contains(&node.node_type) => {
+                    return Err(RenderError::NonDataCameraInput(node_ref));
+                }
+                None => return Err(RenderError::MissingNodeType(node.node_type.clone())),
+            };
+            let result = match &*node.node_type {
+                COMPOSITE_NAME => eval_composite(&input, context, &mut output),
+                GAUSSIAN_BLUR_NAME => eval_gaussian(&input, context, &mut output),
+                MASK_NAME => eval_mask(&input, &mut output),
+                _ => return Err(RenderError::MissingNodeType(node.node_type.clone())),
+            };
+            result.map_err(|e| RenderError::Eval(node_ref, e))?;
+        }
+
+        self.cache.insert(node_ref, output.values);
+        Ok(())
+    }
+
+    /// Returns all node inputs: linked outputs, then property values, then schema defaults.
+    fn node_inputs(
+        &self,
+        node_ref: NodeRef,
+        ignore_prop: Option<usize>,
+    ) -> Result<FnvHashMap<usize, Vec<Arc<Value>>>, EvalError> {
+        let mut inputs = FnvHashMap::default();
+
+        for (input, out_prop, in_prop) in self.graph.node_inputs(node_ref) {
+            if Some(in_prop) == ignore_prop {
+                continue;
+            }
+
+            let values = self
+                .cache
+                .get(&input)
+                .ok_or(EvalError::MissingInput(in_prop))?;
+            if let Some(value) = values.get(&out_prop) {
+                inputs
+                    .entry(in_prop)
+                    .or_insert_with(|| Vec::new())
+                    .push(Arc::clone(value));
+            } else if self.graph.node(&input).map_or(true, |node| node.enabled) {
+                return Err(EvalError::MissingInput(in_prop));
+            }
+        }
+
+        if let Some(node) = self.graph.node(&node_ref) {
+            for (k, v) in self.graph.property_values(&node_ref) {
+                inputs.entry(k).or_insert_with(|| vec![Arc::new(v.clone())]);
+            }
+
+            if let Some(schema) = self.renderer.schemas.get(&node.node_type) {
+                for port in schema.inputs() {
+                    if let Some(default) = &port.default {
+                        inputs
+                            .entry(port.index)
+                            .or_insert_with(|| vec![Arc::new(default.clone())]);
+                    }
+                }
+            }
+        }
+
+        Ok(inputs)
+    }
+}
+
+/// Creates a closed rectangular path.
+#[cfg(test)]
+fn rect_path(min: (f64, f64), max: (f64, f64)) -> crate::data::Path2D {
+    use crate::data::Path2DCmd;
+
+    vec![
+        Path2DCmd::JumpTo(min.into()),
+        Path2DCmd::LineTo((max.0, min.1).into()),
+        Path2DCmd::LineTo(max.into()),
+        Path2DCmd::LineTo((min.0, max.1).into()),
+        Path2DCmd::CloseShape,
+    ]
+    .into()
+}
+
+/// Creates a graph that renders a blurred layer masked by another layer, with a camera that shows
+/// about 14.5 × 11 units around the origin.
+///
+/// Returns the graph along with the blur and mask nodes.
+#[cfg(test)]
+fn blur_and_mask_shapes(
+    shapes: Vec<Shape>,
+    matte: Vec<Shape>,
+    radius: f64,
+    mode: f64,
+) -> (Graph, NodeRef, NodeRef) {
+    use crate::data::Drawable;
+    use crate::node::Node;
+    use cgmath::Vector3;
+    use defs::CAMERA_NAME;
+
+    // a composite node with the shapes as its input
+    let layer = |graph: &mut Graph, shapes: Vec<Shape>| {
+        let node = graph.add_node(Node::empty(COMPOSITE_NAME.into()));
+        let drawables: Vec<_> = shapes
+            .into_iter()
+            .enumerate()
+            .map(|(i, shape)| Drawable {
+                id: (node, i as u64),
+                shape,
+            })
+            .collect();
+        graph
+            .node_mut(&node)
+            .unwrap()
+            .set(CompositeProps::In.into(), drawables);
+        node
+    };
+
+    let mut graph = Graph::new();
+    let camera = graph.add_node(Node::empty(CAMERA_NAME.into()));
+    {
+        let camera = graph.node_mut(&camera).unwrap();
+        camera.set(CameraProps::Size.into(), Vector2::new(8., 6.));
+        camera.set(CameraProps::Offset.into(), Vector2::new(0., 0.));
+        camera.set(
+            CameraProps::Transform.into(),
+            Matrix4::<f64>::from_translation(Vector3::new(0., 0., 10.)),
+        );
+        camera.set(CameraProps::Fov.into(), 1.);
+        camera.set(CameraProps::ClipNear.into(), 0.1);
+        camera.set(CameraProps::ClipFar.into(), 100.);
+    }
+    graph.set_output(camera);
+
+    let layer_node = layer(&mut graph, shapes);
+    let blur = graph.add_node(Node::empty(GAUSSIAN_BLUR_NAME.into()));
+    graph
+        .node_mut(&blur)
+        .unwrap()
+        .set(GaussianProps::Radius.into(), radius);
+    let matte = layer(&mut graph, matte);
+    let mask = graph.add_node(Node::empty(MASK_NAME.into()));
+    graph
+        .node_mut(&mask)
+        .unwrap()
+        .set(MaskProps::Mode.into(), mode);
+
+    graph.link(
+        layer_node,
+        CompositeProps::Out.into(),
+        blur,
+        GaussianProps::In.into(),
+    );
+    graph.link(blur, GaussianProps::Out.into(), mask, MaskProps::In.into());
+    graph.link(
+        matte,
+        CompositeProps::Out.into(),
+        mask,
+        MaskProps::Mask.into(),
+    );
+    graph.link(mask, MaskProps::Out.into(), camera, CameraProps::In.into());
+
+    (graph, blur, mask)
+}
+
+/// Creates a graph that renders a blurred square masked by another square, both of which cover
+/// the entire frame, so every pixel of the output has the same value.
+///
+/// Returns the graph along with the blur and mask nodes.
+#[cfg(test)]
+fn blur_and_mask_graph(
+    color: crate::data::Color,
+    matte: crate::data::Color,
+    mode: f64,
+) -> (Graph, NodeRef, NodeRef) {
+    let square = |color| Shape {
+        path: rect_path((-1000., -1000.), (1000., 1000.)),
+        stroke: None,
+        fill: Some(color),
+        transform: None,
+    };
+    blur_and_mask_shapes(vec![square(color)], vec![square(matte)], 2., mode)
+}
+
+/// Creates a graph that renders a filled square and an overlapping stroked rectangle with edges
+/// inside the frame, slightly blurred and masked by a layer that covers the right half (x ≥ 0).
+#[cfg(test)]
+fn edges_graph(mode: f64) -> Graph {
+    use crate::data::{Color, StrokeWeight};
+
+    let shapes = vec![
+        Shape {
+            path: rect_path((-4., -3.), (2., 2.)),
+            stroke: None,
+            fill: Some(Color::from([1., 0.5, 0.25, 1.])),
+            transform: None,
+        },
+        Shape {
+            path: rect_path((-1., -1.), (4., 3.)),
+            stroke: Some((
+                StrokeWeight::constant(),
+                0.5,
+                Color::from([0.25, 0.5, 1., 1.]),
+            )),
+            fill: None,
+            transform: None,
+        },
+    ];
+    let matte = vec![Shape {
+        path: rect_path((0., -1000.), (1000., 1000.)),
+        stroke: None,
+        fill: Some(Color::from([0.5, 0.25, 1., 0.75])),
+        transform: None,
+    }];
+    blur_and_mask_shapes(shapes, matte, 0.5, mode).0
+}
+
+/// Asserts that all pixels of an image have the expected value.
+#[cfg(test)]
+fn assert_uniform(image: &Image, expected: [f32; 4]) {
+    for pixel in &image.pixels {
+        for (value, expected) in pixel.iter().zip(&expected) {
+            assert!((value - expected).abs() < 1e-4, "{:?}", pixel);
+        }
+    }
+}
+
+#[test]
+fn blur_and_mask() {
+    use crate::data::Color;
+
+    let (mut graph, _, mask) = blur_and_mask_graph(
+        Color::from([1., 0.5, 0.25, 1.]),
+        Color::from([1., 1., 1., 0.5]),
+        0.,
+    );
+
+    let mut renderer = ReferenceRenderer::new();
+    renderer.set_resolution(2.);
+    let image = renderer.render(&graph).unwrap();
+    assert_eq!((image.width, image.height), (16, 12));
+    assert_uniform(&image, [0.5, 0.25, 0.125, 0.5]);
+
+    graph
+        .node_mut(&mask)
+        .unwrap()
+        .set(MaskProps::Mode.into(), 7.);
+    match renderer.render(&graph) {
+        Err(RenderError::Eval(node, EvalError::InputType(_))) => assert_eq!(node, mask),
+        res => panic!("unexpected result {:?}", res.map(|_| ())),
+    }
+}
+
+#[test]
+fn bypass() {
+    use crate::data::Color;
+
+    let (mut graph, blur, mask) = blur_and_mask_graph(
+        Color::from([1., 0.5, 0.25, 1.]),
+        Color::from([1., 1., 1., 0.5]),
+        0.,
+    );
+
+    let mut renderer = ReferenceRenderer::new();
+    renderer.set_resolution(2.);
+    assert_uniform(&renderer.render(&graph).unwrap(), [0.5, 0.25, 0.125, 0.5]);
+
+    // the bypassed mask passes the blurred layer through
+    graph.node_mut(&mask).unwrap().enabled = false;
+    assert_uniform(&renderer.render(&graph).unwrap(), [1., 0.5, 0.25, 1.]);
+
+    graph.node_mut(&blur).unwrap().enabled = false;
+    assert_uniform(&renderer.render(&graph).unwrap(), [1., 0.5, 0.25, 1.]);
+
+    graph.node_mut(&mask).unwrap().enabled = true;
+    assert_uniform(&renderer.render(&graph).unwrap(), [0.5, 0.25, 0.125, 0.5]);
+}
+
+#[test]
+fn luma_masks() {
+    use crate::data::Color;
+
+    // premultiplied (0.375, 0.1875, 0.75, 0.75) with a luma of 0.4375
+    let matte = Color::from([0.5, 0.25, 1., 0.75]);
+    let mut renderer = ReferenceRenderer::new();
+    renderer.set_resolution(2.);
+
+    let (graph, _, _) = blur_and_mask_graph(
+        Color::from([1., 0.5, 0.25, 1.]),
+        matte,
+        MaskMode::LumaMatte as i32 as f64,
+    );
+    assert_uniform(
+        &renderer.render(&graph).unwrap(),
+        [0.4375, 0.21875, 0.109375, 0.4375],
+    );
+
+    let (graph, _, _) = blur_and_mask_graph(
+        Color::from([1., 0.5, 0.25, 1.]),
+        matte,
+        MaskMode::LumaCutter as i32 as f64,
+    );
+    assert_uniform(
+        &renderer.render(&graph).unwrap(),
+        [0.5625, 0.28125, 0.140625, 0.5625],
+    );
+}
+
+#[test]
+fn edges() {
+    let mut renderer = ReferenceRenderer::new();
+    renderer.set_resolution(2.);
+    let image = renderer
+        .render(&edges_graph(MaskMode::AlphaMatte as i32 as f64))
+        .unwrap();
+    assert_eq!((image.width, image.height), (16, 12));
+
+    // only the right half is covered by the matte
+    let half = image.width / 2;
+    for y in 0..image.height {
+        for x in 0..half {
+            let pixel = image.pixels[(y * image.width + x) as usize];
+            assert_eq!(pixel, [0.; 4], "({}, {})", x, y);
+        }
+    }
+    let right_alphas: Vec<_> = image
+        .pixels
+        .chunks(image.width as usize)
+        .flat_map(|row| row[half as usize..].iter().map(|pixel| pixel[3]))
+        .collect();
+    assert!(right_alphas.iter().any(|alpha| *alpha == 0.));
+    assert!(right_alphas.iter().any(|alpha| *alpha > 0.25));
+}
+
+/// Compares the [Renderer] with the reference renderer.
+///
+/// This needs a Vulkan device, so it only runs with `cargo test -- --ignored`, and fails if there
+/// is no device.
+#[test]
+#[ignore]
+fn matches_renderer() {
+    use crate::data::Color;
+    use crate::render::{offscreen_device, offscreen_instance, Renderer};
+
+    let instance = offscreen_instance().expect("failed to create a Vulkan instance");
+    let (device, queue) = offscreen_device(&instance).expect("no Vulkan device");
+    let mut renderer = Renderer::new(Graph::new(), device, queue).unwrap();
+    for node_type in defs::builtin() {
+        renderer.add_node_type(node_type).unwrap();
+    }
+    renderer.set_resolution(2.);
+    let mut reference = ReferenceRenderer::new();
+    reference.set_resolution(2.);
+
+    for mode in 0..4 {
+        let (uniform, _, _) = blur_and_mask_graph(
+            Color::from([1., 0.5, 0.25, 1.]),
+            Color::from([0.5, 0.25, 1., 0.75]),
+            mode as f64,
+        );
+        for (name, graph) in vec![("uniform", uniform), ("edges", edges_graph(mode as f64))] {
+            let expected = reference.render_to_bitmap(&graph).unwrap();
+            *renderer.graph_mut() = graph;
+            let bitmap = renderer.render_to_bitmap().unwrap();
+
+            // the renderer outputs half floats
+            let difference = bitmap.max_difference(&expected).unwrap();
+            assert!(
+                difference < 1e-2,
+                "{}, mode {}: difference {}",
+                name,
+                mode,
+                difference
+            );
+        }
+    }
+}
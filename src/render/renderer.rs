@@ -1,21 +1,28 @@
 use crate::data::{Camera, Drawable, Value};
 use crate::eval::*;
-use crate::node::{Graph, NodeRef, OrderError};
+use crate::node::defs::CameraProps;
+use crate::node::{
+    DebugOverlay, Graph, GraphEvent, GraphId, GraphProblem, LinkError, NodeRef, OrderError,
+    SubscriberId, GROUP_DEF_PROP,
+};
 use crate::render::{
-    Context, ShapeRasterizer, TexturePool, TextureRef, COLOR_FORMAT, DEPTH_FORMAT,
+    Context, NodeStats, NodeTextures, RenderReport, ShapeRasterizer, TextureDeps, TexturePool,
+    TextureRef, TextureUsage, COLOR_FORMAT, DEPTH_FORMAT,
 };
 use failure::Error;
 use fnv::{FnvHashMap, FnvHashSet};
 use std::collections::HashMap;
+use std::mem;
 use std::sync::Arc;
+use std::time::Instant;
 use vulkano::command_buffer::{AutoCommandBufferBuilder, DynamicState};
 use vulkano::device::{Device, Queue};
 use vulkano::framebuffer::{Framebuffer, RenderPassAbstract};
 use vulkano::pipeline::viewport::{Scissor, Viewport};
 use vulkano::OomError;
 
-const CAMERA_SCENE_INPUT_PROP: usize = 0;
-const CAMERA_DATA_OUTPUT_PROP: usize = 0;
+const CAMERA_SCENE_INPUT_PROP: usize = CameraProps::In as usize;
+const CAMERA_DATA_OUTPUT_PROP: usize = CameraProps::Camera as usize;
 
 /// A node type instance.
 pub enum NodeType {
@@ -37,6 +44,20 @@ impl NodeType {
             NodeType::Graphics(graphics) => graphics.clear_caches(),
         }
     }
+
+    fn schema(&self) -> NodeSchema {
+        match self {
+            NodeType::Data(data) => data.schema(),
+            NodeType::Graphics(graphics) => graphics.schema(),
+        }
+    }
+
+    fn is_graphics(&self) -> bool {
+        match self {
+            NodeType::Data(_) => false,
+            NodeType::Graphics(_) => true,
+        }
+    }
 }
 
 enum NodeInstance {
@@ -61,7 +82,7 @@ impl NodeInstance {
 
     fn is_impure(&self) -> bool {
         match self {
-            NodeInstance::Graphics(_) => false,
+            NodeInstance::Graphics(graphics) => !graphics.is_pure(),
             NodeInstance::Data(data) => !data.is_pure(),
         }
     }
@@ -121,11 +142,11 @@ pub enum RenderError {
     #[fail(display = "failed to topologically sort the graph: {}", _0)]
     OrderError(OrderError),
 
-    /// The camera node did not output a [`Camera`] struct at output port 0.
+    /// The camera node did not output a [`Camera`] struct at its camera output port.
     #[fail(display = "no camera data")]
     NoCameraData,
 
-    /// The camera node did not receive a [`TextureRef`] value at input port 0.
+    /// The camera node did not receive a [`TextureRef`] value at its scene input port.
     #[fail(display = "no scene")]
     NoScene,
 }
@@ -157,19 +178,32 @@ pub struct Renderer {
     shape_rasterizer: ShapeRasterizer<(NodeRef, u64)>,
     shape_render_pass: Arc<dyn RenderPassAbstract + Send + Sync>,
     texture_pool: TexturePool,
+    node_textures: FnvHashMap<NodeRef, NodeTextures>,
     ctx_cache: FnvHashMap<NodeRef, Context>,
     cache: FnvHashMap<NodeRef, FnvHashMap<usize, Arc<Value>>>,
+    stale: FnvHashMap<NodeRef, FnvHashMap<usize, Arc<Value>>>,
     node_types: HashMap<String, NodeType>,
+    schemas: SchemaRegistry,
     nodes: FnvHashMap<NodeRef, NodeInstance>,
     resolution: f32,
+    time: f64,
+    frame: u64,
+    prev_time: Option<f64>,
+    report: Option<RenderReport>,
     cycle: u8,
+    flat: Option<(Graph, HashMap<NodeRef, Vec<NodeRef>>)>,
+    subscriber: (GraphId, SubscriberId),
     device: Arc<Device>,
     queue: Arc<Queue>,
 }
 
 impl Renderer {
     /// Creates a new renderer.
-    pub fn new(graph: Graph, device: Arc<Device>, queue: Arc<Queue>) -> Result<Renderer, Error> {
+    pub fn new(
+        mut graph: Graph,
+        device: Arc<Device>,
+        queue: Arc<Queue>,
+    ) -> Result<Renderer, Error> {
         let shape_render_pass: Arc<dyn RenderPassAbstract + Send + Sync> =
             Arc::new(single_pass_renderpass! {
                 Arc::clone(&device),
@@ -193,17 +227,28 @@ impl Renderer {
                 }
             }?);
 
+        let subscriber = (graph.id(), graph.subscribe());
+
         Ok(Renderer {
             graph,
             shape_rasterizer: ShapeRasterizer::new(Arc::clone(&device), &shape_render_pass, 0)?,
             shape_render_pass,
             cache: FnvHashMap::default(),
+            stale: FnvHashMap::default(),
             ctx_cache: FnvHashMap::default(),
             node_types: HashMap::new(),
+            schemas: SchemaRegistry::new(),
             nodes: FnvHashMap::default(),
             texture_pool: TexturePool::new(Arc::clone(&device), Arc::clone(&queue)),
+            node_textures: FnvHashMap::default(),
             resolution: 1.,
+            time: 0.,
+            frame: 0,
+            prev_time: None,
+            report: None,
             cycle: 0,
+            flat: None,
+            subscriber,
             device,
             queue,
         })
@@ -215,6 +260,7 @@ impl Renderer {
             NodeTypeDef::Data(new) => NodeType::Data(new()),
             NodeTypeDef::Graphics(new) => NodeType::Graphics(new(&self.device, &self.queue)?),
         };
+        self.schemas.insert(&node_type.name(), node_type.schema());
         self.node_types.insert(node_type.name(), node_type);
         Ok(())
     }
@@ -225,6 +271,7 @@ impl Renderer {
         gen: F,
     ) -> Result<(), Error> {
         let node_type = gen(&self.device, &self.queue)?;
+        self.schemas.insert(&node_type.name(), node_type.schema());
         self.node_types.insert(node_type.name(), node_type);
         Ok(())
     }
@@ -234,6 +281,173 @@ impl Renderer {
         &mut self.node_types
     }
 
+    /// Returns the port schema of a node type, if the node type exists.
+    pub fn schema(&self, node_type: &str) -> Option<&NodeSchema> {
+        self.schemas.get(node_type)
+    }
+
+    /// Returns the schemas of all loaded node types, e.g. to refer to properties by name (see
+    /// [crate::node::Node::set_named]).
+    pub fn schemas(&self) -> &SchemaRegistry {
+        &self.schemas
+    }
+
+    /// Checks whether two node properties may be linked.
+    ///
+    /// Links between nodes whose types don’t publish a schema are always allowed. Drawables are
+    /// accepted by texture inputs on graphics nodes, since they will be rasterized.
+    pub fn check_link(
+        &self,
+        out_node: NodeRef,
+        out_prop: usize,
+        in_node: NodeRef,
+        in_prop: usize,
+    ) -> Result<(), LinkError> {
+        let node_type = |node_ref: NodeRef| match self.graph.node(&node_ref) {
+            Some(node) if node.group_def().is_some() => Ok(None),
+            Some(node) => match self.node_types.get(&node.node_type) {
+                Some(node_type) => Ok(Some((node_type, self.schemas.get(&node.node_type)))),
+                None => Err(LinkError::MissingNodeType(node.node_type.clone())),
+            },
+            None => Err(LinkError::MissingNode(node_ref)),
+        };
+
+        let out_type = node_type(out_node)?;
+        let in_type = node_type(in_node)?;
+
+        self.graph
+            .check_link(&self.schemas, out_node, out_prop, in_node, in_prop)?;
+
+        // the graph assumes that drawables are rasterized, but only graphics nodes do that
+        if let (Some((_, Some(out_schema))), Some((in_type, Some(in_schema)))) = (out_type, in_type)
+        {
+            if let (Some(out_port), Some(in_port)) =
+                (out_schema.output(out_prop), in_schema.input(in_prop))
+            {
+                if !out_port.can_link_to(in_port, in_type.is_graphics()) {
+                    return Err(LinkError::TypeMismatch(
+                        out_port.types.clone(),
+                        in_port.types.clone(),
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Validates and links two node properties.
+    pub fn link(
+        &mut self,
+        out_node: NodeRef,
+        out_prop: usize,
+        in_node: NodeRef,
+        in_prop: usize,
+    ) -> Result<(), LinkError> {
+        self.check_link(out_node, out_prop, in_node, in_prop)?;
+        self.graph.link(out_node, out_prop, in_node, in_prop);
+        Ok(())
+    }
+
+    /// Validates all links in the graph and returns every invalid link along with the reason.
+    pub fn check_links(&self) -> Vec<((NodeRef, usize), (NodeRef, usize), LinkError)> {
+        self.graph
+            .iter_links()
+            .filter_map(
+                |(out, input)| match self.check_link(out.0, out.1, input.0, input.1) {
+                    Ok(()) => None,
+                    Err(err) => Some((out, input, err)),
+                },
+            )
+            .collect()
+    }
+
+    /// Validates the graph and returns all problems found.
+    ///
+    /// In addition to the structural problems found by [Graph::validate], this finds nodes with
+    /// unknown types, invalid links, and non-data nodes used as camera inputs.
+    pub fn validate(&self) -> Vec<GraphProblem> {
+        let mut problems = self.graph.validate(&self.schemas);
+
+        for (node_ref, node) in self.graph.iter_nodes() {
+            if node.group_def().is_none() && !self.node_types.contains_key(&node.node_type) {
+                problems.push(GraphProblem::UnknownNodeType(
+                    *node_ref,
+                    node.node_type.clone(),
+                ));
+            }
+        }
+
+        for ((out_node, out_prop), (in_node, in_prop)) in self.graph.iter_links() {
+            let problem = match self.check_link(out_node, out_prop, in_node, in_prop) {
+                Ok(()) => continue,
+                // already reported above
+                Err(LinkError::MissingNode(_)) | Err(LinkError::MissingNodeType(_)) => continue,
+                Err(LinkError::NoSuchOutput(..)) | Err(LinkError::NoSuchInput(..)) => {
+                    GraphProblem::NoSuchPort(out_node, out_prop, in_node, in_prop)
+                }
+                Err(LinkError::TypeMismatch(..)) => {
+                    GraphProblem::TypeMismatch(out_node, out_prop, in_node, in_prop)
+                }
+                Err(LinkError::InputOccupied(node, prop)) => {
+                    GraphProblem::InputOccupied(node, prop)
+                }
+            };
+            if !problems.contains(&problem) {
+                problems.push(problem);
+            }
+        }
+
+        // camera inputs must be data nodes
+        let camera_ref = self.graph.output();
+        let mut visited = FnvHashSet::default();
+        let mut stack: Vec<_> = self
+            .graph
+            .node_inputs(camera_ref)
+            .filter(|(_, _, in_prop)| *in_prop != CAMERA_SCENE_INPUT_PROP)
+            .map(|(node, ..)| node)
+            .collect();
+        while let Some(node_ref) = stack.pop() {
+            if node_ref == camera_ref || !visited.insert(node_ref) {
+                continue;
+            }
+            let is_graphics = self
+                .graph
+                .node(&node_ref)
+                .and_then(|node| self.node_types.get(&node.node_type))
+                .map_or(false, |node_type| node_type.is_graphics());
+            if is_graphics {
+                problems.push(GraphProblem::NonDataCameraInput(node_ref));
+            }
+            stack.extend(self.graph.node_inputs(node_ref).map(|(node, ..)| node));
+        }
+
+        problems
+    }
+
+    /// Returns the dirty and cached state of all nodes for debug exports (see [Graph::to_dot]).
+    pub fn debug_overlay(&self) -> DebugOverlay {
+        DebugOverlay {
+            dirty: self
+                .graph
+                .iter_nodes()
+                .map(|(k, _)| *k)
+                .filter(|k| self.graph.is_dirty(k))
+                .collect(),
+            cached: self.cache.keys().map(|k| *k).collect(),
+        }
+    }
+
+    /// Returns the device used for rendering.
+    pub fn device(&self) -> &Arc<Device> {
+        &self.device
+    }
+
+    /// Returns the queue used for rendering.
+    pub fn queue(&self) -> &Arc<Queue> {
+        &self.queue
+    }
+
     /// Returns a reference to the graph.
     pub fn graph(&self) -> &Graph {
         &self.graph
@@ -254,28 +468,128 @@ impl Renderer {
         self.resolution = value;
     }
 
-    /// Propagates cache invalidation through the graph starting from the given node’s outputs.
-    /// This should be called if a node’s outputs have changed and all subsequent nodes
-    /// must thus be re-evaluated.
+    /// Returns the current time in seconds.
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
+    /// Sets the current time in seconds. Animated properties will be updated on the next render,
+    /// and only nodes whose values change will be re-evaluated.
+    pub fn set_time(&mut self, time: f64) {
+        self.time = time;
+    }
+
+    /// Returns the current frame number, which is incremented after every render.
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    /// Sets the current frame number.
+    pub fn set_frame(&mut self, frame: u64) {
+        self.frame = frame;
+    }
+
+    /// Enables or disables collection of per-node statistics (see [Renderer::report]).
+    pub fn set_profiling(&mut self, enabled: bool) {
+        self.report = if enabled {
+            Some(RenderReport::default())
+        } else {
+            None
+        };
+    }
+
+    /// Returns per-node statistics for the last render, if profiling is enabled.
+    pub fn report(&self) -> Option<&RenderReport> {
+        self.report.as_ref()
+    }
+
+    /// Sets a video memory budget for pooled textures in bytes, or removes it.
+    ///
+    /// Free textures are evicted from the pool, least recently used first, to stay within the
+    /// budget. Textures that are in use are never evicted, so usage may still exceed it.
+    pub fn set_texture_budget(&mut self, budget: Option<u64>) {
+        self.texture_pool.set_budget(budget);
+    }
+
+    /// Returns the current (estimated) video memory usage of pooled textures.
+    pub fn texture_usage(&self) -> TextureUsage {
+        self.texture_pool.usage()
+    }
+
+    /// Records statistics for an evaluated node, if profiling is enabled.
+    ///
+    /// `allocations` is the texture pool allocation count before evaluation started.
+    fn record_stats(&mut self, node: NodeRef, start: Instant, allocations: u64, vertices: usize) {
+        if let Some(report) = &mut self.report {
+            report.insert(NodeStats {
+                cpu_time: start.elapsed(),
+                textures_allocated: self.texture_pool.allocation_count() - allocations,
+                vertices_tessellated: vertices,
+                ..NodeStats::new(node)
+            });
+        }
+    }
+
+    /// Records a node whose cached outputs were used, if profiling is enabled and the node has
+    /// not been evaluated already (such as camera inputs).
+    fn record_cached(&mut self, node: NodeRef) {
+        if let Some(report) = &mut self.report {
+            report.insert(NodeStats {
+                cached: true,
+                ..NodeStats::new(node)
+            });
+        }
+    }
+
+    /// Returns the evaluation context passed to data nodes.
+    fn eval_context(&self) -> EvalContext {
+        EvalContext {
+            time: self.time,
+            frame: self.frame,
+            delta: self.prev_time.map_or(0., |prev_time| self.time - prev_time),
+            resolution: self.resolution,
+        }
+    }
+
+    /// Invalidates the cached outputs of the given node so that it will be re-evaluated. The
+    /// outputs are kept around to find out whether they actually changed (see
+    /// [Renderer::set_cache]).
+    fn invalidate_cache(&mut self, node: NodeRef) {
+        if let Some(outputs) = self.cache.remove(&node) {
+            self.stale.insert(node, outputs);
+        }
+    }
+
+    /// Invalidates the caches of all nodes connected to the given node’s outputs.
+    /// This should be called if a node’s outputs have changed and the nodes using them must thus
+    /// be re-evaluated. Nodes further down are only invalidated if those outputs change in turn.
     fn propagate_cache_invalidation(&mut self, node: NodeRef) {
         for (node, ..) in self.graph.node_outputs(node).collect::<Vec<_>>() {
-            self.cache.remove(&node);
-            self.propagate_cache_invalidation(node);
+            self.invalidate_cache(node);
         }
     }
 
     /// Updates the output cache for the given node. If the outputs are different from the previous
-    /// values, all subsequent nodes’ caches will be invalidated.
+    /// values, the caches of nodes using them will be invalidated.
+    ///
+    /// Textures are compared by version (see [TextureRef::version]), so this also detects textures
+    /// that were written to again.
     fn set_cache(&mut self, node: NodeRef, outputs: FnvHashMap<usize, Arc<Value>>) {
-        if let Some(cached) = self.cache.get(&node) {
-            // FIXME: textures may have been updated without their ID changing
-            if cached != &outputs {
-                return;
+        let previous = match self.cache.remove(&node) {
+            Some(previous) => Some(previous),
+            None => self.stale.remove(&node),
+        };
+
+        match previous {
+            Some(previous) if previous == outputs => {
+                // keep the previous values so nodes can tell that their inputs are the same
+                self.cache.insert(node, previous);
+            }
+            _ => {
+                self.cache.insert(node, outputs);
+                self.propagate_cache_invalidation(node);
             }
         }
-
-        self.cache.insert(node, outputs);
-        self.propagate_cache_invalidation(node);
     }
 
     /// Returns all node inputs.
@@ -298,6 +612,9 @@ impl Renderer {
                         .entry(in_prop)
                         .or_insert_with(|| Vec::new())
                         .push(Arc::clone(&value));
+                } else if self.is_disabled(input) {
+                    // disabled nodes may output nothing, which is treated like no link
+                    continue;
                 } else {
                     return Err(EvalError::MissingInput(in_prop));
                 }
@@ -307,17 +624,49 @@ impl Renderer {
         }
 
         // collect fallback property values
-        if let Some(node) = self.graph.node(&node) {
-            for (k, v) in node.props.iter() {
-                inputs
-                    .entry(*k)
-                    .or_insert_with(|| vec![Arc::new(v.clone())]);
+        if let Some(node_data) = self.graph.node(&node) {
+            for (k, v) in self.graph.property_values(&node) {
+                inputs.entry(k).or_insert_with(|| vec![Arc::new(v.clone())]);
+            }
+
+            // and default values from the schema
+            if let Some(schema) = self.schemas.get(&node_data.node_type) {
+                for port in schema.inputs() {
+                    if let Some(default) = &port.default {
+                        inputs
+                            .entry(port.index)
+                            .or_insert_with(|| vec![Arc::new(default.clone())]);
+                    }
+                }
             }
         }
 
         Ok(inputs)
     }
 
+    /// Returns true if the node exists and is disabled.
+    fn is_disabled(&self, node: NodeRef) -> bool {
+        self.graph.node(&node).map_or(false, |node| !node.enabled)
+    }
+
+    /// Returns the outputs of a disabled node: inputs are passed through to outputs as declared by
+    /// the node type’s schema (see [NodeSchema::bypass]), and all other outputs produce nothing.
+    fn bypass_outputs(&self, node_ref: NodeRef, inputs: &Input) -> FnvHashMap<usize, Arc<Value>> {
+        let mut outputs = FnvHashMap::default();
+        let schema = self
+            .graph
+            .node(&node_ref)
+            .and_then(|node| self.schemas.get(&node.node_type));
+        if let Some(schema) = schema {
+            for (input, output) in &schema.bypass {
+                if let Some(value) = inputs.values.get(input).and_then(|values| values.get(0)) {
+                    outputs.insert(*output, Arc::clone(value));
+                }
+            }
+        }
+        outputs
+    }
+
     /// Recursively propagates node contexts.
     fn propagate_contexts(
         &mut self,
@@ -373,6 +722,8 @@ impl Renderer {
 
     /// Evaluates a single data node for the camera inputs and caches its outputs.
     fn eval_one_camera(&mut self, node_ref: NodeRef, is_camera: bool) -> Result<(), RenderError> {
+        let start = Instant::now();
+        let allocations = self.texture_pool.allocation_count();
         let inputs = Input {
             values: self
                 .node_inputs(
@@ -385,6 +736,7 @@ impl Renderer {
                 )
                 .map_err(|e| RenderError::Eval(node_ref, e))?,
             node: node_ref,
+            context: self.eval_context(),
         };
 
         let mut outputs = Output {
@@ -393,7 +745,14 @@ impl Renderer {
 
         self.ensure_node_instance(node_ref)?;
 
+        // the camera itself can not be bypassed
+        let bypass = !is_camera && self.is_disabled(node_ref);
+        if bypass {
+            outputs.values = self.bypass_outputs(node_ref, &inputs);
+        }
+
         match self.nodes.get_mut(&node_ref).unwrap() {
+            NodeInstance::Data(_) if bypass => (),
             NodeInstance::Data(node) => node
                 .eval(inputs, &mut outputs)
                 .map_err(|e| RenderError::Eval(node_ref, e))?,
@@ -402,6 +761,7 @@ impl Renderer {
             }
         }
 
+        self.record_stats(node_ref, start, allocations, 0);
         self.set_cache(node_ref, outputs.values);
         Ok(())
     }
@@ -429,11 +789,15 @@ impl Renderer {
         node_ref: NodeRef,
         mut cmd_buffer: AutoCommandBufferBuilder,
     ) -> Result<AutoCommandBufferBuilder, RenderError> {
+        let start = Instant::now();
+        let allocations = self.texture_pool.allocation_count();
+        let mut vertices = 0;
         let inputs = Input {
             values: self
                 .node_inputs(node_ref, None)
                 .map_err(|e| RenderError::Eval(node_ref, e))?,
             node: node_ref,
+            context: self.eval_context(),
         };
         let mut outputs = Output {
             values: FnvHashMap::default(),
@@ -441,24 +805,39 @@ impl Renderer {
 
         self.ensure_node_instance(node_ref)?;
 
-        match self.nodes.get_mut(&node_ref).unwrap() {
-            NodeInstance::Data(node) => node
-                .eval(inputs, &mut outputs)
-                .map_err(|e| RenderError::Eval(node_ref, e))?,
-            NodeInstance::Graphics(node) => {
-                let context = match self.ctx_cache.get(&node_ref) {
-                    Some(context) => *context,
-                    None => return Err(InternalRendererError::NoContext(node_ref).into()),
-                };
-
-                let node_context = NodeContext {
-                    context,
-                    tex_pool: &mut self.texture_pool,
-                };
-
-                cmd_buffer = node
-                    .eval(inputs, node_context, &mut outputs, cmd_buffer)
-                    .map_err(|e| RenderError::Eval(node_ref, e))?;
+        if self.is_disabled(node_ref) {
+            outputs.values = self.bypass_outputs(node_ref, &inputs);
+        } else {
+            match self.nodes.get_mut(&node_ref).unwrap() {
+                NodeInstance::Data(node) => node
+                    .eval(inputs, &mut outputs)
+                    .map_err(|e| RenderError::Eval(node_ref, e))?,
+                NodeInstance::Graphics(node) => {
+                    let context = match self.ctx_cache.get(&node_ref) {
+                        Some(context) => *context,
+                        None => return Err(InternalRendererError::NoContext(node_ref).into()),
+                    };
+
+                    let textures = self.node_textures.entry(node_ref).or_default();
+                    textures.begin(TextureDeps::new(inputs.values.clone(), context));
+
+                    let node_context = NodeContext {
+                        context,
+                        tex_pool: &mut self.texture_pool,
+                        textures,
+                        tessellated_vertices: &mut vertices,
+                    };
+
+                    match node.eval(inputs, node_context, &mut outputs, cmd_buffer) {
+                        Ok(c) => cmd_buffer = c,
+                        Err(err) => {
+                            // the node may have left its textures half-written
+                            self.node_textures.remove(&node_ref);
+                            return Err(RenderError::Eval(node_ref, err));
+                        }
+                    }
+                    self.node_textures.get_mut(&node_ref).unwrap().commit();
+                }
             }
         }
 
@@ -488,18 +867,153 @@ impl Renderer {
                 Value::Drawables(drawables) => {
                     let (c, tex) = self.rasterize_drawables(drawables, context, cmd_buffer)?;
                     cmd_buffer = c;
+                    vertices += self.shape_rasterizer.take_tessellated_vertices();
                     *value = Arc::new(Value::Texture(tex));
                 }
                 v => panic!("don’t know how to rasterize {:?}", v.value_type()),
             }
         }
 
+        self.record_stats(node_ref, start, allocations, vertices);
         self.set_cache(node_ref, outputs.values);
         Ok(cmd_buffer)
     }
 
     /// Renders the entire scene.
+    ///
+    /// If the graph contains group instances, a flattened copy of the graph (see [Graph::flatten])
+    /// is rendered instead. The copy is kept until nodes, links, or group instances change;
+    /// property changes of other nodes are copied into it. Only nodes that originate from dirty
+    /// nodes are re-evaluated.
     pub fn render(
+        &mut self,
+        cmd_buffer: AutoCommandBufferBuilder,
+    ) -> Result<(AutoCommandBufferBuilder, TextureRef), RenderError> {
+        let (graph_id, subscriber) = self.subscriber;
+        if graph_id != self.graph.id() || !self.graph.is_subscribed(subscriber) {
+            // the graph was replaced; a clone of the previous graph still has the old subscription
+            self.graph.unsubscribe(subscriber);
+            self.subscriber = (self.graph.id(), self.graph.subscribe());
+            self.flat = None;
+        }
+        self.graph.animate(self.time);
+        if let Some(report) = &mut self.report {
+            report.clear();
+        }
+        let events = self.graph.drain_events(self.subscriber.1);
+
+        if !self.graph.has_groups() {
+            self.flat = None;
+            let result = self.render_graph(cmd_buffer);
+            self.end_frame();
+            return result;
+        }
+
+        let (mut flat, origins) = match self.flat.take() {
+            Some((mut flat, origins)) if !self.changes_groups(&events) => {
+                // only top-level nodes outside groups changed, and those keep their references
+                for event in &events {
+                    match event {
+                        GraphEvent::PropertyChanged(node, _)
+                        | GraphEvent::EnabledChanged(node, _) => {
+                            if let (Some(node), Some(flat_node)) =
+                                (self.graph.flatten_node(*node), flat.node_mut(node))
+                            {
+                                *flat_node = node;
+                            }
+                        }
+                        _ => (),
+                    }
+                }
+                (flat, origins)
+            }
+            _ => {
+                let (mut flat, origins) = self.graph.flatten_with_origins();
+
+                // cached outputs are still valid for nodes that didn’t change
+                let flat_nodes: Vec<_> = flat.iter_nodes().map(|(k, _)| *k).collect();
+                for node in &flat_nodes {
+                    flat.mark_clean(node);
+                }
+                for event in &events {
+                    match event {
+                        GraphEvent::Linked(_, _, node, _) | GraphEvent::Unlinked(_, _, node, _) => {
+                            self.graph.mark_dirty(*node)
+                        }
+                        _ => (),
+                    }
+                }
+
+                (flat, origins)
+            }
+        };
+        // group contents may be animated too
+        flat.animate(self.time);
+
+        let dirty_nodes: Vec<_> = self
+            .graph
+            .iter_nodes()
+            .map(|(k, _)| *k)
+            .filter(|k| self.graph.is_dirty(k))
+            .collect();
+        for node in &dirty_nodes {
+            for flat_node in origins.get(node).into_iter().flatten() {
+                flat.mark_dirty(*flat_node);
+            }
+            self.graph.mark_clean(node);
+        }
+
+        mem::swap(&mut self.graph, &mut flat);
+        let result = self.render_graph(cmd_buffer);
+        mem::swap(&mut self.graph, &mut flat);
+        self.end_frame();
+
+        self.flat = Some((flat, origins));
+        result
+    }
+
+    /// Returns true if the events change the graph in a way that requires flattening it again,
+    /// i.e. anything other than property and enabled state changes of nodes that aren’t group
+    /// instances.
+    fn changes_groups(&self, events: &[GraphEvent]) -> bool {
+        let is_group = |node: &NodeRef| {
+            self.graph
+                .node(node)
+                .map_or(true, |node| node.group_def().is_some())
+        };
+        events.iter().any(|event| match event {
+            GraphEvent::PropertyChanged(node, prop) => *prop == GROUP_DEF_PROP || is_group(node),
+            GraphEvent::EnabledChanged(node, _) => is_group(node),
+            _ => true,
+        })
+    }
+
+    /// Renders the entire scene (see [Renderer::render]) and returns per-node statistics along
+    /// with the output texture, regardless of whether profiling is enabled.
+    pub fn render_profiled(
+        &mut self,
+        cmd_buffer: AutoCommandBufferBuilder,
+    ) -> Result<(AutoCommandBufferBuilder, TextureRef, RenderReport), RenderError> {
+        let was_profiling = self.report.is_some();
+        self.set_profiling(true);
+        let result = self.render(cmd_buffer);
+        let report = self.report.clone().unwrap_or_default();
+        if !was_profiling {
+            self.set_profiling(false);
+        }
+
+        let (cmd_buffer, texture) = result?;
+        Ok((cmd_buffer, texture, report))
+    }
+
+    /// Advances the frame number after a render.
+    fn end_frame(&mut self) {
+        self.frame += 1;
+        self.prev_time = Some(self.time);
+    }
+
+    /// Renders the scene in the current graph, which must not contain group instances.
+    fn render_graph(
         &mut self,
         mut cmd_buffer: AutoCommandBufferBuilder,
     ) -> Result<(AutoCommandBufferBuilder, TextureRef), RenderError> {
@@ -561,13 +1075,26 @@ impl Renderer {
 
             if is_dirty {
                 self.graph.mark_clean(node_ref);
-                self.cache.remove(node_ref);
-                self.propagate_cache_invalidation(*node_ref);
+                self.invalidate_cache(*node_ref);
             }
         }
 
+        // nodes that aren’t part of the order won’t be re-evaluated when their inputs change, so
+        // their outputs can’t be trusted later on
+        let ordered: FnvHashSet<_> = order.iter().collect();
+        let unordered: Vec<_> = self
+            .cache
+            .keys()
+            .filter(|node| !ordered.contains(node))
+            .cloned()
+            .collect();
+        for node in unordered {
+            self.invalidate_cache(node);
+        }
+
         for node_ref in &order {
             if self.cache.contains_key(node_ref) {
+                self.record_cached(*node_ref);
                 continue;
             }
             cmd_buffer = self.eval_one(*node_ref, cmd_buffer)?;
@@ -579,8 +1106,13 @@ impl Renderer {
             self.shape_rasterizer.drop_unused();
             self.texture_pool.drop_unused();
 
-            let mut unused_nodes: FnvHashSet<_> =
-                self.cache.keys().chain(self.nodes.keys()).map(|k| *k).collect();
+            let mut unused_nodes: FnvHashSet<_> = self
+                .cache
+                .keys()
+                .chain(self.stale.keys())
+                .chain(self.nodes.keys())
+                .map(|k| *k)
+                .collect();
 
             for node in order {
                 unused_nodes.remove(&node);
@@ -588,7 +1120,9 @@ impl Renderer {
 
             for node in unused_nodes {
                 self.cache.remove(&node);
+                self.stale.remove(&node);
                 self.nodes.remove(&node);
+                self.node_textures.remove(&node);
             }
         }
 
@@ -692,9 +1226,11 @@ impl Renderer {
     /// Drops all caches or other ‘inessential data’ such as buffers and textures.
     pub fn clear_caches(&mut self) {
         self.shape_rasterizer.clear_caches();
+        self.node_textures.clear();
         self.texture_pool.clear_caches();
         self.ctx_cache.clear();
         self.cache.clear();
+        self.stale.clear();
 
         for (_, node_type) in &mut self.node_types {
             node_type.clear_caches();
@@ -0,0 +1,98 @@
+use crate::node::NodeRef;
+use fnv::FnvHashMap;
+use std::time::Duration;
+
+/// Statistics for a single node during a render.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NodeStats {
+    /// The node. This may be a node inside a group instance (see [crate::node::Graph::flatten]).
+    pub node: NodeRef,
+
+    /// True if the node was not evaluated because its cached outputs were still valid.
+    pub cached: bool,
+
+    /// CPU time spent evaluating the node, including recording GPU commands.
+    pub cpu_time: Duration,
+
+    /// Number of textures allocated from the texture pool.
+    pub textures_allocated: u64,
+
+    /// Number of vertices produced by tessellating shapes.
+    pub vertices_tessellated: usize,
+}
+
+impl NodeStats {
+    pub(crate) fn new(node: NodeRef) -> NodeStats {
+        NodeStats {
+            node,
+            cached: false,
+            cpu_time: Duration::default(),
+            textures_allocated: 0,
+            vertices_tessellated: 0,
+        }
+    }
+}
+
+/// Per-node statistics collected during a render (see [crate::render::Renderer::set_profiling]).
+///
+/// GPU execution time is not included: vulkano 0.11 can not record timestamp queries into the
+/// command buffers nodes are evaluated with, nor read query results back.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RenderReport {
+    nodes: Vec<NodeStats>,
+    indices: FnvHashMap<NodeRef, usize>,
+}
+
+impl RenderReport {
+    /// Returns statistics for every node, in evaluation order.
+    pub fn nodes(&self) -> &[NodeStats] {
+        &self.nodes
+    }
+
+    /// Returns the statistics for a node.
+    pub fn node(&self, node: NodeRef) -> Option<&NodeStats> {
+        self.indices.get(&node).map(|index| &self.nodes[*index])
+    }
+
+    /// Adds statistics for a node, unless the node was already recorded. Returns false if so.
+    pub(crate) fn insert(&mut self, stats: NodeStats) -> bool {
+        if self.indices.contains_key(&stats.node) {
+            return false;
+        }
+        self.indices.insert(stats.node, self.nodes.len());
+        self.nodes.push(stats);
+        true
+    }
+
+    /// Removes all statistics.
+    pub(crate) fn clear(&mut self) {
+        self.nodes.clear();
+        self.indices.clear();
+    }
+
+    /// Returns the total CPU time spent evaluating nodes.
+    pub fn total_cpu_time(&self) -> Duration {
+        self.nodes.iter().map(|stats| stats.cpu_time).sum()
+    }
+
+    /// Returns the number of nodes that were evaluated rather than taken from the cache.
+    pub fn evaluated_count(&self) -> usize {
+        self.nodes.iter().filter(|stats| !stats.cached).count()
+    }
+
+    /// Returns the total number of textures allocated.
+    pub fn textures_allocated(&self) -> u64 {
+        self.nodes
+            .iter()
+            .map(|stats| stats.textures_allocated)
+            .sum()
+    }
+
+    /// Returns the total number of tessellated vertices.
+    pub fn vertices_tessellated(&self) -> usize {
+        self.nodes
+            .iter()
+            .map(|stats| stats.vertices_tessellated)
+            .sum()
+    }
+}
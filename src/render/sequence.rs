@@ -0,0 +1,160 @@
+use crate::data::{Bitmap, BitmapError};
+use crate::render::{RenderError, Renderer};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process::{Child, Command, ExitStatus, Stdio};
+
+/// Frame sequence rendering errors.
+#[derive(Debug, Fail)]
+pub enum SequenceError {
+    /// A frame failed to render.
+    #[fail(display = "failed to render frame {}: {}", _0, _1)]
+    Render(u64, RenderError),
+
+    /// A frame could not be written.
+    #[fail(display = "failed to write frame {}: {}", _0, _1)]
+    Bitmap(u64, BitmapError),
+
+    /// The encoder could not be started or written to.
+    #[fail(display = "encoder error: {}", _0)]
+    Io(io::Error),
+
+    /// The encoder exited unsuccessfully.
+    #[fail(display = "encoder exited with {}", _0)]
+    Encoder(ExitStatus),
+
+    /// Frames changed size, which raw video can not represent.
+    #[fail(display = "frame {} has a different size than the first frame", _0)]
+    SizeChanged(u64),
+}
+
+impl From<io::Error> for SequenceError {
+    fn from(err: io::Error) -> SequenceError {
+        SequenceError::Io(err)
+    }
+}
+
+/// A frame sequence to render.
+///
+/// Frames are rendered from `start` up to but excluding `end`, where each frame number is divided
+/// by `fps` to get the time (see [Renderer::set_time]).
+#[derive(Debug)]
+pub struct Sequence {
+    /// First frame number.
+    pub start: u64,
+
+    /// Frame number after the last frame.
+    pub end: u64,
+
+    /// Frames per second.
+    pub fps: f64,
+
+    /// If set, every frame is written to a file with this path, where the last run of `#`
+    /// characters is replaced by the zero-padded frame number (e.g. `out/frame-####.png`). The file
+    /// format is determined by the extension (see [Bitmap::save]).
+    pub output: Option<String>,
+
+    /// If set, this command is spawned and every frame is written to its standard input as raw
+    /// 8-bit sRGB RGBA pixels with straight alpha, row by row. For example, with
+    /// `ffmpeg -f rawvideo -pix_fmt rgba -s WIDTHxHEIGHT -r FPS -i - out.mp4`.
+    ///
+    /// Raw video requires all frames to have the same size.
+    pub encoder: Option<Command>,
+}
+
+impl Sequence {
+    /// Creates a new frame sequence without any outputs.
+    pub fn new(start: u64, end: u64, fps: f64) -> Sequence {
+        Sequence {
+            start,
+            end,
+            fps,
+            output: None,
+            encoder: None,
+        }
+    }
+
+    /// Returns the output path for a frame.
+    pub fn output_path(&self, frame: u64) -> Option<PathBuf> {
+        let output = self.output.as_ref()?;
+        let path = match output.rfind('#') {
+            Some(end) => {
+                let start = output[..end].trim_end_matches('#').len();
+                let width = end + 1 - start;
+                format!(
+                    "{}{:0width$}{}",
+                    &output[..start],
+                    frame,
+                    &output[end + 1..],
+                    width = width
+                )
+            }
+            None => output.clone(),
+        };
+        Some(path.into())
+    }
+
+    /// Renders all frames, calling the callback with the frame number and bitmap after each frame.
+    pub fn render<F: FnMut(u64, &Bitmap)>(
+        &mut self,
+        renderer: &mut Renderer,
+        mut callback: F,
+    ) -> Result<(), SequenceError> {
+        let mut encoder: Option<Child> = match &mut self.encoder {
+            Some(command) => Some(command.stdin(Stdio::piped()).spawn()?),
+            None => None,
+        };
+        let mut size = None;
+
+        for frame in self.start..self.end {
+            renderer.set_time(frame as f64 / self.fps);
+            renderer.set_frame(frame);
+            let bitmap = renderer
+                .render_to_bitmap()
+                .map_err(|err| SequenceError::Render(frame, err))?;
+
+            if let Some(path) = self.output_path(frame) {
+                bitmap
+                    .save(path)
+                    .map_err(|err| SequenceError::Bitmap(frame, err))?;
+            }
+
+            if let Some(encoder) = &mut encoder {
+                let frame_size = (bitmap.width, bitmap.height);
+                if *size.get_or_insert(frame_size) != frame_size {
+                    return Err(SequenceError::SizeChanged(frame));
+                }
+                let data = bitmap
+                    .to_srgb8()
+                    .map_err(|err| SequenceError::Bitmap(frame, err))?;
+                encoder.stdin.as_mut().unwrap().write_all(&data)?;
+            }
+
+            callback(frame, &bitmap);
+        }
+
+        if let Some(mut encoder) = encoder {
+            // close stdin so the encoder finishes
+            drop(encoder.stdin.take());
+            let status = encoder.wait()?;
+            if !status.success() {
+                return Err(SequenceError::Encoder(status));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[test]
+fn output_paths() {
+    let mut sequence = Sequence::new(0, 10, 24.);
+    assert_eq!(sequence.output_path(3), None);
+
+    sequence.output = Some("out/frame-####.png".into());
+    assert_eq!(sequence.output_path(3), Some("out/frame-0003.png".into()));
+    assert_eq!(sequence.output_path(12345), Some("out/frame-12345.png".into()));
+
+    sequence.output = Some("#/frame.exr".into());
+    assert_eq!(sequence.output_path(7), Some("7/frame.exr".into()));
+}
@@ -164,6 +164,28 @@ impl Shape {
         Ok((ibuf, vbuf))
     }
 
+    /// Tessellates the stroke, if any, into vertices and triangle indices.
+    pub(crate) fn stroke_mesh(&self) -> Option<(Vec<Vector2<f32>>, Vec<u16>)> {
+        let (weight, width, _) = self.stroke.as_ref()?;
+        let shape_verts = self.path.flatten_to_verts();
+
+        let mut verts = Vec::new();
+        let mut indices = Vec::new();
+        for contiguous_shape in shape_verts {
+            let (mut v, i) = stroke_tess::tessellate(
+                &Self::stroke_points(&weight, *width, &contiguous_shape),
+                STROKE_ARC_THRESHOLD,
+            );
+            verts.append(&mut v);
+            let offset = indices.len() as u16;
+            indices.reserve(i.len());
+            i.into_iter()
+                .map(|i| i + offset)
+                .for_each(|i| indices.push(i));
+        }
+        Some((verts, indices))
+    }
+
     fn tess_stroke(
         &self,
         dev: &Arc<Device>,
@@ -176,23 +198,7 @@ impl Shape {
         )>,
         Error,
     > {
-        if let Some((weight, width, _)) = &self.stroke {
-            let shape_verts = self.path.flatten_to_verts();
-
-            let mut verts = Vec::new();
-            let mut indices = Vec::new();
-            for contiguous_shape in shape_verts {
-                let (mut v, i) = stroke_tess::tessellate(
-                    &Self::stroke_points(&weight, *width, &contiguous_shape),
-                    STROKE_ARC_THRESHOLD,
-                );
-                verts.append(&mut v);
-                let offset = indices.len() as u16;
-                indices.reserve(i.len());
-                i.into_iter()
-                    .map(|i| i + offset)
-                    .for_each(|i| indices.push(i));
-            }
+        if let Some((verts, indices)) = self.stroke_mesh() {
             Ok(Some(Self::create_or_update_buffers(
                 dev, ibuf, vbuf, &indices, &verts,
             )?))
@@ -201,6 +207,45 @@ impl Shape {
         }
     }
 
+    /// Tessellates the fill, if any, into vertices and triangle indices.
+    pub(crate) fn fill_mesh(&self) -> Result<Option<(Vec<Vector2<f32>>, Vec<u16>)>, Error> {
+        if self.fill.is_none() {
+            return Ok(None);
+        }
+
+        let shape_verts: Vec<_> = self
+            .path
+            .flatten_to_verts()
+            .into_iter()
+            .flat_map(|s| s)
+            .collect();
+
+        let mut buffers = VertexBuffers::new();
+
+        {
+            let mut vertex_builder = geometry_builder::simple_builder(&mut buffers);
+            let mut tessellator = FillTessellator::new();
+            let opts = FillOptions::DEFAULT
+                .on_error(OnError::Recover)
+                .with_normals(false);
+
+            #[derive(Debug, Fail)]
+            #[fail(display = "tessellation error: {:?}", _0)]
+            struct FillErr(FillError);
+
+            tessellator
+                .tessellate_path(VertIterator::new(&shape_verts), &opts, &mut vertex_builder)
+                .map_err(FillErr)?;
+        }
+
+        let verts: Vec<_> = buffers
+            .vertices
+            .into_iter()
+            .map(|v| Vector2::new(v.position.x, v.position.y))
+            .collect();
+        Ok(Some((verts, buffers.indices)))
+    }
+
     fn tess_fill(
         &self,
         dev: &Arc<Device>,
@@ -213,43 +258,9 @@ impl Shape {
         )>,
         Error,
     > {
-        if let Some(_) = self.fill {
-            let shape_verts: Vec<_> = self
-                .path
-                .flatten_to_verts()
-                .into_iter()
-                .flat_map(|s| s)
-                .collect();
-
-            let mut buffers = VertexBuffers::new();
-
-            {
-                let mut vertex_builder = geometry_builder::simple_builder(&mut buffers);
-                let mut tessellator = FillTessellator::new();
-                let opts = FillOptions::DEFAULT
-                    .on_error(OnError::Recover)
-                    .with_normals(false);
-
-                #[derive(Debug, Fail)]
-                #[fail(display = "tessellation error: {:?}", _0)]
-                struct FillErr(FillError);
-
-                tessellator
-                    .tessellate_path(VertIterator::new(&shape_verts), &opts, &mut vertex_builder)
-                    .map_err(FillErr)?;
-            }
-
-            let verts: Vec<_> = buffers
-                .vertices
-                .into_iter()
-                .map(|v| Vector2::new(v.position.x, v.position.y))
-                .collect();
+        if let Some((verts, indices)) = self.fill_mesh()? {
             Ok(Some(Self::create_or_update_buffers(
-                dev,
-                ibuf,
-                vbuf,
-                &buffers.indices,
-                &verts,
+                dev, ibuf, vbuf, &indices, &verts,
             )?))
         } else {
             Ok(None)
@@ -462,6 +473,7 @@ pub struct ShapeRasterizer<ID: Copy + Hash + Eq> {
     shape_ds_cache:
         HashMap<(MatrixCacheKey, MatrixCacheKey), Weak<dyn DescriptorSet + Send + Sync>>,
     used_ids: FnvHashSet<ID>,
+    tessellated_vertices: usize,
 }
 
 impl<ID: Copy + Hash + Eq> ShapeRasterizer<ID> {
@@ -511,6 +523,7 @@ impl<ID: Copy + Hash + Eq> ShapeRasterizer<ID> {
             shape_pipeline,
             device,
             used_ids: FnvHashSet::default(),
+            tessellated_vertices: 0,
         })
     }
 
@@ -594,6 +607,8 @@ impl<ID: Copy + Hash + Eq> ShapeRasterizer<ID> {
 
             let stroke = shape.tess_stroke(&self.device, None, None)?;
             let fill = shape.tess_fill(&self.device, None, None)?;
+            self.tessellated_vertices += stroke.as_ref().map_or(0, |(_, verts)| verts.len());
+            self.tessellated_vertices += fill.as_ref().map_or(0, |(_, verts)| verts.len());
 
             self.cache.insert(
                 id,
@@ -654,6 +669,7 @@ impl<ID: Copy + Hash + Eq> ShapeRasterizer<ID> {
                     let mut ifill = mem::replace(fill, unsafe { mem::uninitialized() });
                     let (ibuf, vbuf) = ifill.map_or((None, None), |(x, y)| (Some(x), Some(y)));
                     ifill = shape.tess_fill(&self.device, ibuf, vbuf)?;
+                    self.tessellated_vertices += ifill.as_ref().map_or(0, |(_, v)| v.len());
                     mem::forget(mem::replace(fill, ifill));
                 }
 
@@ -662,6 +678,7 @@ impl<ID: Copy + Hash + Eq> ShapeRasterizer<ID> {
                     let mut istroke = mem::replace(stroke, unsafe { mem::uninitialized() });
                     let (ibuf, vbuf) = istroke.map_or((None, None), |(x, y)| (Some(x), Some(y)));
                     istroke = shape.tess_stroke(&self.device, ibuf, vbuf)?;
+                    self.tessellated_vertices += istroke.as_ref().map_or(0, |(_, v)| v.len());
                     mem::forget(mem::replace(stroke, istroke));
                 }
 
@@ -744,6 +761,11 @@ impl<ID: Copy + Hash + Eq> ShapeRasterizer<ID> {
         self.draw_shape(id, cmd_buffer, dyn_state)
     }
 
+    /// Returns the number of vertices tessellated since the last call and resets the count.
+    pub fn take_tessellated_vertices(&mut self) -> usize {
+        mem::replace(&mut self.tessellated_vertices, 0)
+    }
+
     /// Frees all resources that weren’t used since the last call to `drop_unused`.
     pub fn drop_unused(&mut self) {
         for id in self
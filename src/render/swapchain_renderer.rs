@@ -9,7 +9,7 @@ use vulkano::descriptor::descriptor_set::FixedSizeDescriptorSetsPool;
 use vulkano::descriptor::PipelineLayoutAbstract;
 use vulkano::device::Device;
 use vulkano::format::Format;
-use vulkano::framebuffer::{Framebuffer, RenderPassAbstract, Subpass};
+use vulkano::framebuffer::{Framebuffer, FramebufferAbstract, RenderPassAbstract, Subpass};
 use vulkano::image::SwapchainImage;
 use vulkano::pipeline::vertex::SingleBufferDefinition;
 use vulkano::pipeline::viewport::Viewport;
@@ -21,8 +21,12 @@ mod render_vs {
 #version 450
 layout(location = 0) in vec2 a_position;
 layout(location = 0) out vec2 v_position;
+layout(push_constant) uniform Region {
+    // texture coordinates of the bottom right corner of the input region
+    vec2 in_scale;
+};
 void main() {
-    v_position = a_position / vec2(2, -2) + vec2(0.5);
+    v_position = (a_position / vec2(2, -2) + vec2(0.5)) * in_scale;
     gl_Position = vec4(a_position, 0, 1);
 }
     ");
@@ -41,6 +45,8 @@ void main() {
     ");
 }
 
+use self::render_vs::ty::Region;
+
 #[repr(C)]
 struct Vertex {
     a_position: [f32; 2],
@@ -64,6 +70,10 @@ pub(crate) struct SwapchainRenderer {
     graphics_ds_pool: FixedSizeDescriptorSetsPool<CTGraphicsPipeline>,
     vertex_buf: Arc<CpuAccessibleBuffer<[Vertex]>>,
     input_sampler: Arc<Sampler>,
+    framebuffers: Vec<(
+        Arc<SwapchainImage<NarwhalSurface>>,
+        Arc<dyn FramebufferAbstract + Send + Sync>,
+    )>,
 }
 
 impl SwapchainRenderer {
@@ -129,22 +139,50 @@ impl SwapchainRenderer {
             graphics_ds_pool,
             vertex_buf,
             input_sampler,
+            framebuffers: Vec::new(),
         })
     }
 
+    /// Drops all cached framebuffers. Should be called when the swapchain is recreated.
+    pub fn clear_framebuffers(&mut self) {
+        self.framebuffers.clear();
+    }
+
+    /// Returns the cached framebuffer for a swapchain image or creates a new one.
+    fn framebuffer(
+        &mut self,
+        output: &Arc<SwapchainImage<NarwhalSurface>>,
+    ) -> Result<Arc<dyn FramebufferAbstract + Send + Sync>, Error> {
+        if let Some((_, framebuffer)) = self
+            .framebuffers
+            .iter()
+            .find(|(image, _)| Arc::ptr_eq(image, output))
+        {
+            return Ok(Arc::clone(framebuffer));
+        }
+
+        let framebuffer: Arc<dyn FramebufferAbstract + Send + Sync> = Arc::new(
+            Framebuffer::start(Arc::clone(&self.render_pass))
+                .add(Arc::clone(&output))?
+                .build()?,
+        );
+        self.framebuffers
+            .push((Arc::clone(output), Arc::clone(&framebuffer)));
+        Ok(framebuffer)
+    }
+
+    /// Renders the input region with its bottom right corner at the texture coordinates
+    /// `in_scale` onto the entire output.
     pub fn render(
         &mut self,
         mut cmd_buffer: AutoCommandBufferBuilder,
         input: &Texture,
+        in_scale: [f32; 2],
         output: &Arc<SwapchainImage<NarwhalSurface>>,
     ) -> Result<AutoCommandBufferBuilder, Error> {
         let size = output.dimensions();
 
-        let framebuffer = Arc::new(
-            Framebuffer::start(Arc::clone(&self.render_pass))
-                .add(Arc::clone(&output))?
-                .build()?,
-        );
+        let framebuffer = self.framebuffer(output)?;
 
         let render_set = self
             .graphics_ds_pool
@@ -166,7 +204,7 @@ impl SwapchainRenderer {
                 },
                 Arc::clone(&self.vertex_buf),
                 render_set,
-                (),
+                Region { in_scale },
             )?
             .end_render_pass()?;
 
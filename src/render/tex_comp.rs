@@ -111,6 +111,7 @@ impl TexCompositor {
         // FIXME: should cache most of this stuff
         let globals = self.global_pool.next(Globals { camera })?;
         let size = texture.size();
+        let uv = texture.uv_scale();
 
         // TODO: something about the depth buffer? maybe?
 
@@ -119,9 +120,9 @@ impl TexCompositor {
             BufferUsage::vertex_buffer(),
             [
                 [0., 0., 0., 0.],
-                [size.x as f32, 0., 1., 0.],
-                [0., size.y as f32, 0., 1.],
-                [size.x as f32, size.y as f32, 1., 1.],
+                [size.x as f32, 0., uv.x, 0.],
+                [0., size.y as f32, 0., uv.y],
+                [size.x as f32, size.y as f32, uv.x, uv.y],
             ]
             .iter()
             .map(|v| CompTexVertex { a_position: *v }),
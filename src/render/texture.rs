@@ -1,10 +1,15 @@
+use crate::data::Value;
 use crate::eval::{EvalError, EvalResult};
-use crate::render::{COLOR_FORMAT, DEPTH_FORMAT};
+use crate::render::{Context, COLOR_FORMAT, DEPTH_FORMAT};
 use cgmath::{Matrix4, SquareMatrix, Vector2};
 use failure::Error;
 use fnv::FnvHashMap;
+use half::f16;
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
-use std::{fmt, mem};
+use vulkano::buffer::{BufferUsage, CpuAccessibleBuffer};
+use vulkano::command_buffer::AutoCommandBufferBuilder;
 use vulkano::device::{Device, Queue};
 use vulkano::format::Format;
 use vulkano::image::{self, AttachmentImage, Dimensions, ImageUsage, StorageImage};
@@ -15,12 +20,58 @@ enum TexType {
     Storage,
 }
 
-// TODO: also make this per-node so data can be cached
+/// Textures are allocated with sizes rounded up to a multiple of this many pixels, so that
+/// requests for slightly different sizes (such as while resizing a window) can share textures.
+const SIZE_BUCKET: u32 = 64;
+
+/// Estimated video memory per pixel: RGBA16F color and D32 depth.
+const BYTES_PER_PIXEL: u64 = 8 + 4;
+
+fn px_size(width: f32, height: f32, resolution: f32) -> [u32; 2] {
+    [(width * resolution) as u32, (height * resolution) as u32]
+}
+
+fn bucket_size(px: u32) -> u32 {
+    (px.max(1) + SIZE_BUCKET - 1) / SIZE_BUCKET * SIZE_BUCKET
+}
+
+/// Texture pool memory usage.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TextureUsage {
+    /// Number of allocated textures.
+    pub count: usize,
+
+    /// Estimated video memory allocated for textures, in bytes.
+    pub allocated: u64,
+
+    /// Estimated video memory of textures that are currently in use, in bytes.
+    pub in_use: u64,
+
+    /// The memory budget in bytes, if any.
+    pub budget: Option<u64>,
+}
+
+struct PoolEntry {
+    texture: TextureRef,
+    ty: TexType,
+    last_used: u64,
+}
+
+impl PoolEntry {
+    fn bytes(&self) -> u64 {
+        let [width, height] = self.texture.color.dimensions();
+        u64::from(width) * u64::from(height) * BYTES_PER_PIXEL
+    }
+}
+
 pub(crate) struct TexturePool {
     device: Arc<Device>,
     queue: Arc<Queue>,
-    sizes: FnvHashMap<(u32, u32, TexType), Vec<TextureRef>>,
+    entries: Vec<PoolEntry>,
     texture_id_counter: u64,
+    use_counter: u64,
+    gc_mark: u64,
+    budget: Option<u64>,
 }
 
 impl TexturePool {
@@ -28,26 +79,65 @@ impl TexturePool {
         TexturePool {
             device,
             queue,
-            sizes: FnvHashMap::default(),
+            entries: Vec::new(),
             texture_id_counter: 0,
+            use_counter: 0,
+            gc_mark: 0,
+            budget: None,
         }
     }
 
+    /// Drops all free textures that haven’t been used since the last call to `drop_unused`.
     pub fn drop_unused(&mut self) {
-        let sizes = mem::replace(&mut self.sizes, unsafe { mem::uninitialized() });
-        let new_sizes = sizes
-            .into_iter()
-            .map(|(k, pool)| {
-                (
-                    k,
-                    pool.into_iter()
-                        .filter(|x| x.is_shared())
-                        .collect::<Vec<_>>(),
-                )
-            })
-            .filter(|(_, pool)| !pool.is_empty())
-            .collect();
-        mem::forget(mem::replace(&mut self.sizes, new_sizes));
+        let gc_mark = self.gc_mark;
+        self.entries
+            .retain(|entry| entry.texture.is_shared() || entry.last_used > gc_mark);
+        self.gc_mark = self.use_counter;
+    }
+
+    /// Sets the memory budget in bytes and evicts free textures if it is exceeded.
+    ///
+    /// Textures that are in use are never evicted, so usage may still exceed the budget.
+    pub fn set_budget(&mut self, budget: Option<u64>) {
+        self.budget = budget;
+        if let Some(budget) = budget {
+            self.evict(budget);
+        }
+    }
+
+    /// Returns the current memory usage.
+    pub fn usage(&self) -> TextureUsage {
+        let mut usage = TextureUsage {
+            count: self.entries.len(),
+            budget: self.budget,
+            ..TextureUsage::default()
+        };
+        for entry in &self.entries {
+            usage.allocated += entry.bytes();
+            if entry.texture.is_shared() {
+                usage.in_use += entry.bytes();
+            }
+        }
+        usage
+    }
+
+    /// Drops free textures, least recently used first, until at most `target` bytes are
+    /// allocated or no free textures are left.
+    fn evict(&mut self, target: u64) {
+        let mut allocated = self.usage().allocated;
+        while allocated > target {
+            let lru = self
+                .entries
+                .iter()
+                .enumerate()
+                .filter(|(_, entry)| !entry.texture.is_shared())
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(i, _)| i);
+            match lru {
+                Some(i) => allocated -= self.entries.swap_remove(i).bytes(),
+                None => break,
+            }
+        }
     }
 
     /// Retrieves a free attachment from the pool or creates a new one otherwise.
@@ -70,6 +160,40 @@ impl TexturePool {
         self.texture(width, height, resolution, TexType::Storage)
     }
 
+    /// Uploads RGBA pixels into a free storage texture from the pool.
+    ///
+    /// The pixels are given row by row and should be in linear ACEScg with premultiplied alpha.
+    pub fn upload(
+        &mut self,
+        cmd_buffer: AutoCommandBufferBuilder,
+        px_width: u32,
+        px_height: u32,
+        resolution: f32,
+        pixels: &[f32],
+    ) -> Result<(AutoCommandBufferBuilder, TextureRef), Error> {
+        let texture = self.texture_px(px_width, px_height, resolution, TexType::Storage)?;
+
+        let buffer = CpuAccessibleBuffer::from_iter(
+            Arc::clone(&self.device),
+            BufferUsage::transfer_source(),
+            pixels.iter().map(|x| f16::from_f32(*x)),
+        )?;
+
+        let cmd_buffer = cmd_buffer
+            .copy_buffer_to_image_dimensions(
+                buffer,
+                Arc::clone(texture.color().as_storage()?),
+                [0, 0, 0],
+                [px_width, px_height, 1],
+                0,
+                1,
+                0,
+            )
+            .map_err(|e| Error::from(e))?;
+
+        Ok((cmd_buffer, texture))
+    }
+
     fn texture(
         &mut self,
         width: f32,
@@ -77,41 +201,63 @@ impl TexturePool {
         resolution: f32,
         ty: TexType,
     ) -> Result<TextureRef, Error> {
-        let px_width = (width * resolution) as u32;
-        let px_height = (height * resolution) as u32;
-        let key = (px_width, px_height, ty);
-
-        if self.sizes.contains_key(&key) {
-            if let Some(pool_textures) = self.sizes.get(&key) {
-                for pool_texture in pool_textures {
-                    if !pool_texture.is_shared() {
-                        return Ok(pool_texture.clone());
-                    }
-                }
-            }
+        let [px_width, px_height] = px_size(width, height, resolution);
+        self.texture_px(px_width, px_height, resolution, ty)
+    }
+
+    fn texture_px(
+        &mut self,
+        px_width: u32,
+        px_height: u32,
+        resolution: f32,
+        ty: TexType,
+    ) -> Result<TextureRef, Error> {
+        let dimensions = [bucket_size(px_width), bucket_size(px_height)];
+        self.use_counter += 1;
+        let use_counter = self.use_counter;
+
+        // prefer the most recently used texture
+        if let Some(entry) = self
+            .entries
+            .iter_mut()
+            .filter(|entry| {
+                entry.ty == ty
+                    && entry.texture.color.dimensions() == dimensions
+                    && !entry.texture.is_shared()
+            })
+            .max_by_key(|entry| entry.last_used)
+        {
+            entry.last_used = use_counter;
+            return Ok(entry.texture.region(px_width, px_height, resolution));
+        }
+
+        if let Some(budget) = self.budget {
+            let bytes = u64::from(dimensions[0]) * u64::from(dimensions[1]) * BYTES_PER_PIXEL;
+            self.evict(budget.saturating_sub(bytes));
         }
 
+        let [width, height] = dimensions;
         let color = match ty {
             TexType::Attachment => Texture::Attachment(AttachmentImage::multisampled_with_usage(
                 Arc::clone(&self.device),
-                [px_width, px_height],
+                [width, height],
                 1, // vulkano has no support for vkCmdResolveImage yet
                 COLOR_FORMAT,
                 ImageUsage {
                     sampled: true,
+                    transfer_source: true,
                     ..ImageUsage::none()
                 },
             )?),
             TexType::Storage => Texture::Storage(StorageImage::with_usage(
                 Arc::clone(&self.device),
-                Dimensions::Dim2d {
-                    width: px_width,
-                    height: px_height,
-                },
+                Dimensions::Dim2d { width, height },
                 COLOR_FORMAT,
                 ImageUsage {
                     sampled: true,
                     storage: true,
+                    transfer_source: true,
+                    transfer_destination: true,
                     ..ImageUsage::none()
                 },
                 Some(self.queue.family()),
@@ -122,7 +268,7 @@ impl TexturePool {
             TexType::Attachment => Some(Texture::Attachment(
                 AttachmentImage::multisampled_with_usage(
                     Arc::clone(&self.device),
-                    [px_width, px_height],
+                    [width, height],
                     1, // vulkano has no support for vkCmdResolveImage yet
                     DEPTH_FORMAT,
                     ImageUsage {
@@ -133,10 +279,7 @@ impl TexturePool {
             )),
             TexType::Storage => Some(Texture::Storage(StorageImage::with_usage(
                 Arc::clone(&self.device),
-                Dimensions::Dim2d {
-                    width: px_width,
-                    height: px_height,
-                },
+                Dimensions::Dim2d { width, height },
                 DEPTH_FORMAT,
                 ImageUsage {
                     sampled: true,
@@ -147,27 +290,35 @@ impl TexturePool {
             )?)),
         };
 
-        let tex_ref = TextureRef {
+        let texture = TextureRef {
             texture_id: self.texture_id_counter,
+            latest_version: Arc::new(AtomicUsize::new(0)),
+            version: 0,
             color,
             depth,
+            px_size: dimensions,
             transform: Matrix4::identity(),
             resolution,
         };
         self.texture_id_counter += 1;
 
-        if !self.sizes.contains_key(&key) {
-            self.sizes.insert(key, vec![tex_ref]);
-        } else {
-            self.sizes.get_mut(&key).unwrap().push(tex_ref);
-        }
+        let tex_ref = texture.region(px_width, px_height, resolution);
+        self.entries.push(PoolEntry {
+            texture,
+            ty,
+            last_used: use_counter,
+        });
+        Ok(tex_ref)
+    }
 
-        Ok(self.sizes[&key].last().unwrap().clone())
+    /// Returns the number of textures allocated so far.
+    pub fn allocation_count(&self) -> u64 {
+        self.texture_id_counter
     }
 
     /// Clears all caches.
     pub fn clear_caches(&mut self) {
-        self.sizes.clear();
+        self.entries.clear();
     }
 }
 
@@ -177,6 +328,133 @@ enum TextureConversionError {
     IsNotStorageImage,
 }
 
+/// The inputs and context of a node evaluation, which determine what the node writes into its
+/// cached textures.
+#[derive(Clone)]
+pub(crate) struct TextureDeps {
+    inputs: FnvHashMap<usize, Vec<Arc<Value>>>,
+    context: Context,
+}
+
+impl TextureDeps {
+    pub fn new(inputs: FnvHashMap<usize, Vec<Arc<Value>>>, context: Context) -> TextureDeps {
+        TextureDeps { inputs, context }
+    }
+
+    fn matches(&self, other: &TextureDeps) -> bool {
+        self.context == other.context
+            && self.inputs.len() == other.inputs.len()
+            && self.inputs.iter().all(|(key, values)| {
+                other.inputs.get(key).map_or(false, |other_values| {
+                    values.len() == other_values.len()
+                        && values
+                            .iter()
+                            .zip(other_values)
+                            .all(|(a, b)| same_input(a, b))
+                })
+            })
+    }
+}
+
+fn same_input(a: &Arc<Value>, b: &Arc<Value>) -> bool {
+    if Arc::ptr_eq(a, b) {
+        return true;
+    }
+    match (&**a, &**b) {
+        (Value::Any(a), Value::Any(b)) => Arc::ptr_eq(a, b),
+        (a, b) => a == b,
+    }
+}
+
+struct NodeTexture {
+    texture: TextureRef,
+    ty: TexType,
+    deps: Option<TextureDeps>,
+    requested: bool,
+}
+
+/// Textures owned by a single node, which persist across renders (see
+/// [crate::eval::NodeContext::cached_texture]).
+#[derive(Default)]
+pub(crate) struct NodeTextures {
+    textures: FnvHashMap<&'static str, NodeTexture>,
+    deps: Option<TextureDeps>,
+}
+
+impl NodeTextures {
+    /// Starts an evaluation with the given dependencies.
+    pub fn begin(&mut self, deps: TextureDeps) {
+        self.deps = Some(deps);
+        for entry in self.textures.values_mut() {
+            entry.requested = false;
+        }
+    }
+
+    /// Returns the texture for the given purpose, allocating a new one from the pool if there is
+    /// none yet or if its size or resolution doesn’t match.
+    ///
+    /// Also returns true if the texture was last written with the same dependencies. Otherwise, the
+    /// texture is marked as changed (see [TextureRef::mark_written]).
+    pub fn get(
+        &mut self,
+        pool: &mut TexturePool,
+        purpose: &'static str,
+        width: f32,
+        height: f32,
+        resolution: f32,
+        attachment: bool,
+    ) -> Result<(TextureRef, bool), Error> {
+        let ty = if attachment {
+            TexType::Attachment
+        } else {
+            TexType::Storage
+        };
+        let px_size = px_size(width, height, resolution);
+
+        let reusable = self.textures.get(purpose).map_or(false, |entry| {
+            entry.ty == ty
+                && entry.texture.px_size == px_size
+                && entry.texture.resolution == resolution
+        });
+        if !reusable {
+            // release the old texture first so the pool may hand it out again
+            self.textures.remove(purpose);
+            let texture = pool.texture_px(px_size[0], px_size[1], resolution, ty)?;
+            self.textures.insert(
+                purpose,
+                NodeTexture {
+                    texture,
+                    ty,
+                    deps: None,
+                    requested: false,
+                },
+            );
+        }
+
+        let entry = self.textures.get_mut(purpose).unwrap();
+        let is_valid = match (entry.deps.take(), &self.deps) {
+            (Some(prev), Some(deps)) => prev.matches(deps),
+            _ => false,
+        };
+        // newly allocated textures have already been marked by the pool
+        if !is_valid && reusable {
+            entry.texture.mark_written();
+        }
+        entry.requested = true;
+        Ok((entry.texture.clone(), is_valid))
+    }
+
+    /// Finishes an evaluation: textures requested since [NodeTextures::begin] are marked as
+    /// written with its dependencies, and all others are released.
+    pub fn commit(&mut self) {
+        let deps = self.deps.take();
+        self.textures.retain(|_, entry| entry.requested);
+        for entry in self.textures.values_mut() {
+            entry.deps = deps.clone();
+        }
+    }
+}
+
 /// A texture.
 #[derive(Debug, Clone)]
 pub enum Texture {
@@ -240,17 +518,43 @@ impl_image_view_access_for_texture! {
 
 /// A texture reference.
 ///
+/// Textures may be larger than requested, in which case only a region at the top left is used.
 /// Also contains a transform (not shared).
 #[derive(Clone)]
 pub struct TextureRef {
     texture_id: u64,
+    latest_version: Arc<AtomicUsize>,
+    version: usize,
     color: Texture,
     depth: Option<Texture>,
+    px_size: [u32; 2],
     transform: Matrix4<f32>,
     resolution: f32,
 }
 
 impl TextureRef {
+    /// Returns the texture ID, which is the same for all references to the same texture.
+    pub fn id(&self) -> u64 {
+        self.texture_id
+    }
+
+    /// Returns the version of the texture contents this reference was created with.
+    ///
+    /// The version is incremented whenever a node writes to the texture, so two references with
+    /// the same ID and version refer to the same pixels.
+    pub fn version(&self) -> usize {
+        self.version
+    }
+
+    /// Marks the texture contents as changed by incrementing its version.
+    ///
+    /// Textures obtained from [crate::eval::NodeContext] are already marked as changed if the
+    /// node is expected to write to them, so this only needs to be called when writing to other
+    /// textures.
+    pub fn mark_written(&mut self) {
+        self.version = self.latest_version.fetch_add(1, Ordering::SeqCst) + 1;
+    }
+
     /// Returns the color texture.
     pub fn color(&self) -> &Texture {
         &self.color
@@ -276,12 +580,40 @@ impl TextureRef {
         &mut self.transform
     }
 
-    /// Returns the texture size.
+    /// Returns the texture size (i.e. the size of the used region).
     pub fn size(&self) -> Vector2<f32> {
-        let [w, h] = self.color.dimensions();
+        let [w, h] = self.px_size;
         (w as f32 / self.resolution, h as f32 / self.resolution).into()
     }
 
+    /// Returns the size of the used region in pixels.
+    pub fn px_size(&self) -> [u32; 2] {
+        self.px_size
+    }
+
+    /// Returns the texture coordinates of the bottom right corner of the used region.
+    pub fn uv_scale(&self) -> Vector2<f32> {
+        let [w, h] = self.color.dimensions();
+        (
+            self.px_size[0] as f32 / w as f32,
+            self.px_size[1] as f32 / h as f32,
+        )
+            .into()
+    }
+
+    /// Returns a reference to a region of this texture with an identity transform, marked as
+    /// changed since it is about to be written to.
+    fn region(&self, px_width: u32, px_height: u32, resolution: f32) -> TextureRef {
+        let mut tex_ref = TextureRef {
+            px_size: [px_width, px_height],
+            transform: Matrix4::identity(),
+            resolution,
+            ..self.clone()
+        };
+        tex_ref.mark_written();
+        tex_ref
+    }
+
     /// Returns the resolution.
     pub fn resolution(&self) -> f32 {
         self.resolution
@@ -293,14 +625,24 @@ impl TextureRef {
 }
 
 impl PartialEq for TextureRef {
+    /// References are equal if they refer to the same region of the same texture with the same
+    /// contents, and have the same transform and resolution.
     fn eq(&self, other: &TextureRef) -> bool {
         self.texture_id == other.texture_id
+            && self.version == other.version
+            && self.px_size == other.px_size
+            && self.transform == other.transform
+            && self.resolution == other.resolution
     }
 }
 
 impl fmt::Debug for TextureRef {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "TextureRef {{ #{}, color, ", self.texture_id)?;
+        write!(
+            f,
+            "TextureRef {{ #{} v{}, color, ",
+            self.texture_id, self.version
+        )?;
         if self.depth.is_some() {
             write!(f, "depth, ")?;
         } else {
@@ -308,8 +650,8 @@ impl fmt::Debug for TextureRef {
         }
         write!(
             f,
-            "transform: {:?}, resolution: {} }}",
-            self.transform, self.resolution
+            "size: {:?}, transform: {:?}, resolution: {} }}",
+            self.px_size, self.transform, self.resolution
         )
     }
 }
@@ -102,11 +102,6 @@ impl<K: Ord, V, M> BSMap<K, V, M> {
     ) -> impl Iterator<Item = &'a (K, V)> {
         self.range_by(move |probe| f(probe).cmp(&key))
     }
-
-    /// Returns the key with the greatest [Ord].
-    pub fn greatest_key(&self) -> Option<&K> {
-        self.0.last().map(|&(ref k, _)| k)
-    }
 }
 
 impl<K: Ord, V> BSMap<K, V, SingleValue> {
@@ -186,14 +181,12 @@ impl<K: Ord, V: Eq> BSMap<K, V, ValueSet> {
     }
 
     pub fn remove_value(&mut self, key: &K, value: &V) -> Option<V> {
-        let (lower_bound, _) = self.range_bounds(|probe| probe.cmp(&key));
-        if let Some(index) = self
-            .0
+        let (lower_bound, upper_bound) = self.range_bounds(|probe| probe.cmp(&key));
+        if let Some(index) = self.0[lower_bound..upper_bound]
             .iter()
-            .skip(lower_bound)
             .position(|&(_, ref v)| v == value)
         {
-            Some(self.0.remove(index).1)
+            Some(self.0.remove(lower_bound + index).1)
         } else {
             None
         }